@@ -17,7 +17,7 @@
 
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use gtrusthop::{
-    core::{State, Domain, PlanItem, Multigoal},
+    core::{State, Domain, PlanItem, Multigoal, InternedState, StringInterner},
     planning::{PlannerBuilder, PlanningStrategy},
     examples::blocks_htn_example::create_blocks_htn_domain,
 };
@@ -185,9 +185,7 @@ fn create_scattered_initial_state(num_blocks: usize) -> State {
     
     // Create some initial stacks
     for (i, block) in blocks.iter().enumerate() {
-        if i == 0 {
-            state.set_var("pos", block, "table".into());
-        } else if i % 3 == 0 {
+        if i % 3 == 0 {
             state.set_var("pos", block, "table".into());
         } else {
             let base_block = &blocks[i - 1];
@@ -196,7 +194,7 @@ fn create_scattered_initial_state(num_blocks: usize) -> State {
     }
     
     // Set clear flags
-    for (_i, block) in blocks.iter().enumerate() {
+    for block in blocks.iter() {
         let is_clear = !blocks.iter().any(|b| {
             if let Some(pos) = state.get_var("pos", b) {
                 pos.as_str() == Some(block)
@@ -250,7 +248,7 @@ fn create_complex_initial_state(num_blocks: usize) -> State {
     }
     
     // Set clear flags
-    for (_i, block) in blocks.iter().enumerate() {
+    for block in blocks.iter() {
         let is_clear = !blocks.iter().any(|b| {
             if let Some(pos) = state.get_var("pos", b) {
                 pos.as_str() == Some(block)
@@ -441,8 +439,6 @@ fn create_interleaved_goal(num_blocks: usize) -> Multigoal {
     for (i, block) in blocks.iter().enumerate() {
         if i < 2 {
             goal.set_goal("pos", block, "table".into());
-        } else if i % 2 == 0 {
-            goal.set_goal("pos", block, blocks[i - 2].as_str().into());
         } else {
             goal.set_goal("pos", block, blocks[i - 2].as_str().into());
         }
@@ -768,11 +764,47 @@ fn backtracking_benchmarks(c: &mut Criterion) {
     group.finish();
 }
 
+/// Copy the state of a 16-block problem, plain and interned, then write to it as an action would
+fn state_copy_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("state_copy");
+    let state = create_scattered_initial_state(16);
+    let mut interner = StringInterner::new();
+    let interned = InternedState::from_state(&state, &mut interner);
+
+    group.bench_function("copy", |b| {
+        b.iter(|| black_box(black_box(&state).copy(None)))
+    });
+    group.bench_function("copy_and_write", |b| {
+        b.iter(|| {
+            let mut copy = black_box(&state).copy(None);
+            copy.set_var("holding", "hand", "a".into());
+            black_box(copy)
+        })
+    });
+    group.bench_function("intern", |b| {
+        b.iter(|| black_box(InternedState::from_state(black_box(&state), &mut interner)))
+    });
+    group.bench_function("interned_clone", |b| {
+        b.iter(|| black_box(black_box(&interned).clone()))
+    });
+    group.bench_function("interned_clone_and_write", |b| {
+        let mut interner = interner.clone();
+        b.iter(|| {
+            let mut copy = black_box(&interned).clone();
+            copy.set_var("holding", "hand", &"a".into(), &mut interner);
+            black_box(copy)
+        })
+    });
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     planning_strategy_benchmarks,
     memory_usage_benchmarks,
-    backtracking_benchmarks
+    backtracking_benchmarks,
+    state_copy_benchmarks
 );
 criterion_main!(benches);
 
@@ -791,9 +823,9 @@ fn create_sussman_initial_state(num_blocks: usize) -> State {
     state.set_var("clear", &blocks[2], true.into());  // C clear
     
     // Add remaining blocks on table if any
-    for i in 3..num_blocks {
-        state.set_var("pos", &blocks[i], "table".into());
-        state.set_var("clear", &blocks[i], true.into());
+    for block in blocks.iter().skip(3) {
+        state.set_var("pos", block, "table".into());
+        state.set_var("clear", block, true.into());
     }
     
     state.set_var("holding", "hand", false.into());
@@ -807,23 +839,15 @@ fn create_interleaved_initial_state(num_blocks: usize) -> State {
     
     // Create interleaved towers: a-c-e-g on table, b-d-f-h on table
     for (i, block) in blocks.iter().enumerate() {
-        if i % 2 == 0 {
-            if i == 0 {
-                state.set_var("pos", block, "table".into());
-            } else {
-                state.set_var("pos", block, blocks[i - 2].as_str().into());
-            }
+        if i < 2 {
+            state.set_var("pos", block, "table".into());
         } else {
-            if i == 1 {
-                state.set_var("pos", block, "table".into());
-            } else {
-                state.set_var("pos", block, blocks[i - 2].as_str().into());
-            }
+            state.set_var("pos", block, blocks[i - 2].as_str().into());
         }
     }
     
     // Set clear flags
-    for (_i, block) in blocks.iter().enumerate() {
+    for block in blocks.iter() {
         let is_clear = !blocks.iter().any(|b| {
             if let Some(pos) = state.get_var("pos", b) {
                 pos.as_str() == Some(block)
@@ -856,7 +880,7 @@ fn create_stress_initial_state(num_blocks: usize) -> State {
     }
     
     // Set clear flags
-    for (_i, block) in blocks.iter().enumerate() {
+    for block in blocks.iter() {
         let is_clear = !blocks.iter().any(|b| {
             if let Some(pos) = state.get_var("pos", b) {
                 pos.as_str() == Some(block)
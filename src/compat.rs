@@ -0,0 +1,24 @@
+//! Collections and prelude items shared by builds with and without `std`
+//!
+//! Modules import from here instead of `std` so that the same code builds on
+//! `alloc` and `hashbrown` when the `std` feature is disabled.
+
+#[cfg(feature = "std")]
+pub(crate) use std::collections::{HashMap, HashSet};
+#[cfg(not(feature = "std"))]
+pub(crate) use hashbrown::{HashMap, HashSet};
+
+/// Insertion-ordered map hashed like `HashMap`
+#[cfg(feature = "std")]
+pub(crate) type IndexMap<K, V> = indexmap::IndexMap<K, V>;
+/// Insertion-ordered map hashed like `HashMap`
+#[cfg(not(feature = "std"))]
+pub(crate) type IndexMap<K, V> = indexmap::IndexMap<K, V, hashbrown::DefaultHashBuilder>;
+
+/// Items of the `std` prelude that `core` doesn't provide
+pub(crate) mod prelude {
+    pub(crate) use alloc::boxed::Box;
+    pub(crate) use alloc::string::{String, ToString};
+    pub(crate) use alloc::vec::Vec;
+    pub(crate) use alloc::{format, vec};
+}
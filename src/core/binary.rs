@@ -0,0 +1,165 @@
+//! Compact binary encoding of plans, states and multigoals (`bincode` feature)
+//!
+//! Every encoding starts with a magic number and a format version, so data written
+//! by an incompatible version of GTRusthop is rejected instead of misread.
+
+use super::{Multigoal, PlanItem, State, StateValue};
+use crate::error::{GTRustHopError, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Magic number at the start of every encoding
+const MAGIC: &[u8; 4] = b"GTRH";
+
+/// Version of the encoding, bumped on any incompatible change
+const FORMAT_VERSION: u8 = 1;
+
+/// A state value in a form bincode can decode
+///
+/// bincode is not self-describing, so `serde_json::Value` cannot be decoded from it
+/// directly; values are stored with an explicit variant tag instead.
+#[derive(Serialize, Deserialize)]
+enum BinaryValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+    Array(Vec<BinaryValue>),
+    Object(Vec<(String, BinaryValue)>),
+}
+
+impl From<StateValue> for BinaryValue {
+    fn from(value: StateValue) -> Self {
+        match value {
+            StateValue::Null => Self::Null,
+            StateValue::Bool(b) => Self::Bool(b),
+            StateValue::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => Self::Int(i),
+                (None, Some(u)) => Self::UInt(u),
+                (None, None) => Self::Float(n.as_f64().unwrap_or_default()),
+            },
+            StateValue::String(s) => Self::String(s),
+            StateValue::Array(values) => Self::Array(values.into_iter().map(Self::from).collect()),
+            StateValue::Object(map) => Self::Object(map.into_iter().map(|(k, v)| (k, Self::from(v))).collect()),
+        }
+    }
+}
+
+impl From<BinaryValue> for StateValue {
+    fn from(value: BinaryValue) -> Self {
+        match value {
+            BinaryValue::Null => Self::Null,
+            BinaryValue::Bool(b) => Self::Bool(b),
+            BinaryValue::Int(i) => i.into(),
+            BinaryValue::UInt(u) => u.into(),
+            BinaryValue::Float(f) => f.into(),
+            BinaryValue::String(s) => Self::String(s),
+            BinaryValue::Array(values) => Self::Array(values.into_iter().map(Self::from).collect()),
+            BinaryValue::Object(entries) => Self::Object(entries.into_iter().map(|(k, v)| (k, Self::from(v))).collect()),
+        }
+    }
+}
+
+fn encode<T: Serialize + ?Sized>(item: &T) -> Result<Vec<u8>> {
+    let value = serde_json::to_value(item).map_err(|e| GTRustHopError::invalid_encoding(e.to_string()))?;
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(FORMAT_VERSION);
+    bytes.extend(bincode::serialize(&BinaryValue::from(value)).map_err(|e| GTRustHopError::invalid_encoding(e.to_string()))?);
+    Ok(bytes)
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let Some((header, body)) = bytes.split_first_chunk::<5>() else {
+        return Err(GTRustHopError::invalid_encoding("missing header"));
+    };
+    if &header[..4] != MAGIC {
+        return Err(GTRustHopError::invalid_encoding("not a GTRusthop encoding"));
+    }
+    if header[4] != FORMAT_VERSION {
+        return Err(GTRustHopError::invalid_encoding(format!(
+            "format version {} is not supported, expected {FORMAT_VERSION}",
+            header[4]
+        )));
+    }
+    let value: BinaryValue = bincode::deserialize(body)
+        .map_err(|e| GTRustHopError::invalid_encoding(e.to_string()))?;
+    serde_json::from_value(value.into()).map_err(|e| GTRustHopError::invalid_encoding(e.to_string()))
+}
+
+/// Encode a plan
+pub fn plan_to_bytes(plan: &[PlanItem]) -> Result<Vec<u8>> {
+    encode(plan)
+}
+
+/// Decode a plan encoded with `plan_to_bytes()`
+pub fn plan_from_bytes(bytes: &[u8]) -> Result<Vec<PlanItem>> {
+    decode(bytes)
+}
+
+/// Encode a state
+pub fn state_to_bytes(state: &State) -> Result<Vec<u8>> {
+    encode(state)
+}
+
+/// Decode a state encoded with `state_to_bytes()`
+pub fn state_from_bytes(bytes: &[u8]) -> Result<State> {
+    decode(bytes)
+}
+
+/// Encode a multigoal; a custom decomposer is not encoded
+pub fn multigoal_to_bytes(multigoal: &Multigoal) -> Result<Vec<u8>> {
+    encode(multigoal)
+}
+
+/// Decode a multigoal encoded with `multigoal_to_bytes()`
+pub fn multigoal_from_bytes(bytes: &[u8]) -> Result<Multigoal> {
+    decode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::string_value;
+
+    #[test]
+    fn test_binary_round_trip() -> Result<()> {
+        let plan = vec![
+            PlanItem::action("pickup", vec![string_value("a")]),
+            PlanItem::action("move", vec![(-3).into(), 2.5.into(), true.into(), StateValue::Null]),
+            PlanItem::task("deliver", vec![serde_json::json!({"items": ["a", "b"], "count": 2})]),
+        ];
+        assert_eq!(plan_from_bytes(&plan_to_bytes(&plan)?)?, plan);
+
+        let mut state = State::new("blocks");
+        state.set_var("pos", "a", string_value("table"));
+        state.set_var("clear", "a", true.into());
+        assert_eq!(state_from_bytes(&state_to_bytes(&state)?)?, state);
+
+        let mut multigoal = Multigoal::new("goal");
+        multigoal.set_goal("pos", "a", string_value("b"));
+        let decoded = multigoal_from_bytes(&multigoal_to_bytes(&multigoal)?)?;
+        assert_eq!(decoded.get_goal("pos", "a"), Some(&string_value("b")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_rejects_bad_header() -> Result<()> {
+        let mut bytes = plan_to_bytes(&[PlanItem::action("pickup", vec![string_value("a")])])?;
+        bytes[4] = FORMAT_VERSION + 1;
+        assert!(matches!(plan_from_bytes(&bytes), Err(GTRustHopError::InvalidEncoding { .. })));
+
+        assert!(plan_from_bytes(b"JSON[]").is_err());
+        assert!(plan_from_bytes(b"GT").is_err());
+        assert!(state_from_bytes(&plan_to_bytes(&[])?).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_reports_encoding_failures() {
+        // JSON object keys must be strings
+        let unencodable = std::collections::HashMap::from([((1, 2), "pair")]);
+        assert!(matches!(encode(&unencodable), Err(GTRustHopError::InvalidEncoding { .. })));
+    }
+}
@@ -1,14 +1,33 @@
 //! Domain representation for GTRusthop
 
-use super::{State, Multigoal, StateValue, TodoList};
-use crate::error::Result;
-use indexmap::IndexMap;
-use std::sync::Arc;
+use crate::compat::prelude::*;
+use super::{DomainSignature, State, Multigoal, PlanItem, StateValue, TodoList};
+use crate::error::{GTRustHopError, Result};
+use crate::compat::{HashSet, IndexMap};
+use alloc::collections::BTreeSet;
+use core::any::TypeId;
+use alloc::sync::Arc;
 
 /// Type alias for action functions
 /// Actions take a mutable state and arguments, return Option<State> (None if not applicable)
 pub type ActionFn = Arc<dyn Fn(&mut State, &[StateValue]) -> Option<State> + Send + Sync>;
 
+/// Type alias for action precondition functions
+/// Preconditions check if an action applies to a state and arguments, without changing it
+pub type ActionPrecondFn = Arc<dyn Fn(&State, &[StateValue]) -> bool + Send + Sync>;
+
+/// Type alias for action effect functions
+/// Effects change a state an action's precondition holds for, see `Domain::declare_action_with_precond()`
+pub type ActionEffectFn = Arc<dyn Fn(&mut State, &[StateValue]) + Send + Sync>;
+
+/// Type alias for actions reading rigid facts
+/// Like actions, but also given the planner's rigid state, see `Domain::declare_action_with_rigid()`
+pub type RigidActionFn = Arc<dyn Fn(&mut State, &State, &[StateValue]) -> Option<State> + Send + Sync>;
+
+/// Type alias for nondeterministic action functions
+/// Nondeterministic actions return every state they may lead to, none if not applicable
+pub type NondetActionFn = Arc<dyn Fn(&State, &[StateValue]) -> Vec<State> + Send + Sync>;
+
 /// Type alias for command functions  
 /// Commands are like actions but for execution (not planning)
 pub type CommandFn = Arc<dyn Fn(&mut State, &[StateValue]) -> Option<State> + Send + Sync>;
@@ -21,10 +40,31 @@ pub type TaskMethodFn = Arc<dyn Fn(&State, &[StateValue]) -> Option<TodoList> +
 /// Unigoal methods take a state, arg, and desired value, return Option<TodoList>
 pub type UnigoalMethodFn = Arc<dyn Fn(&State, &str, &StateValue) -> Option<TodoList> + Send + Sync>;
 
+/// Type alias for relational goal method functions
+/// Relational goal methods receive the relation arguments and desired value and return subtasks
+pub type RelgoalMethodFn = Arc<dyn Fn(&State, &[StateValue], &StateValue) -> Option<TodoList> + Send + Sync>;
+
 /// Type alias for multigoal method functions
 /// Multigoal methods take a state and multigoal, return Option<TodoList>
 pub type MultigoalMethodFn = Arc<dyn Fn(&State, &Multigoal) -> Option<TodoList> + Send + Sync>;
 
+/// Type alias for action observer functions
+/// Observers receive the action name, arguments, state before the action and
+/// resulting state, or `None` if the action was not applicable
+pub type ActionObserverFn = Arc<dyn Fn(&str, &[StateValue], &State, Option<&State>) + Send + Sync>;
+
+/// Type alias for state invariant functions
+/// Invariants return `Err` with a description if a state is malformed
+pub type StateInvariantFn = Arc<dyn Fn(&State) -> core::result::Result<(), String> + Send + Sync>;
+
+/// Type alias for resource delta functions
+/// Resource deltas receive the state before an action and its arguments, and return
+/// how much the action adds to (or, if negative, takes from) a resource
+pub type ResourceDeltaFn = Arc<dyn Fn(&State, &[StateValue]) -> f64 + Send + Sync>;
+
+/// Weight of task methods declared without one, see `Domain::declare_task_method_weighted()`
+pub const DEFAULT_METHOD_WEIGHT: f64 = 1.0;
+
 /// Represents a planning domain containing actions, methods, and commands
 #[derive(Clone)]
 pub struct Domain {
@@ -38,8 +78,48 @@ pub struct Domain {
     task_methods: IndexMap<String, Vec<TaskMethodFn>>,
     /// Map of state variable names to lists of unigoal method functions
     unigoal_methods: IndexMap<String, Vec<UnigoalMethodFn>>,
+    /// Map of relation names to lists of relational goal method functions
+    relgoal_methods: IndexMap<String, Vec<RelgoalMethodFn>>,
     /// List of multigoal method functions
     multigoal_methods: Vec<MultigoalMethodFn>,
+    /// State variables handled by each multigoal method, `None` for all of them
+    multigoal_method_vars: Vec<Option<Vec<String>>>,
+    /// Map of task names taking a multigoal id as first argument to the ids they know
+    multigoal_tasks: IndexMap<String, HashSet<String>>,
+    /// Whether planners should verify goals for this domain, unless overridden
+    default_verify_goals: Option<bool>,
+    /// Called by the planner after every action attempt
+    action_observer: Option<ActionObserverFn>,
+    /// Checks planners run on every initial state, in the order they were added
+    state_invariants: Vec<StateInvariantFn>,
+    /// Map of action names to costs, for actions not costing 1
+    action_costs: IndexMap<String, f64>,
+    /// Map of action names to their tags, see `PlannerBuilder::prefer_tags()`
+    action_tags: IndexMap<String, Vec<String>>,
+    /// Map of action names to the resources they change and by how much
+    resource_deltas: IndexMap<String, Vec<(String, ResourceDeltaFn)>>,
+    /// Map of action names to the state variables they read or write
+    action_variables: IndexMap<String, Vec<String>>,
+    /// Map of task names to the weights of their methods, in method order
+    task_method_weights: IndexMap<String, Vec<f64>>,
+    /// Map of task names to the number of arguments their methods expect
+    task_arities: IndexMap<String, usize>,
+    /// Map of action names to their separately declared preconditions
+    action_preconditions: IndexMap<String, ActionPrecondFn>,
+    /// Map of action names to the effects applied once their precondition holds
+    action_effects: IndexMap<String, ActionEffectFn>,
+    /// Map of action names to all their outcomes, for nondeterministic actions
+    nondet_actions: IndexMap<String, NondetActionFn>,
+    /// Map of action names to their functions, for actions reading rigid facts
+    rigid_actions: IndexMap<String, RigidActionFn>,
+    /// Action and command names declared more than once, in declaration order
+    redeclared: Vec<String>,
+    /// Types of the zero-sized methods declared for each task, variable or relation
+    method_types: HashSet<(String, TypeId)>,
+    /// Names of the tasks, variables and relations given the same method twice, in declaration order
+    redeclared_methods: Vec<String>,
+    /// Revision counter, incremented on every declaration or setting change
+    revision: u64,
     /// Copy counter for generating unique names
     copy_counter: usize,
 }
@@ -47,22 +127,55 @@ pub struct Domain {
 impl Domain {
     /// Create a new domain with the given name
     pub fn new(name: impl Into<String>) -> Self {
-        let mut domain = Self {
-            name: name.into(),
-            actions: IndexMap::new(),
-            commands: IndexMap::new(),
-            task_methods: IndexMap::new(),
-            unigoal_methods: IndexMap::new(),
-            multigoal_methods: Vec::new(),
-            copy_counter: 0,
-        };
+        let mut domain = Self::new_without_builtins(name);
 
         // Add built-in verification methods
         domain.add_builtin_methods();
         domain
     }
 
+    /// Create a new domain without the built-in `_verify_g` and `_verify_mg` methods
+    ///
+    /// The instance-based `Planner` checks verification tasks itself, so it plans
+    /// with such a domain as with any other; only the legacy strategies need them.
+    pub fn new_without_builtins(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            actions: IndexMap::default(),
+            commands: IndexMap::default(),
+            task_methods: IndexMap::default(),
+            unigoal_methods: IndexMap::default(),
+            relgoal_methods: IndexMap::default(),
+            multigoal_methods: Vec::new(),
+            multigoal_method_vars: Vec::new(),
+            multigoal_tasks: IndexMap::default(),
+            default_verify_goals: None,
+            action_observer: None,
+            state_invariants: Vec::new(),
+            action_costs: IndexMap::default(),
+            action_tags: IndexMap::default(),
+            resource_deltas: IndexMap::default(),
+            action_variables: IndexMap::default(),
+            task_method_weights: IndexMap::default(),
+            task_arities: IndexMap::default(),
+            action_preconditions: IndexMap::default(),
+            action_effects: IndexMap::default(),
+            nondet_actions: IndexMap::default(),
+            rigid_actions: IndexMap::default(),
+            redeclared: Vec::new(),
+            method_types: HashSet::new(),
+            redeclared_methods: Vec::new(),
+            revision: 0,
+            copy_counter: 0,
+        }
+    }
+
     /// Add built-in verification methods
+    ///
+    /// The instance-based `Planner` intercepts `_verify_g` and `_verify_mg` tasks and
+    /// reports an unmet goal as `MethodVerificationFailed` or
+    /// `MultigoalVerificationFailed`, so a verification failure never looks like an
+    /// ordinary dead end. These methods remain for the legacy planning strategies.
     fn add_builtin_methods(&mut self) {
         // Add _verify_g task method
         let verify_g_method: TaskMethodFn = Arc::new(|state, args| {
@@ -77,8 +190,8 @@ impl Domain {
                     if state.satisfies_unigoal(var_name, arg, desired_val) {
                         Some(vec![]) // Success, no subtasks
                     } else {
-                        // In a real implementation, this would raise an exception
-                        // For now, we'll return None to indicate failure
+                        // Only reached by the legacy strategies, which treat this as
+                        // an inapplicable method; see `add_builtin_methods()`
                         None
                     }
                 } else {
@@ -117,8 +230,9 @@ impl Domain {
         F: Fn(&mut State, &[StateValue]) -> Option<State> + Send + Sync + 'static,
     {
         for (name, action_fn) in actions {
-            self.actions.insert(name, Arc::new(action_fn));
+            self.insert_action(name, Arc::new(action_fn));
         }
+        self.revision += 1;
         Ok(())
     }
 
@@ -127,18 +241,225 @@ impl Domain {
     where
         F: Fn(&mut State, &[StateValue]) -> Option<State> + Send + Sync + 'static,
     {
-        self.actions.insert(name.into(), Arc::new(action_fn));
+        self.insert_action(name.into(), Arc::new(action_fn));
+        self.revision += 1;
+        Ok(())
+    }
+
+    /// Declare an action as a precondition and an effect
+    ///
+    /// The action applies the effect when the precondition holds, like an action
+    /// declared with `declare_action()`. The precondition can also be checked on
+    /// its own, see `action_applicable()`. The planner checks it once per attempt:
+    /// it skips copying the state for actions that don't apply and only runs the
+    /// effect for those that do.
+    pub fn declare_action_with_precond<P, E>(&mut self, name: impl Into<String>, precond: P, effect: E) -> Result<()>
+    where
+        P: Fn(&State, &[StateValue]) -> bool + Send + Sync + 'static,
+        E: Fn(&mut State, &[StateValue]) + Send + Sync + 'static,
+    {
+        let name = name.into();
+        let precond: ActionPrecondFn = Arc::new(precond);
+        let effect: ActionEffectFn = Arc::new(effect);
+        let (check, apply) = (Arc::clone(&precond), Arc::clone(&effect));
+        self.declare_action(name.clone(), move |state: &mut State, args: &[StateValue]| {
+            if !check(state, args) {
+                return None;
+            }
+            apply(state, args);
+            Some(state.clone())
+        })?;
+        self.action_preconditions.insert(name.clone(), precond);
+        self.action_effects.insert(name, effect);
         Ok(())
     }
 
+    /// Get the precondition of an action declared with `declare_action_with_precond()`
+    pub fn action_precondition(&self, name: &str) -> Option<&ActionPrecondFn> {
+        self.action_preconditions.get(name)
+    }
+
+    /// Get the effect of an action declared with `declare_action_with_precond()`
+    ///
+    /// Unlike the action itself, the effect doesn't check the precondition.
+    pub fn action_effect(&self, name: &str) -> Option<&ActionEffectFn> {
+        self.action_effects.get(name)
+    }
+
+    /// Check if an action applies to a state, without changing it
+    ///
+    /// Uses the action's precondition if it has one, and otherwise applies the
+    /// action to a copy of the state. Unknown actions never apply.
+    pub fn action_applicable(&self, name: &str, state: &State, args: &[StateValue]) -> bool {
+        if let Some(precond) = self.action_preconditions.get(name) {
+            return precond(state, args);
+        }
+        self.actions.get(name).is_some_and(|action_fn| action_fn(&mut state.clone(), args).is_some())
+    }
+
+    /// Declare a single action, failing if an action with that name already exists
+    ///
+    /// `declare_action()` replaces an existing action of the same name and only
+    /// records the redeclaration (see `redeclared_names()`); this returns
+    /// `GTRustHopError::DuplicateAction` and leaves the existing action in place.
+    pub fn declare_action_checked<F>(&mut self, name: impl Into<String>, action_fn: F) -> Result<()>
+    where
+        F: Fn(&mut State, &[StateValue]) -> Option<State> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        if self.actions.contains_key(&name) {
+            return Err(GTRustHopError::duplicate_action(name));
+        }
+        self.declare_action(name, action_fn)
+    }
+
+    fn insert_action(&mut self, name: String, action_fn: ActionFn) {
+        self.action_preconditions.shift_remove(&name);
+        self.action_effects.shift_remove(&name);
+        self.nondet_actions.shift_remove(&name);
+        self.rigid_actions.shift_remove(&name);
+        if self.actions.insert(name.clone(), action_fn).is_some() {
+            self.redeclared.push(name);
+        }
+    }
+
+    /// Declare an action that may lead to any of several states
+    ///
+    /// `outcomes_fn` returns every possible resulting state, or none if the
+    /// action doesn't apply. Applied as an ordinary action, for example by
+    /// `apply_plan()` or a planner outside conformant mode (see
+    /// `PlannerBuilder::with_conformant()`), it leads to the first outcome.
+    pub fn declare_nondet_action<F>(&mut self, name: impl Into<String>, outcomes_fn: F) -> Result<()>
+    where
+        F: Fn(&State, &[StateValue]) -> Vec<State> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        let outcomes: NondetActionFn = Arc::new(outcomes_fn);
+        let first = Arc::clone(&outcomes);
+        self.declare_action(name.clone(), move |state: &mut State, args: &[StateValue]| {
+            first(state, args).into_iter().next()
+        })?;
+        self.nondet_actions.insert(name, outcomes);
+        Ok(())
+    }
+
+    /// Declare an action that also reads facts that never change
+    ///
+    /// Planners pass `action_fn` the rigid state set with
+    /// `PlannerBuilder::with_rigid_state()`, such as distances between locations.
+    /// Applied without a planner, for example by `apply_plan()`, the action gets
+    /// an empty rigid state.
+    pub fn declare_action_with_rigid<F>(&mut self, name: impl Into<String>, action_fn: F) -> Result<()>
+    where
+        F: Fn(&mut State, &State, &[StateValue]) -> Option<State> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        let rigid_fn: RigidActionFn = Arc::new(action_fn);
+        let without_rigid = Arc::clone(&rigid_fn);
+        self.declare_action(name.clone(), move |state: &mut State, args: &[StateValue]| {
+            without_rigid(state, &State::new("rigid"), args)
+        })?;
+        self.rigid_actions.insert(name, rigid_fn);
+        Ok(())
+    }
+
+    /// Get the function of an action declared with `declare_action_with_rigid()`
+    pub fn rigid_action(&self, name: &str) -> Option<&RigidActionFn> {
+        self.rigid_actions.get(name)
+    }
+
+    /// Check if an action was declared with `declare_nondet_action()`
+    pub fn is_nondet_action(&self, name: &str) -> bool {
+        self.nondet_actions.contains_key(name)
+    }
+
+    /// Get every state an action may lead to from `state`
+    ///
+    /// Deterministic actions have at most one outcome; unknown actions have none.
+    pub fn action_outcomes(&self, name: &str, state: &State, args: &[StateValue]) -> Vec<State> {
+        if let Some(outcomes_fn) = self.nondet_actions.get(name) {
+            return outcomes_fn(state, args);
+        }
+        self.get_action(name)
+            .and_then(|action_fn| action_fn(&mut state.copy(None), args))
+            .into_iter()
+            .collect()
+    }
+
+    fn insert_command(&mut self, name: String, command_fn: CommandFn) {
+        if self.commands.insert(name.clone(), command_fn).is_some() {
+            self.redeclared.push(name);
+        }
+    }
+
+    /// Declare the state variables an action reads or writes
+    ///
+    /// Actions are closures, so the domain cannot find these variables by itself;
+    /// they are only used by `declared_variables()`.
+    pub fn declare_action_variables(&mut self, action: impl Into<String>, variables: &[&str]) {
+        self.action_variables
+            .entry(action.into())
+            .or_default()
+            .extend(variables.iter().map(|var| var.to_string()));
+        self.revision += 1;
+    }
+
+    /// Get the state variables this domain is declared to use
+    ///
+    /// Gathers the variables declared for actions with `declare_action_variables()`,
+    /// the variables with unigoal methods, the relations with relational goal methods,
+    /// and the variables of multigoal methods declared with `declare_multigoal_method_for()`.
+    pub fn declared_variables(&self) -> BTreeSet<String> {
+        self.action_variables.values().flatten()
+            .chain(self.unigoal_methods.keys())
+            .chain(self.relgoal_methods.keys())
+            .chain(self.multigoal_method_vars.iter().flatten().flatten())
+            .cloned()
+            .collect()
+    }
+
+    /// Get the declared variables (see `declared_variables()`) missing from a state, sorted
+    pub fn undefined_in_state(&self, state: &State) -> Vec<String> {
+        let defined: HashSet<&String> = state.var_names().into_iter().collect();
+        self.declared_variables().into_iter().filter(|var| !defined.contains(var)).collect()
+    }
+
+    /// Get the action and command names that were declared more than once
+    pub fn redeclared_names(&self) -> &[String] {
+        &self.redeclared
+    }
+
+    /// Get the tasks, variables and relations a method was declared for twice
+    ///
+    /// Multigoal methods are reported as `"multigoal"`.
+    ///
+    /// Methods carry no name, so a method is identified by its type, which only
+    /// pins down the function for functions and closures capturing nothing. Two
+    /// kinds of duplicates are never reported: methods coerced to function pointers,
+    /// as when a `vec![m_a, m_b]` of different functions is declared, and closures
+    /// capturing values, such as those built by a helper function.
+    pub fn redeclared_method_names(&self) -> &[String] {
+        &self.redeclared_methods
+    }
+
+    /// Remember that a method of type `F` was declared for `name`
+    ///
+    /// Only zero-sized method types are remembered, see `redeclared_method_names()`.
+    fn note_method<F: 'static>(&mut self, name: &str) {
+        if core::mem::size_of::<F>() == 0 && !self.method_types.insert((name.to_string(), TypeId::of::<F>())) {
+            self.redeclared_methods.push(name.to_string());
+        }
+    }
+
     /// Declare commands in this domain
     pub fn declare_commands<F>(&mut self, commands: Vec<(String, F)>) -> Result<()>
     where
         F: Fn(&mut State, &[StateValue]) -> Option<State> + Send + Sync + 'static,
     {
         for (name, command_fn) in commands {
-            self.commands.insert(name, Arc::new(command_fn));
+            self.insert_command(name, Arc::new(command_fn));
         }
+        self.revision += 1;
         Ok(())
     }
 
@@ -147,7 +468,8 @@ impl Domain {
     where
         F: Fn(&mut State, &[StateValue]) -> Option<State> + Send + Sync + 'static,
     {
-        self.commands.insert(name.into(), Arc::new(command_fn));
+        self.insert_command(name.into(), Arc::new(command_fn));
+        self.revision += 1;
         Ok(())
     }
 
@@ -157,18 +479,41 @@ impl Domain {
         F: Fn(&State, &[StateValue]) -> Option<TodoList> + Send + Sync + 'static,
     {
         let task_name = task_name.into();
-        let method_fns: Vec<TaskMethodFn> = methods.into_iter()
-            .map(|f| Arc::new(f) as TaskMethodFn)
-            .collect();
-
-        if let Some(existing_methods) = self.task_methods.get_mut(&task_name) {
-            existing_methods.extend(method_fns);
-        } else {
-            self.task_methods.insert(task_name, method_fns);
+        for method in methods {
+            self.note_method::<F>(&task_name);
+            self.insert_task_method(&task_name, DEFAULT_METHOD_WEIGHT, Arc::new(method));
         }
+        self.revision += 1;
+        Ok(())
+    }
+
+    /// Declare a task method tried before the task's methods of lower weight
+    ///
+    /// The planner tries a task's methods by descending weight, and methods of
+    /// equal weight in declaration order; methods declared without a weight have
+    /// weight `DEFAULT_METHOD_WEIGHT`. `get_task_methods()` returns them in that order.
+    pub fn declare_task_method_weighted<F>(&mut self, task_name: impl Into<String>, weight: f64, method_fn: F) -> Result<()>
+    where
+        F: Fn(&State, &[StateValue]) -> Option<TodoList> + Send + Sync + 'static,
+    {
+        let task_name = task_name.into();
+        self.note_method::<F>(&task_name);
+        self.insert_task_method(&task_name, weight, Arc::new(method_fn));
+        self.revision += 1;
         Ok(())
     }
 
+    /// Insert a task method after all methods of the task with at least its weight
+    fn insert_task_method(&mut self, task_name: &str, weight: f64, method_fn: TaskMethodFn) {
+        let methods = self.task_methods.entry(task_name.to_string()).or_default();
+        let weights = self.task_method_weights
+            .entry(task_name.to_string())
+            .or_insert_with(|| vec![DEFAULT_METHOD_WEIGHT; methods.len()]);
+        let index = weights.iter().position(|other| *other < weight).unwrap_or(weights.len());
+        methods.insert(index, method_fn);
+        weights.insert(index, weight);
+    }
+
     /// Declare a single task method
     pub fn declare_task_method<F>(&mut self, task_name: impl Into<String>, method_fn: F) -> Result<()>
     where
@@ -177,12 +522,34 @@ impl Domain {
         self.declare_task_methods(task_name, vec![method_fn])
     }
 
+    /// Declare a task method for a task that always takes `arity` arguments
+    ///
+    /// The planner then fails with `GTRustHopError::TaskArityMismatch` when the
+    /// task is called with a different number of arguments, instead of every
+    /// method silently not applying.
+    pub fn declare_task_method_arity<F>(&mut self, task_name: impl Into<String>, arity: usize, method_fn: F) -> Result<()>
+    where
+        F: Fn(&State, &[StateValue]) -> Option<TodoList> + Send + Sync + 'static,
+    {
+        let task_name = task_name.into();
+        self.task_arities.insert(task_name.clone(), arity);
+        self.declare_task_method(task_name, method_fn)
+    }
+
+    /// Get the number of arguments a task was declared to take, if any
+    pub fn task_arity(&self, task_name: &str) -> Option<usize> {
+        self.task_arities.get(task_name).copied()
+    }
+
     /// Declare unigoal methods for a specific state variable
     pub fn declare_unigoal_methods<F>(&mut self, var_name: impl Into<String>, methods: Vec<F>) -> Result<()>
     where
         F: Fn(&State, &str, &StateValue) -> Option<TodoList> + Send + Sync + 'static,
     {
         let var_name = var_name.into();
+        for _ in &methods {
+            self.note_method::<F>(&var_name);
+        }
         let method_fns: Vec<UnigoalMethodFn> = methods.into_iter()
             .map(|f| Arc::new(f) as UnigoalMethodFn)
             .collect();
@@ -192,6 +559,7 @@ impl Domain {
         } else {
             self.unigoal_methods.insert(var_name, method_fns);
         }
+        self.revision += 1;
         Ok(())
     }
 
@@ -203,16 +571,105 @@ impl Domain {
         self.declare_unigoal_methods(var_name, vec![method_fn])
     }
 
+    /// Declare a unigoal method that achieves `(var_name, arg, value)` with exactly one action
+    ///
+    /// The action is returned unchecked, so its own precondition decides
+    /// whether the goal can be achieved this way.
+    pub fn declare_unigoal_action<F>(&mut self, var_name: impl Into<String>, action_fn: F) -> Result<()>
+    where
+        F: Fn(&str, &StateValue) -> PlanItem + Send + Sync + 'static,
+    {
+        self.declare_unigoal_method(var_name, move |_state: &State, arg: &str, value: &StateValue| {
+            Some(vec![action_fn(arg, value)])
+        })
+    }
+
+    /// Declare relational goal methods for a relation
+    pub fn declare_relgoal_methods<F>(&mut self, rel: impl Into<String>, methods: Vec<F>) -> Result<()>
+    where
+        F: Fn(&State, &[StateValue], &StateValue) -> Option<TodoList> + Send + Sync + 'static,
+    {
+        let rel = rel.into();
+        for _ in &methods {
+            self.note_method::<F>(&rel);
+        }
+        let method_fns: Vec<RelgoalMethodFn> = methods.into_iter()
+            .map(|f| Arc::new(f) as RelgoalMethodFn)
+            .collect();
+
+        if let Some(existing_methods) = self.relgoal_methods.get_mut(&rel) {
+            existing_methods.extend(method_fns);
+        } else {
+            self.relgoal_methods.insert(rel, method_fns);
+        }
+        self.revision += 1;
+        Ok(())
+    }
+
+    /// Declare a single relational goal method
+    pub fn declare_relgoal_method<F>(&mut self, rel: impl Into<String>, method_fn: F) -> Result<()>
+    where
+        F: Fn(&State, &[StateValue], &StateValue) -> Option<TodoList> + Send + Sync + 'static,
+    {
+        self.declare_relgoal_methods(rel, vec![method_fn])
+    }
+
+    /// Declare a task whose first argument is the id of a multigoal
+    ///
+    /// Task methods can only report "not applicable", so a method that looks up a
+    /// multigoal by id can't tell the planner the id is unknown. Declaring the task
+    /// with the ids its methods know lets the planner report an unknown id as
+    /// `GTRustHopError::UnknownMultigoal` instead of a plain planning failure.
+    /// Calling this again for the same task adds to its known ids.
+    pub fn declare_multigoal_task<I, S>(&mut self, task_name: impl Into<String>, ids: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.multigoal_tasks
+            .entry(task_name.into())
+            .or_default()
+            .extend(ids.into_iter().map(Into::into));
+        self.revision += 1;
+        Ok(())
+    }
+
+    /// Get the multigoal ids known to a task declared with `declare_multigoal_task()`
+    pub fn multigoal_task_ids(&self, task_name: &str) -> Option<&HashSet<String>> {
+        self.multigoal_tasks.get(task_name)
+    }
+
     /// Declare multigoal methods
     pub fn declare_multigoal_methods<F>(&mut self, methods: Vec<F>) -> Result<()>
     where
         F: Fn(&State, &Multigoal) -> Option<TodoList> + Send + Sync + 'static,
     {
+        for _ in &methods {
+            self.note_method::<F>("multigoal");
+        }
         let method_fns: Vec<MultigoalMethodFn> = methods.into_iter()
             .map(|f| Arc::new(f) as MultigoalMethodFn)
             .collect();
 
+        self.multigoal_method_vars.extend(method_fns.iter().map(|_| None));
         self.multigoal_methods.extend(method_fns);
+        self.revision += 1;
+        Ok(())
+    }
+
+    /// Declare a multigoal method that only handles goals on some state variables
+    ///
+    /// The method is only tried for multigoals with a goal on at least one of
+    /// `relevant_vars`. Methods declared with `declare_multigoal_method()` are tried
+    /// for every multigoal.
+    pub fn declare_multigoal_method_for<F>(&mut self, relevant_vars: &[&str], method_fn: F) -> Result<()>
+    where
+        F: Fn(&State, &Multigoal) -> Option<TodoList> + Send + Sync + 'static,
+    {
+        self.note_method::<F>("multigoal");
+        self.multigoal_methods.push(Arc::new(method_fn));
+        self.multigoal_method_vars.push(Some(relevant_vars.iter().map(|var| var.to_string()).collect()));
+        self.revision += 1;
         Ok(())
     }
 
@@ -224,11 +681,121 @@ impl Domain {
         self.declare_multigoal_methods(vec![method_fn])
     }
 
+    /// Get the multigoal methods relevant to a multigoal, in declaration order
+    ///
+    /// See `declare_multigoal_method_for()`.
+    pub fn relevant_multigoal_methods(&self, multigoal: &Multigoal) -> Vec<&MultigoalMethodFn> {
+        self.multigoal_methods.iter()
+            .zip(&self.multigoal_method_vars)
+            .filter(|(_, vars)| vars.as_ref().is_none_or(|vars| {
+                vars.iter().any(|var| multigoal.get_goal_map(var).is_some_and(|goals| !goals.is_empty()))
+            }))
+            .map(|(method, _)| method)
+            .collect()
+    }
+
     /// Get an action by name
     pub fn get_action(&self, name: &str) -> Option<&ActionFn> {
         self.actions.get(name)
     }
 
+    /// Set the cost of an action, used by `Planner::find_optimal_plan()`
+    pub fn set_action_cost(&mut self, name: impl Into<String>, cost: f64) {
+        self.action_costs.insert(name.into(), cost);
+        self.revision += 1;
+    }
+
+    /// Get the cost of an action; actions without a declared cost cost 1
+    pub fn action_cost(&self, name: &str) -> f64 {
+        self.action_costs.get(name).copied().unwrap_or(1.0)
+    }
+
+    /// Tag an action, adding to any tags it already has
+    pub fn tag_action(&mut self, name: impl Into<String>, tags: &[&str]) {
+        let action_tags = self.action_tags.entry(name.into()).or_default();
+        for tag in tags {
+            if !action_tags.iter().any(|existing| existing == tag) {
+                action_tags.push(tag.to_string());
+            }
+        }
+        self.revision += 1;
+    }
+
+    /// Get the tags of an action, in the order they were added
+    pub fn action_tags(&self, name: &str) -> &[String] {
+        self.action_tags.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Declare how an action changes a planner resource (see `PlannerBuilder::with_resource()`)
+    ///
+    /// An action may change several resources; the planner prunes any action that
+    /// would leave a resource negative.
+    pub fn declare_action_resource_delta<F>(&mut self, action: impl Into<String>, resource: impl Into<String>, delta_fn: F)
+    where
+        F: Fn(&State, &[StateValue]) -> f64 + Send + Sync + 'static,
+    {
+        self.resource_deltas
+            .entry(action.into())
+            .or_default()
+            .push((resource.into(), Arc::new(delta_fn)));
+        self.revision += 1;
+    }
+
+    /// Get the resource deltas declared for an action
+    pub fn resource_deltas(&self, action: &str) -> &[(String, ResourceDeltaFn)] {
+        self.resource_deltas.get(action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Get the total cost of the actions in a plan
+    pub fn plan_cost(&self, plan: &[PlanItem]) -> f64 {
+        plan.iter().map(|item| self.action_cost(item.name())).sum()
+    }
+
+    /// Apply the actions of a plan to a state, in order
+    ///
+    /// Returns the final state, or `GTRustHopError::PlanExecutionFailed` with the
+    /// index of the first action that is unknown or not applicable. A plan item
+    /// that is not an action yields `GTRustHopError::InvalidItemType`; use
+    /// `apply_plan_skipping_non_actions()` to ignore such items instead.
+    pub fn apply_plan(&self, state: State, plan: &[PlanItem]) -> Result<State> {
+        self.replay_plan(state, plan, false, |_| {})
+    }
+
+    /// Apply the actions of a plan to a state, ignoring items that are not actions
+    pub fn apply_plan_skipping_non_actions(&self, state: State, plan: &[PlanItem]) -> Result<State> {
+        self.replay_plan(state, plan, true, |_| {})
+    }
+
+    /// Apply the actions of a plan and capture every intermediate state
+    ///
+    /// Returns `[s0, s1, ..., sn]`, where `s0` is `initial` and `s_i` is the state
+    /// after the i-th action. Fails like `apply_plan()`.
+    pub fn replay_with_states(&self, initial: State, plan: &[PlanItem]) -> Result<Vec<State>> {
+        let mut states = vec![initial.clone()];
+        self.replay_plan(initial, plan, false, |state| states.push(state.clone()))?;
+        Ok(states)
+    }
+
+    fn replay_plan(&self, state: State, plan: &[PlanItem], skip_non_actions: bool, mut visit: impl FnMut(&State)) -> Result<State> {
+        let mut current = state;
+        for (index, item) in plan.iter().enumerate() {
+            let PlanItem::Action(action_name, args) = item else {
+                if skip_non_actions {
+                    continue;
+                }
+                return Err(GTRustHopError::invalid_item_type(item.to_string(), index));
+            };
+            let action_fn = self.get_action(action_name)
+                .ok_or_else(|| GTRustHopError::plan_execution_failed(index))?;
+
+            let mut state_copy = current.copy(None);
+            current = action_fn(&mut state_copy, args)
+                .ok_or_else(|| GTRustHopError::plan_execution_failed(index))?;
+            visit(&current);
+        }
+        Ok(current)
+    }
+
     /// Get a command by name
     pub fn get_command(&self, name: &str) -> Option<&CommandFn> {
         self.commands.get(name)
@@ -244,6 +811,11 @@ impl Domain {
         self.unigoal_methods.get(var_name)
     }
 
+    /// Get relational goal methods for a relation
+    pub fn get_relgoal_methods(&self, rel: &str) -> Option<&Vec<RelgoalMethodFn>> {
+        self.relgoal_methods.get(rel)
+    }
+
     /// Get all multigoal methods
     pub fn get_multigoal_methods(&self) -> &Vec<MultigoalMethodFn> {
         &self.multigoal_methods
@@ -269,6 +841,88 @@ impl Domain {
         self.unigoal_methods.contains_key(var_name)
     }
 
+    /// Declare whether planners should verify goals for this domain by default
+    ///
+    /// Domain authors whose methods are known to achieve their goals can turn
+    /// verification off. `PlannerBuilder::with_goal_verification()` still takes
+    /// precedence over this setting.
+    pub fn set_default_verify_goals(&mut self, verify: bool) {
+        self.default_verify_goals = Some(verify);
+        self.revision += 1;
+    }
+
+    /// Observe every action the planner attempts in this domain
+    ///
+    /// The observer is called after each action attempt with the action name,
+    /// arguments, state before the action and the resulting state (`None` if the
+    /// action was inapplicable). Useful for tracing or metrics independent of the
+    /// planner's verbose output. Replaces any previous observer.
+    pub fn set_action_observer(&mut self, observer: ActionObserverFn) {
+        self.action_observer = Some(observer);
+        self.revision += 1;
+    }
+
+    /// Get the action observer, if one was set
+    pub fn action_observer(&self) -> Option<&ActionObserverFn> {
+        self.action_observer.as_ref()
+    }
+
+    /// Add a structural check planners run on the initial state before searching
+    ///
+    /// A planner searching from a state that fails an invariant returns
+    /// `GTRustHopError::InvariantViolated` with the invariant's message. Unlike
+    /// `PlannerBuilder::with_state_constraint()`, later states are not checked.
+    pub fn add_state_invariant(&mut self, invariant: StateInvariantFn) {
+        self.state_invariants.push(invariant);
+        self.revision += 1;
+    }
+
+    /// Check a state against the invariants, in the order they were added
+    pub fn check_state_invariants(&self, state: &State) -> Result<()> {
+        for invariant in &self.state_invariants {
+            invariant(state).map_err(GTRustHopError::invariant_violated)?;
+        }
+        Ok(())
+    }
+
+    /// Get the domain revision
+    ///
+    /// The revision starts at 0 and increases with every `declare_*` call, so it
+    /// can be compared with a planner's captured domain to detect a planner that
+    /// was built before the latest edits (see `Planner::domain_name_with_version()`).
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Get the domain's default for goal verification, if one was declared
+    pub fn default_verify_goals(&self) -> Option<bool> {
+        self.default_verify_goals
+    }
+
+    /// Check if the domain declares no actions and no methods
+    ///
+    /// The built-in `_verify_g` and `_verify_mg` task methods are not counted.
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+            && self.multigoal_methods.is_empty()
+            && self.unigoal_methods.values().all(|methods| methods.is_empty())
+            && self.relgoal_methods.values().all(|methods| methods.is_empty())
+            && self
+                .task_methods
+                .iter()
+                .all(|(name, methods)| Self::is_builtin_task(name) || methods.is_empty())
+    }
+
+    /// Check if a task name refers to one of the built-in verification tasks
+    pub(crate) fn is_builtin_task(task_name: &str) -> bool {
+        matches!(task_name, "_verify_g" | "_verify_mg")
+    }
+
+    /// Check if relational goal methods exist for a relation
+    pub fn has_relgoal_methods(&self, rel: &str) -> bool {
+        self.relgoal_methods.contains_key(rel)
+    }
+
     /// Get all action names
     pub fn action_names(&self) -> Vec<&String> {
         self.actions.keys().collect()
@@ -280,7 +934,14 @@ impl Domain {
     }
 
     /// Get all task names
+    ///
+    /// Leaves out the built-in verification tasks, see `task_names_including_builtins()`.
     pub fn task_names(&self) -> Vec<&String> {
+        self.task_methods.keys().filter(|name| !Self::is_builtin_task(name)).collect()
+    }
+
+    /// Get all task names, including the built-in `_verify_g` and `_verify_mg` tasks
+    pub fn task_names_including_builtins(&self) -> Vec<&String> {
         self.task_methods.keys().collect()
     }
 
@@ -289,6 +950,26 @@ impl Domain {
         self.unigoal_methods.keys().collect()
     }
 
+    /// Get a structural fingerprint of the domain, see `DomainSignature`
+    pub fn signature(&self) -> DomainSignature {
+        DomainSignature::of(self)
+    }
+
+    /// Get the variables with unigoal methods that no action declares, sorted
+    ///
+    /// Such methods usually sit under a misspelled variable name. Actions declare
+    /// their variables with `declare_action_variables()`; if no action does, the
+    /// domain can't be checked and this returns nothing.
+    pub fn unigoal_vars_without_actions(&self) -> Vec<&String> {
+        if self.action_variables.is_empty() {
+            return Vec::new();
+        }
+        let used: HashSet<&String> = self.action_variables.values().flatten().collect();
+        let mut unused: Vec<&String> = self.unigoal_methods.keys().filter(|var| !used.contains(var)).collect();
+        unused.sort();
+        unused
+    }
+
     /// Create a copy of the domain with an optional new name
     pub fn copy(&self, new_name: Option<String>) -> Self {
         let mut copy = self.clone();
@@ -335,17 +1016,18 @@ impl Domain {
     pub fn print_methods(&self) {
         self.print_task_methods();
         self.print_unigoal_methods();
+        self.print_relgoal_methods();
         self.print_multigoal_methods();
     }
 
     /// Print task methods
     pub fn print_task_methods(&self) {
-        if self.task_methods.is_empty() {
+        if self.task_names().is_empty() {
             println!("-- There are no task methods --");
         } else {
             println!("\nTask name:         Relevant task methods:");
             println!("---------------    ----------------------");
-            for (task_name, methods) in &self.task_methods {
+            for (task_name, methods) in self.task_methods.iter().filter(|(name, _)| !Self::is_builtin_task(name)) {
                 println!("{:<19}{} methods", task_name, methods.len());
             }
             println!();
@@ -366,6 +1048,20 @@ impl Domain {
         }
     }
 
+    /// Print relational goal methods
+    pub fn print_relgoal_methods(&self) {
+        if self.relgoal_methods.is_empty() {
+            println!("-- There are no relational goal methods --");
+        } else {
+            println!("Relation name:     Relevant relational goal methods:");
+            println!("---------------    ---------------------------------");
+            for (rel, methods) in &self.relgoal_methods {
+                println!("{:<19}{} methods", rel, methods.len());
+            }
+            println!();
+        }
+    }
+
     /// Print multigoal methods
     pub fn print_multigoal_methods(&self) {
         if self.multigoal_methods.is_empty() {
@@ -378,21 +1074,292 @@ impl Domain {
 
 }
 
-impl std::fmt::Display for Domain {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Domain {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "<Domain {}>", self.name)
     }
 }
 
-impl std::fmt::Debug for Domain {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Debug for Domain {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Domain")
             .field("name", &self.name)
             .field("actions", &self.actions.keys().collect::<Vec<_>>())
             .field("commands", &self.commands.keys().collect::<Vec<_>>())
             .field("task_methods", &self.task_methods.keys().collect::<Vec<_>>())
             .field("unigoal_methods", &self.unigoal_methods.keys().collect::<Vec<_>>())
+            .field("relgoal_methods", &self.relgoal_methods.keys().collect::<Vec<_>>())
             .field("multigoal_methods_count", &self.multigoal_methods.len())
+            .field("default_verify_goals", &self.default_verify_goals)
+            .field("has_action_observer", &self.action_observer.is_some())
+            .field("revision", &self.revision)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::string_value;
+
+    fn create_counter_domain() -> Result<Domain> {
+        let mut domain = Domain::new("counter");
+        domain.declare_action("increment", |state: &mut State, _args: &[StateValue]| {
+            let count = state.get_var("count", "c")?.as_i64()?;
+            state.set_var("count", "c", (count + 1).into());
+            Some(state.clone())
+        })?;
+        domain.declare_action("reset", |state: &mut State, _args: &[StateValue]| {
+            state.set_var("count", "c", 0.into());
+            Some(state.clone())
+        })?;
+        domain.declare_action("require_zero", |state: &mut State, _args: &[StateValue]| {
+            (state.get_var("count", "c")?.as_i64()? == 0).then(|| state.clone())
+        })?;
+        Ok(domain)
+    }
+
+    fn create_counter_state() -> State {
+        let mut state = State::new("counter");
+        state.set_var("count", "c", 0.into());
+        state
+    }
+
+    #[test]
+    fn test_apply_plan() -> Result<()> {
+        let domain = create_counter_domain()?;
+        let plan = vec![
+            PlanItem::action("increment", vec![]),
+            PlanItem::action("increment", vec![]),
+            PlanItem::action("reset", vec![]),
+            PlanItem::action("increment", vec![]),
+        ];
+
+        let state = domain.apply_plan(create_counter_state(), &plan)?;
+        assert_eq!(state.get_var("count", "c"), Some(&1.into()));
+
+        // Non-action items are rejected unless skipped
+        let mixed = vec![PlanItem::task("count_up", vec![string_value("c")]), PlanItem::action("increment", vec![])];
+        let result = domain.apply_plan(create_counter_state(), &mixed);
+        assert!(matches!(result, Err(GTRustHopError::InvalidItemType { depth: 0, .. })));
+        let state = domain.apply_plan_skipping_non_actions(create_counter_state(), &mixed)?;
+        assert_eq!(state.get_var("count", "c"), Some(&1.into()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_plan_fails_midway() -> Result<()> {
+        let domain = create_counter_domain()?;
+        let plan = vec![
+            PlanItem::action("require_zero", vec![]),
+            PlanItem::action("increment", vec![]),
+            PlanItem::action("require_zero", vec![]),
+            PlanItem::action("reset", vec![]),
+        ];
+        assert_eq!(domain.apply_plan(create_counter_state(), &plan), Err(GTRustHopError::plan_execution_failed(2)));
+
+        // Unknown actions fail the same way
+        let plan = vec![PlanItem::action("increment", vec![]), PlanItem::action("teleport", vec![])];
+        assert_eq!(domain.apply_plan(create_counter_state(), &plan), Err(GTRustHopError::plan_execution_failed(1)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_applicable_agrees_with_applying() -> Result<()> {
+        let mut domain = create_counter_domain()?;
+        domain.declare_action_with_precond(
+            "decrement",
+            |state: &State, _args: &[StateValue]| state.get_var("count", "c").and_then(StateValue::as_i64).is_some_and(|count| count > 0),
+            |state: &mut State, _args: &[StateValue]| {
+                let count = state.get_var("count", "c").and_then(StateValue::as_i64).unwrap_or_default();
+                state.set_var("count", "c", (count - 1).into());
+            },
+        )?;
+        assert!(domain.action_precondition("decrement").is_some());
+
+        let zero = create_counter_state();
+        let one = domain.apply_plan(zero.clone(), &[PlanItem::action("increment", vec![])])?;
+        for name in ["decrement", "require_zero", "increment", "teleport"] {
+            for state in [&zero, &one] {
+                let applied = domain.get_action(name).and_then(|action_fn| action_fn(&mut state.clone(), &[]));
+                assert_eq!(domain.action_applicable(name, state, &[]), applied.is_some(), "{name}");
+            }
+        }
+        assert_eq!(domain.apply_plan(one, &[PlanItem::action("decrement", vec![])])?.get_var("count", "c"), Some(&0.into()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_task_names_omit_builtins() -> Result<()> {
+        let mut domain = Domain::new("with_builtins");
+        domain.declare_task_method("count_up", |_state: &State, _args: &[StateValue]| Some(vec![]))?;
+        assert_eq!(domain.task_names(), vec!["count_up"]);
+        assert!(domain.task_names_including_builtins().iter().any(|name| *name == "_verify_g"));
+
+        let mut plain = Domain::new_without_builtins("without_builtins");
+        assert!(plain.is_empty());
+        plain.declare_task_method("count_up", |_state: &State, _args: &[StateValue]| Some(vec![]))?;
+        assert_eq!(plain.task_names_including_builtins(), vec!["count_up"]);
+        assert!(!plain.has_task_methods("_verify_g"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_declare_action_checked_rejects_duplicates() -> Result<()> {
+        let mut domain = create_counter_domain()?;
+        let result = domain.declare_action_checked("reset", |_state: &mut State, _args: &[StateValue]| None);
+        assert_eq!(result, Err(GTRustHopError::duplicate_action("reset")));
+
+        // The original action is kept
+        let state = domain.apply_plan(create_counter_state(), &[PlanItem::action("reset", vec![])])?;
+        assert_eq!(state.get_var("count", "c"), Some(&0.into()));
+
+        domain.declare_action_checked("decrement", |state: &mut State, _args: &[StateValue]| Some(state.clone()))?;
+        assert!(domain.redeclared_names().is_empty());
+
+        domain.declare_command("c_reset", |state: &mut State, _args: &[StateValue]| Some(state.clone()))?;
+        domain.declare_command("c_reset", |state: &mut State, _args: &[StateValue]| Some(state.clone()))?;
+        domain.declare_action("reset", |state: &mut State, _args: &[StateValue]| Some(state.clone()))?;
+        assert_eq!(domain.redeclared_names(), ["c_reset".to_string(), "reset".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_redeclared_methods_are_recorded() -> Result<()> {
+        fn m_count(_state: &State, _args: &[StateValue]) -> Option<TodoList> {
+            Some(vec![PlanItem::action("increment", vec![])])
+        }
+        fn m_loc(_state: &State, _arg: &str, _value: &StateValue) -> Option<TodoList> {
+            Some(vec![])
+        }
+        fn m_near(_state: &State, _args: &[StateValue], _value: &StateValue) -> Option<TodoList> {
+            Some(vec![])
+        }
+        fn m_split(_state: &State, _multigoal: &Multigoal) -> Option<TodoList> {
+            Some(vec![])
+        }
+
+        let mut domain = create_counter_domain()?;
+        domain.declare_task_method("count", m_count)?;
+        domain.declare_task_method("count_twice", m_count)?;
+        // Distinct closures are distinct methods, even with the same body
+        domain.declare_unigoal_method("loc", |_state: &State, _arg: &str, _value: &StateValue| Some(vec![]))?;
+        domain.declare_unigoal_method("loc", |_state: &State, _arg: &str, _value: &StateValue| Some(vec![]))?;
+        domain.declare_multigoal_method(m_split)?;
+        assert!(domain.redeclared_method_names().is_empty());
+
+        domain.declare_task_method_weighted("count", 2.0, m_count)?;
+        domain.declare_unigoal_methods("loc", vec![m_loc, m_loc])?;
+        domain.declare_unigoal_method("pos", m_loc)?;
+        domain.declare_unigoal_method("pos", m_loc)?;
+        domain.declare_relgoal_method("near", m_near)?;
+        domain.declare_relgoal_method("near", m_near)?;
+        domain.declare_multigoal_method_for(&["pos"], m_split)?;
+        assert_eq!(domain.redeclared_method_names(), ["count", "loc", "pos", "near", "multigoal"].map(String::from));
+        // Duplicates are still declared
+        assert_eq!(domain.get_task_methods("count").map(Vec::len), Some(2));
+
+        // Function pointers and capturing closures can't be told apart, so they are not reported
+        let mut domain = create_counter_domain()?;
+        let m_pointer: fn(&State, &[StateValue]) -> Option<TodoList> = m_count;
+        domain.declare_task_methods("count", vec![m_pointer, m_pointer])?;
+        let step = 1;
+        let m_capturing = move |_state: &State, _args: &[StateValue]| Some(vec![PlanItem::action("increment", vec![step.into()])]);
+        domain.declare_task_method("count", m_capturing)?;
+        domain.declare_task_method("count", m_capturing)?;
+        assert!(domain.redeclared_method_names().is_empty());
+        assert_eq!(domain.get_task_methods("count").map(Vec::len), Some(4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_with_states() -> Result<()> {
+        use crate::domains::blocks::install_blocks_actions;
+
+        let mut domain = Domain::new("replayed_blocks");
+        install_blocks_actions(&mut domain)?;
+        let mut state = State::new("blocks");
+        for block in ["a", "b", "c"] {
+            state.set_var("pos", block, string_value("table"));
+            state.set_var("clear", block, true.into());
+        }
+        state.set_var("holding", "hand", false.into());
+
+        let plan = vec![
+            PlanItem::action("pickup", vec![string_value("a")]),
+            PlanItem::action("stack", vec![string_value("a"), string_value("b")]),
+            PlanItem::action("pickup", vec![string_value("c")]),
+        ];
+        let states = domain.replay_with_states(state.clone(), &plan)?;
+        let pos = |state: &State, block: &str| state.get_var("pos", block).and_then(|v| v.as_str()).map(str::to_string);
+        let progression: Vec<_> = states.iter().map(|s| (pos(s, "a"), pos(s, "c"))).collect();
+        let expected = [("table", "table"), ("hand", "table"), ("b", "table"), ("b", "hand")]
+            .map(|(a, c)| (Some(a.to_string()), Some(c.to_string())));
+        assert_eq!(progression, expected);
+
+        // Picking up a covered block fails at that action
+        let blocked = [plan[0].clone(), plan[1].clone(), PlanItem::action("pickup", vec![string_value("b")])];
+        assert_eq!(domain.replay_with_states(state, &blocked), Err(GTRustHopError::plan_execution_failed(2)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_undefined_in_state_flags_missing_variable() -> Result<()> {
+        use crate::domains::blocks::{install_blocks_actions, install_blocks_methods};
+
+        let mut domain = Domain::new("blocks");
+        install_blocks_actions(&mut domain)?;
+        install_blocks_methods(&mut domain)?;
+        domain.declare_unigoal_method("pos", |_state: &State, _arg: &str, _value: &StateValue| None)?;
+        let expected: BTreeSet<String> = ["clear", "holding", "pos"].map(String::from).into();
+        assert_eq!(domain.declared_variables(), expected);
+
+        let mut state = State::new("blocks");
+        state.set_var("pos", "a", string_value("table"));
+        state.set_var("clear", "a", true.into());
+        assert_eq!(domain.undefined_in_state(&state), vec!["holding".to_string()]);
+
+        state.set_var("holding", "hand", false.into());
+        assert!(domain.undefined_in_state(&state).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_observer_sees_blocks_plan() -> Result<()> {
+        use crate::domains::blocks::{install_blocks_actions, install_blocks_methods};
+        use crate::planning::PlannerBuilder;
+        use std::sync::Mutex;
+
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&observed);
+
+        let mut domain = Domain::new("observed_blocks");
+        install_blocks_actions(&mut domain)?;
+        install_blocks_methods(&mut domain)?;
+        domain.set_action_observer(Arc::new(move |name: &str, _args: &[StateValue], _before: &State, after: Option<&State>| {
+            log.lock().unwrap().push((name.to_string(), after.is_some()));
+        }));
+
+        let mut state = State::new("blocks");
+        state.set_var("pos", "a", string_value("b"));
+        state.set_var("pos", "b", string_value("table"));
+        state.set_var("clear", "a", true.into());
+        state.set_var("clear", "b", false.into());
+        state.set_var("holding", "hand", false.into());
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+        let todo = vec![
+            PlanItem::task("take", vec![string_value("a")]),
+            PlanItem::task("put", vec![string_value("a"), string_value("table")]),
+        ];
+        let plan = planner.find_plan(state, todo)?.expect("a can be moved to the table");
+
+        let observed = observed.lock().unwrap();
+        assert_eq!(observed.len(), plan.len());
+        assert_eq!(*observed, vec![("unstack".to_string(), true), ("putdown".to_string(), true)]);
+        Ok(())
+    }
+}
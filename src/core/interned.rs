@@ -0,0 +1,211 @@
+//! Interned state representation for GTRusthop
+
+use crate::compat::prelude::*;
+use super::{State, StateValue};
+use crate::compat::{HashMap, HashSet, IndexMap};
+use alloc::sync::Arc;
+
+/// Pool of shared strings used by `InternedState`
+///
+/// Interning the same string twice returns the same allocation, so states built
+/// with one interner share block names, locations and other repeated values.
+#[derive(Debug, Clone, Default)]
+pub struct StringInterner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl StringInterner {
+    /// Create an empty interner
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the shared copy of a string, adding it to the pool if needed
+    pub fn intern(&mut self, string: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.get(string) {
+            return Arc::clone(interned);
+        }
+        let interned: Arc<str> = Arc::from(string);
+        self.strings.insert(Arc::clone(&interned));
+        interned
+    }
+
+    /// Get the number of distinct strings in the pool
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Check if the pool is empty
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+/// A state value whose string, if any, is shared through a `StringInterner`
+#[derive(Debug, Clone, PartialEq)]
+pub enum InternedValue {
+    /// An interned string
+    Str(Arc<str>),
+    /// Any other value, stored as is
+    Other(StateValue),
+}
+
+impl InternedValue {
+    /// Intern a state value
+    pub fn new(value: &StateValue, interner: &mut StringInterner) -> Self {
+        match value {
+            StateValue::String(string) => Self::Str(interner.intern(string)),
+            other => Self::Other(other.clone()),
+        }
+    }
+
+    /// Convert back to a state value
+    pub fn to_value(&self) -> StateValue {
+        match self {
+            Self::Str(string) => StateValue::String(string.to_string()),
+            Self::Other(value) => value.clone(),
+        }
+    }
+}
+
+impl PartialEq<StateValue> for InternedValue {
+    fn eq(&self, other: &StateValue) -> bool {
+        match (self, other) {
+            (Self::Str(string), StateValue::String(other)) => **string == **other,
+            (Self::Str(_), _) => false,
+            (Self::Other(value), other) => value == other,
+        }
+    }
+}
+
+/// A state storing variable names, arguments and string values as interned strings
+///
+/// Cloning an interned state never allocates strings, which makes it a compact
+/// way to keep many similar states, e.g. the states along a plan. Values are
+/// converted to `StateValue` on access, so `get_var()` answers like
+/// `State::get_var()`; use `to_state()` to plan from it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InternedState {
+    /// Name of the state
+    pub name: String,
+    /// State variables as nested maps: var_name -> arg -> value
+    variables: IndexMap<Arc<str>, HashMap<Arc<str>, InternedValue>>,
+}
+
+impl InternedState {
+    /// Create an empty interned state
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            variables: IndexMap::default(),
+        }
+    }
+
+    /// Intern all variables of a state
+    pub fn from_state(state: &State, interner: &mut StringInterner) -> Self {
+        let mut interned = Self::new(state.name.clone());
+        for var_name in state.var_names() {
+            for (arg, value) in state.relation(var_name).iter() {
+                interned.set_var(var_name, arg, value, interner);
+            }
+        }
+        interned
+    }
+
+    /// Set a state variable value
+    pub fn set_var(&mut self, var_name: &str, arg: &str, value: &StateValue, interner: &mut StringInterner) {
+        let value = InternedValue::new(value, interner);
+        self.variables
+            .entry(interner.intern(var_name))
+            .or_default()
+            .insert(interner.intern(arg), value);
+    }
+
+    /// Get a state variable value
+    pub fn get_var(&self, var_name: &str, arg: &str) -> Option<StateValue> {
+        Some(self.get_interned(var_name, arg)?.to_value())
+    }
+
+    /// Get a state variable value without converting it
+    pub fn get_interned(&self, var_name: &str, arg: &str) -> Option<&InternedValue> {
+        self.variables.get(var_name)?.get(arg)
+    }
+
+    /// Convert back to a regular state
+    pub fn to_state(&self) -> State {
+        let mut state = State::new(self.name.clone());
+        for (var_name, var_map) in &self.variables {
+            for (arg, value) in var_map {
+                state.set_var(var_name.as_ref(), arg.as_ref(), value.to_value());
+            }
+        }
+        state
+    }
+}
+
+impl PartialEq<State> for InternedState {
+    /// Compare names and variables, without converting values
+    fn eq(&self, other: &State) -> bool {
+        self.name == other.name
+            && self.variables.len() == other.var_names().len()
+            && self.variables.iter().all(|(var_name, var_map)| {
+                let relation = other.relation(var_name);
+                relation.len() == var_map.len()
+                    && var_map.iter().all(|(arg, value)| relation.get(arg).is_some_and(|other| value == other))
+            })
+    }
+}
+
+impl PartialEq<InternedState> for State {
+    fn eq(&self, other: &InternedState) -> bool {
+        other == self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_blocks_state() -> State {
+        let mut state = State::new("blocks");
+        state.set_var("pos", "a", "table".into());
+        state.set_var("pos", "b", "table".into());
+        state.set_var("pos", "c", "a".into());
+        state.set_var("clear", "c", true.into());
+        state
+    }
+
+    #[test]
+    fn test_interned_state_equals_original() {
+        let state = create_blocks_state();
+        let mut interner = StringInterner::new();
+        let interned = InternedState::from_state(&state, &mut interner);
+
+        assert_eq!(interned, state);
+        assert_eq!(state, interned);
+        assert_eq!(interned.to_state(), state);
+        assert_eq!(interned.get_var("pos", "c"), state.get_var("pos", "c").cloned());
+        assert_eq!(interned.get_var("clear", "c"), Some(true.into()));
+
+        let mut moved = state.copy(Some("blocks".to_string()));
+        moved.set_var("pos", "c", "b".into());
+        assert_ne!(interned, moved);
+        // A string is never equal to a non-string value
+        moved.set_var("pos", "c", true.into());
+        assert_ne!(interned, moved);
+    }
+
+    #[test]
+    fn test_interned_states_share_strings() {
+        let mut interner = StringInterner::new();
+        let first = InternedState::from_state(&create_blocks_state(), &mut interner);
+        let second = InternedState::from_state(&create_blocks_state().copy(None), &mut interner);
+
+        let (Some(InternedValue::Str(a)), Some(InternedValue::Str(b))) = (first.get_interned("pos", "a"), second.get_interned("pos", "b")) else {
+            panic!("expected interned strings");
+        };
+        assert!(Arc::ptr_eq(a, b));
+        // pos, clear, a, b, c, table
+        assert_eq!(interner.len(), 6);
+    }
+}
@@ -3,12 +3,21 @@
 pub mod state;
 pub mod multigoal;
 pub mod domain;
+pub mod signature;
+pub mod interned;
+#[cfg(feature = "bincode")]
+pub mod binary;
 
-pub use state::State;
-pub use multigoal::Multigoal;
+pub use state::{Relation, State};
+pub use multigoal::{Multigoal, MultigoalBuilder};
 pub use domain::Domain;
+pub use signature::{DomainDiff, DomainSignature};
+pub use interned::{InternedState, InternedValue, StringInterner};
+#[cfg(feature = "bincode")]
+pub use binary::{multigoal_from_bytes, multigoal_to_bytes, plan_from_bytes, plan_to_bytes, state_from_bytes, state_to_bytes};
 
-use std::collections::HashMap;
+use crate::compat::prelude::*;
+use crate::compat::HashMap;
 use serde::{Deserialize, Serialize};
 
 /// Type alias for state variable values
@@ -17,6 +26,29 @@ pub type StateValue = serde_json::Value;
 /// Type alias for state variable maps
 pub type StateVarMap = HashMap<String, StateValue>;
 
+/// The kind of a `PlanItem`, for matching without destructuring
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlanItemKind {
+    /// A compound task
+    Task,
+    /// A primitive action
+    Action,
+    /// A single state-variable goal
+    Unigoal,
+    /// A conjunctive goal
+    Multigoal,
+    /// A relational goal over several arguments
+    RelGoal,
+    /// A disjunction of alternatives
+    OneOf,
+    /// A block of items that may be done in any order
+    Unordered,
+    /// A task whose arguments may contain wildcards
+    TaskPattern,
+    /// A goal to keep true for the rest of the plan
+    Maintain,
+}
+
 /// Represents a task or action in the planning system
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum PlanItem {
@@ -28,8 +60,58 @@ pub enum PlanItem {
     Unigoal(String, String, StateValue),
     /// A multigoal
     Multigoal(Multigoal),
+    /// A relational goal over several arguments, e.g. `connected(a, b) = true`
+    RelGoal {
+        /// Name of the relation (state variable)
+        rel: String,
+        /// Arguments of the relation
+        args: Vec<StateValue>,
+        /// Desired value of the relation
+        value: StateValue,
+    },
+    /// Alternatives of which any one suffices, e.g. be at the park or the station
+    OneOf(Vec<PlanItem>),
+    /// Items that may be done in any order; the planner tries each ordering
+    /// (at most `MAX_UNORDERED_ITEMS` items per block)
+    Unordered(Vec<PlanItem>),
+    /// A task whose arguments may contain wildcards (see `wildcard()`); the
+    /// planner tries each entity of the wildcard's type in turn
+    TaskPattern(String, Vec<StateValue>),
+    /// A goal (unigoal, multigoal or relational goal) that must hold now and in
+    /// every state reached by the rest of the plan; actions breaking it are pruned
+    Maintain(Box<PlanItem>),
 }
 
+/// State variable listing the entities of each type, e.g. `types[taxi] = ["taxi1", "taxi2"]`
+///
+/// Used to fill the wildcards of a `PlanItem::TaskPattern`.
+pub const TYPES_VAR: &str = "types";
+
+/// Built-in task achieving a multigoal registered with the planner, e.g. `_achieve_multigoal(goal_sussman)`
+///
+/// The planner looks the id up among its multigoals (see `PlannerBuilder::with_multigoal()`)
+/// and plans for the multigoal itself, so domains need no `achieve` task of their own.
+pub const ACHIEVE_MULTIGOAL_TASK: &str = "_achieve_multigoal";
+
+/// State variable holding the level of each planner resource, e.g. `resources[fuel] = 12.0`
+///
+/// See `PlannerBuilder::with_resource()` and `Domain::declare_action_resource_delta()`.
+pub const RESOURCES_VAR: &str = "resources";
+
+/// Prefix marking a string argument of a `PlanItem::TaskPattern` as a wildcard
+pub const WILDCARD_PREFIX: &str = "?";
+
+/// Maximum number of items in a `PlanItem::Unordered` block
+///
+/// The planner may explore every permutation of a block, so larger blocks are
+/// rejected with a `PlanningFailed` error rather than searched.
+pub const MAX_UNORDERED_ITEMS: usize = 8;
+
+/// Maximum number of soft goals in a multigoal passed to `Planner::find_optimal_plan()`
+///
+/// Every subset of the soft goals is planned for, so the work doubles with each one.
+pub const MAX_SOFT_GOALS: usize = 10;
+
 impl PlanItem {
     /// Create a new task
     pub fn task(name: impl Into<String>, args: Vec<StateValue>) -> Self {
@@ -55,26 +137,106 @@ impl PlanItem {
         Self::Multigoal(multigoal)
     }
 
+    /// Create a new relational goal
+    pub fn relgoal(rel: impl Into<String>, args: Vec<StateValue>, value: StateValue) -> Self {
+        Self::RelGoal {
+            rel: rel.into(),
+            args,
+            value,
+        }
+    }
+
+    /// Create a new disjunction of alternative goals or tasks
+    pub fn one_of(alternatives: Vec<PlanItem>) -> Self {
+        Self::OneOf(alternatives)
+    }
+
+    /// Create a new block of items that may be done in any order
+    pub fn unordered(items: Vec<PlanItem>) -> Self {
+        Self::Unordered(items)
+    }
+
+    /// Create a new task pattern, a task whose arguments may contain wildcards
+    pub fn task_pattern(name: impl Into<String>, args: Vec<StateValue>) -> Self {
+        Self::TaskPattern(name.into(), args)
+    }
+
+    /// Create a new goal to maintain for the rest of the plan
+    pub fn maintain(goal: PlanItem) -> Self {
+        Self::Maintain(Box::new(goal))
+    }
+
     /// Get the name of this plan item
     pub fn name(&self) -> &str {
         match self {
-            Self::Task(name, _) | Self::Action(name, _) | Self::Unigoal(name, _, _) => name,
+            Self::Task(name, _) | Self::Action(name, _) | Self::Unigoal(name, _, _) | Self::TaskPattern(name, _) => name,
+            Self::RelGoal { rel, .. } => rel,
             Self::Multigoal(mg) => &mg.name,
+            Self::OneOf(_) => "one_of",
+            Self::Unordered(_) => "unordered",
+            Self::Maintain(_) => "maintain",
         }
     }
 
     /// Get the arguments of this plan item as owned values
     pub fn args(&self) -> Vec<StateValue> {
         match self {
-            Self::Task(_, args) | Self::Action(_, args) => args.clone(),
+            Self::Task(_, args) | Self::Action(_, args) | Self::TaskPattern(_, args) => args.clone(),
             Self::Unigoal(_, arg, value) => vec![
                 string_value(arg),
                 value.clone(),
             ],
-            Self::Multigoal(_) => vec![],
+            Self::RelGoal { args, value, .. } => {
+                args.iter().cloned().chain(core::iter::once(value.clone())).collect()
+            }
+            Self::Multigoal(_) | Self::OneOf(_) | Self::Unordered(_) | Self::Maintain(_) => vec![],
         }
     }
 
+    /// Get argument `index` without cloning, in the same order as `args()`
+    ///
+    /// Returns `None` if the index is out of range. The first argument of a
+    /// unigoal is a plain string; use `arg_str()` to read it.
+    pub fn arg(&self, index: usize) -> Option<&StateValue> {
+        match self {
+            Self::Task(_, args) | Self::Action(_, args) | Self::TaskPattern(_, args) => args.get(index),
+            Self::Unigoal(_, _, value) => (index == 1).then_some(value),
+            Self::RelGoal { args, value, .. } => args.get(index).or((index == args.len()).then_some(value)),
+            Self::Multigoal(_) | Self::OneOf(_) | Self::Unordered(_) | Self::Maintain(_) => None,
+        }
+    }
+
+    /// Get argument `index` as a string slice
+    ///
+    /// Returns `None` if the index is out of range or the argument is not a string.
+    pub fn arg_str(&self, index: usize) -> Option<&str> {
+        match self {
+            Self::Unigoal(_, arg, _) if index == 0 => Some(arg),
+            _ => self.arg(index)?.as_str(),
+        }
+    }
+
+    /// Get argument `index` as an integer
+    ///
+    /// Returns `None` if the index is out of range or the argument is not an integer.
+    pub fn arg_i64(&self, index: usize) -> Option<i64> {
+        self.arg(index)?.as_i64()
+    }
+
+    /// Get argument `index` as a floating point number
+    ///
+    /// Returns `None` if the index is out of range or the argument is not a number.
+    pub fn arg_f64(&self, index: usize) -> Option<f64> {
+        self.arg(index)?.as_f64()
+    }
+
+    /// Get argument `index` as a boolean
+    ///
+    /// Returns `None` if the index is out of range or the argument is not a boolean.
+    pub fn arg_bool(&self, index: usize) -> Option<bool> {
+        self.arg(index)?.as_bool()
+    }
+
     /// Check if this is a task
     pub fn is_task(&self) -> bool {
         matches!(self, Self::Task(_, _))
@@ -94,12 +256,69 @@ impl PlanItem {
     pub fn is_multigoal(&self) -> bool {
         matches!(self, Self::Multigoal(_))
     }
+
+    /// Check if this is a relational goal
+    pub fn is_relgoal(&self) -> bool {
+        matches!(self, Self::RelGoal { .. })
+    }
+
+    /// Check if this is a disjunction of alternatives
+    pub fn is_one_of(&self) -> bool {
+        matches!(self, Self::OneOf(_))
+    }
+
+    /// Check if this is an unordered block
+    pub fn is_unordered(&self) -> bool {
+        matches!(self, Self::Unordered(_))
+    }
+
+    /// Check if this is a task pattern
+    pub fn is_task_pattern(&self) -> bool {
+        matches!(self, Self::TaskPattern(_, _))
+    }
+
+    /// Check if this is a goal to maintain
+    pub fn is_maintain(&self) -> bool {
+        matches!(self, Self::Maintain(_))
+    }
+
+    /// Check if this goal holds in `state`
+    ///
+    /// Returns `None` if this item is not a unigoal, multigoal or relational goal.
+    pub fn is_satisfied_by(&self, state: &State) -> Option<bool> {
+        match self {
+            Self::Unigoal(var_name, arg, value) => Some(state.satisfies_unigoal(var_name, arg, value)),
+            Self::Multigoal(multigoal) => Some(multigoal.is_satisfied_by(state)),
+            Self::RelGoal { rel, args, value } => Some(state.satisfies_relgoal(rel, args, value)),
+            _ => None,
+        }
+    }
+
+    /// Check if this is a primitive item, i.e. an action that can appear in a plan
+    pub fn is_primitive(&self) -> bool {
+        self.is_action()
+    }
+
+    /// Get the kind of this plan item
+    pub fn kind(&self) -> PlanItemKind {
+        match self {
+            Self::Task(_, _) => PlanItemKind::Task,
+            Self::Action(_, _) => PlanItemKind::Action,
+            Self::Unigoal(_, _, _) => PlanItemKind::Unigoal,
+            Self::Multigoal(_) => PlanItemKind::Multigoal,
+            Self::RelGoal { .. } => PlanItemKind::RelGoal,
+            Self::OneOf(_) => PlanItemKind::OneOf,
+            Self::Unordered(_) => PlanItemKind::Unordered,
+            Self::TaskPattern(_, _) => PlanItemKind::TaskPattern,
+            Self::Maintain(_) => PlanItemKind::Maintain,
+        }
+    }
 }
 
-impl std::fmt::Display for PlanItem {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for PlanItem {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
-            Self::Task(name, args) => {
+            Self::Task(name, args) | Self::TaskPattern(name, args) => {
                 write!(f, "({name}")?;
                 for arg in args {
                     write!(f, " {arg}")?;
@@ -116,7 +335,29 @@ impl std::fmt::Display for PlanItem {
             Self::Unigoal(var_name, arg, value) => {
                 write!(f, "({var_name} {arg} {value})")
             }
+            Self::RelGoal { rel, args, value } => {
+                write!(f, "({rel}")?;
+                for arg in args {
+                    write!(f, " {arg}")?;
+                }
+                write!(f, " {value})")
+            }
             Self::Multigoal(mg) => write!(f, "{mg}"),
+            Self::OneOf(alternatives) => {
+                write!(f, "(one_of")?;
+                for alternative in alternatives {
+                    write!(f, " {alternative}")?;
+                }
+                write!(f, ")")
+            }
+            Self::Unordered(items) => {
+                write!(f, "(unordered")?;
+                for item in items {
+                    write!(f, " {item}")?;
+                }
+                write!(f, ")")
+            }
+            Self::Maintain(goal) => write!(f, "(maintain {goal})"),
         }
     }
 }
@@ -127,6 +368,11 @@ pub type Plan = Vec<PlanItem>;
 /// Type alias for a todo list (sequence of tasks, goals, and actions)
 pub type TodoList = Vec<PlanItem>;
 
+/// Check if every item is primitive, i.e. the items form a plan rather than a todo list
+pub fn is_primitive_plan(items: &[PlanItem]) -> bool {
+    items.iter().all(PlanItem::is_primitive)
+}
+
 /// Helper function to create a plan item from a tuple
 pub fn plan_item_from_tuple(name: &str, args: &[StateValue]) -> PlanItem {
     PlanItem::Task(name.to_string(), args.to_vec())
@@ -137,6 +383,16 @@ pub fn state_value_from<T: Into<StateValue>>(value: T) -> StateValue {
     value.into()
 }
 
+/// Create a wildcard argument for a `PlanItem::TaskPattern`, matching any entity of `type_name`
+pub fn wildcard(type_name: &str) -> StateValue {
+    StateValue::String(format!("{WILDCARD_PREFIX}{type_name}"))
+}
+
+/// Get the type named by a wildcard argument, or `None` if `value` is not a wildcard
+pub fn wildcard_type(value: &StateValue) -> Option<&str> {
+    value.as_str()?.strip_prefix(WILDCARD_PREFIX)
+}
+
 /// Helper functions to create StateValue from various types
 pub fn string_value(s: impl Into<String>) -> StateValue {
     StateValue::String(s.into())
@@ -154,6 +410,83 @@ pub fn bool_value(b: bool) -> StateValue {
     StateValue::Bool(b)
 }
 
+/// Conversion of Rust values to state values, used by the `args!` macro
+///
+/// Integers always become integer values and floats float values, so a goal of
+/// `args![5]` is never accidentally written as `5.0`.
+pub trait IntoStateValue {
+    /// Convert to a state value
+    fn into_state_value(self) -> StateValue;
+}
+
+impl IntoStateValue for StateValue {
+    fn into_state_value(self) -> StateValue {
+        self
+    }
+}
+
+impl IntoStateValue for &str {
+    fn into_state_value(self) -> StateValue {
+        string_value(self)
+    }
+}
+
+impl IntoStateValue for String {
+    fn into_state_value(self) -> StateValue {
+        string_value(self)
+    }
+}
+
+impl IntoStateValue for i32 {
+    fn into_state_value(self) -> StateValue {
+        int_value(self.into())
+    }
+}
+
+impl IntoStateValue for i64 {
+    fn into_state_value(self) -> StateValue {
+        int_value(self)
+    }
+}
+
+impl IntoStateValue for f64 {
+    fn into_state_value(self) -> StateValue {
+        float_value(self)
+    }
+}
+
+impl IntoStateValue for bool {
+    fn into_state_value(self) -> StateValue {
+        bool_value(self)
+    }
+}
+
+/// Build a `Vec<StateValue>` of arguments from Rust values, see `IntoStateValue`
+///
+/// ```rust
+/// use gtrusthop::{args, PlanItem};
+///
+/// let task = PlanItem::task("travel", args!["alice", "home", "park"]);
+/// assert_eq!(task.args().len(), 3);
+/// ```
+#[macro_export]
+macro_rules! args {
+    ($($arg:expr),* $(,)?) => {
+        vec![$($crate::core::IntoStateValue::into_state_value($arg)),*]
+    };
+}
+
+/// Check if two state values are equal, comparing numbers by value
+///
+/// `5` and `5.0` are distinct `serde_json::Value`s but equal here; values other
+/// than two numbers compare with plain equality.
+pub fn values_equal(a: &StateValue, b: &StateValue) -> bool {
+    match (a, b) {
+        (StateValue::Number(x), StateValue::Number(y)) if x.is_f64() || y.is_f64() => x.as_f64() == y.as_f64(),
+        _ => a == b,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -171,6 +504,79 @@ mod tests {
         let unigoal = PlanItem::unigoal("loc", "alice", "park".into());
         assert!(unigoal.is_unigoal());
         assert_eq!(unigoal.name(), "loc");
+
+        let relgoal = PlanItem::relgoal("connected", vec!["a".into(), "b".into()], true.into());
+        assert!(relgoal.is_relgoal());
+        assert_eq!(relgoal.name(), "connected");
+        assert_eq!(relgoal.args(), vec![string_value("a"), string_value("b"), bool_value(true)]);
+    }
+
+    #[test]
+    fn test_plan_item_arg_accessors() {
+        let action = PlanItem::action("pay", vec!["alice".into(), 20.into(), 2.5.into(), true.into()]);
+
+        // In range, matching type
+        assert_eq!(action.arg_str(0), Some("alice"));
+        assert_eq!(action.arg_i64(1), Some(20));
+        assert_eq!(action.arg_f64(2), Some(2.5));
+        assert_eq!(action.arg_bool(3), Some(true));
+        assert_eq!(action.arg(1), Some(&int_value(20)));
+
+        // Out of range
+        assert_eq!(action.arg(4), None);
+        assert_eq!(action.arg_str(4), None);
+
+        // Wrong type
+        assert_eq!(action.arg_i64(0), None);
+        assert_eq!(action.arg_str(1), None);
+        assert_eq!(action.arg_bool(0), None);
+
+        // Goals follow the `args()` order
+        let unigoal = PlanItem::unigoal("loc", "alice", "park".into());
+        assert_eq!(unigoal.arg_str(0), Some("alice"));
+        assert_eq!(unigoal.arg_str(1), Some("park"));
+        assert_eq!(unigoal.arg(0), None);
+        let relgoal = PlanItem::relgoal("connected", vec!["a".into(), "b".into()], true.into());
+        assert_eq!(relgoal.arg_str(1), Some("b"));
+        assert_eq!(relgoal.arg_bool(2), Some(true));
+        assert_eq!(relgoal.arg(3), None);
+    }
+
+    #[test]
+    fn test_plan_item_classification() {
+        let items = vec![
+            PlanItem::task("travel", vec!["alice".into()]),
+            PlanItem::action("walk", vec!["alice".into()]),
+            PlanItem::unigoal("loc", "alice", "park".into()),
+            PlanItem::multigoal(Multigoal::new("goal")),
+            PlanItem::relgoal("connected", vec!["a".into(), "b".into()], true.into()),
+            PlanItem::one_of(vec![PlanItem::unigoal("loc", "alice", "park".into())]),
+            PlanItem::unordered(vec![PlanItem::task("travel", vec![])]),
+            PlanItem::task_pattern("get_taxi", vec![wildcard("taxi"), "alice".into()]),
+            PlanItem::maintain(PlanItem::unigoal("loc", "bob", "home".into())),
+        ];
+
+        let kinds: Vec<PlanItemKind> = items.iter().map(PlanItem::kind).collect();
+        assert_eq!(kinds, vec![
+            PlanItemKind::Task,
+            PlanItemKind::Action,
+            PlanItemKind::Unigoal,
+            PlanItemKind::Multigoal,
+            PlanItemKind::RelGoal,
+            PlanItemKind::OneOf,
+            PlanItemKind::Unordered,
+            PlanItemKind::TaskPattern,
+            PlanItemKind::Maintain,
+        ]);
+
+        let primitive: Vec<bool> = items.iter().map(PlanItem::is_primitive).collect();
+        assert_eq!(primitive, vec![false, true, false, false, false, false, false, false, false]);
+        assert_eq!(items[7].arg(0).and_then(wildcard_type), Some("taxi"));
+        assert_eq!(items[7].arg(1).and_then(wildcard_type), None);
+
+        assert!(!is_primitive_plan(&items));
+        assert!(is_primitive_plan(&items[1..2]));
+        assert!(is_primitive_plan(&[]));
     }
 
     #[test]
@@ -184,4 +590,15 @@ mod tests {
         let bool_val: StateValue = true.into();
         assert_eq!(bool_val, StateValue::Bool(true));
     }
+
+    #[test]
+    fn test_args_macro_matches_explicit_constructors() {
+        assert_eq!(
+            crate::args!["alice", String::from("home"), 42, 7_i64, 1.5, true],
+            vec![string_value("alice"), string_value("home"), int_value(42), int_value(7), float_value(1.5), bool_value(true)]
+        );
+        let no_args: Vec<StateValue> = crate::args![];
+        assert!(no_args.is_empty());
+        assert_eq!(PlanItem::task("travel", crate::args!["alice", "park"]), PlanItem::task("travel", vec!["alice".into(), "park".into()]));
+    }
 }
@@ -1,12 +1,44 @@
 //! Multigoal representation for GTRusthop
 
-use super::StateValue;
-use indexmap::IndexMap;
+use crate::compat::prelude::*;
+use super::{PlanItem, State, StateValue};
+use crate::compat::{HashMap, IndexMap};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use core::hash::{Hash, Hasher};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use alloc::sync::Arc;
+
+/// Number of anonymous multigoals created so far, used to name the next one
+static ANONYMOUS_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Type alias for multigoal decomposition functions
+/// Decomposers receive the multigoal and current state and return the todo list replacing it
+pub type DecomposerFn = Arc<dyn Fn(&Multigoal, &State) -> Vec<PlanItem> + Send + Sync>;
+
+/// Wrapper around a decomposer so `Multigoal` can keep its derived traits
+#[derive(Clone)]
+struct Decomposer(DecomposerFn);
+
+impl core::fmt::Debug for Decomposer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Decomposer")
+    }
+}
+
+impl PartialEq for Decomposer {
+    /// Decomposers are opaque closures, so only the same closure is equal to itself
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
 
 /// Represents a conjunctive goal (multigoal) in the planning domain
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+///
+/// Equality and hashing only consider the goals themselves, so two multigoals
+/// requiring the same values are equal regardless of their names or the order
+/// goals were added in. Use `eq_with_name()` to also compare names. Multigoals
+/// with custom decomposers are only equal if they share the same decomposer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Multigoal {
     /// Name of the multigoal
     pub name: String,
@@ -14,6 +46,15 @@ pub struct Multigoal {
     pub variables: IndexMap<String, HashMap<String, StateValue>>,
     /// Copy counter for generating unique names
     copy_counter: usize,
+    /// Optional custom decomposition used by the planner instead of `to_unigoals()`
+    #[serde(skip)]
+    decomposer: Option<Decomposer>,
+    /// Optional per-goal deadlines: var_name -> arg -> acting iteration
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    deadlines: HashMap<String, HashMap<String, usize>>,
+    /// Optional goals with the penalty of leaving them unsatisfied: var_name -> arg -> (value, penalty)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    soft_goals: HashMap<String, HashMap<String, (StateValue, f64)>>,
 }
 
 impl Multigoal {
@@ -21,8 +62,56 @@ impl Multigoal {
     pub fn new(name: impl Into<String>) -> Self {
         Self {
             name: name.into(),
-            variables: IndexMap::new(),
+            variables: IndexMap::default(),
             copy_counter: 0,
+            decomposer: None,
+            deadlines: HashMap::new(),
+            soft_goals: HashMap::new(),
+        }
+    }
+
+    /// Create a new multigoal with a name no other anonymous multigoal has
+    ///
+    /// Names are `anonymous_0`, `anonymous_1`, ... in creation order, so each
+    /// anonymous multigoal registered with `PlannerBuilder::with_multigoal()`
+    /// gets its own `goal_anonymous_<n>` id.
+    pub fn anonymous() -> Self {
+        Self::new(format!("anonymous_{}", ANONYMOUS_COUNT.fetch_add(1, Ordering::Relaxed)))
+    }
+
+    /// Start building an anonymous multigoal goal by goal
+    pub fn builder() -> MultigoalBuilder {
+        MultigoalBuilder { multigoal: Self::anonymous() }
+    }
+
+    /// Attach a custom decomposition function to this multigoal
+    ///
+    /// When present, the planner calls the decomposer instead of expanding the
+    /// multigoal into its unigoals in declaration order. This is a lighter-weight
+    /// alternative to declaring a domain-level multigoal method, e.g. to order
+    /// goals so that they can be achieved without backtracking.
+    pub fn with_decomposer(mut self, decomposer: DecomposerFn) -> Self {
+        self.decomposer = Some(Decomposer(decomposer));
+        self
+    }
+
+    /// Check if this multigoal carries a custom decomposer
+    pub fn has_decomposer(&self) -> bool {
+        self.decomposer.is_some()
+    }
+
+    /// Decompose this multigoal into a todo list for the given state
+    ///
+    /// Uses the custom decomposer if one was set with `with_decomposer()`,
+    /// otherwise returns one unigoal per goal as produced by `to_unigoals()`.
+    pub fn decompose(&self, state: &State) -> Vec<PlanItem> {
+        match &self.decomposer {
+            Some(Decomposer(decomposer)) => decomposer(self, state),
+            None => self
+                .to_unigoals()
+                .into_iter()
+                .map(|(var_name, arg, value)| PlanItem::unigoal(var_name, arg, value))
+                .collect(),
         }
     }
 
@@ -37,6 +126,69 @@ impl Multigoal {
             .insert(arg, value);
     }
 
+    /// Require a goal to be satisfied within `deadline` iterations of lazy lookahead
+    ///
+    /// `Planner::run_lazy_lookahead()` works on deadlined goals first and returns
+    /// `GTRustHopError::DeadlineMissed` if one is still unsatisfied after
+    /// `deadline` calls to `find_plan`. Planning itself ignores deadlines.
+    pub fn set_deadline(&mut self, var_name: impl Into<String>, arg: impl Into<String>, deadline: usize) {
+        self.deadlines
+            .entry(var_name.into())
+            .or_default()
+            .insert(arg.into(), deadline);
+    }
+
+    /// Get the deadline of a goal, if one was set
+    pub fn get_deadline(&self, var_name: &str, arg: &str) -> Option<usize> {
+        self.deadlines.get(var_name)?.get(arg).copied()
+    }
+
+    /// Get all goals that have a deadline as `(var_name, arg, value, deadline)`, earliest first
+    pub fn deadlined_goals(&self) -> Vec<(String, String, StateValue, usize)> {
+        let mut goals: Vec<_> = self
+            .goals()
+            .into_iter()
+            .filter_map(|(var_name, arg, value)| {
+                let deadline = self.get_deadline(var_name, arg)?;
+                Some((var_name.to_string(), arg.to_string(), value.clone(), deadline))
+            })
+            .collect();
+        goals.sort_by_key(|(_, _, _, deadline)| *deadline);
+        goals
+    }
+
+    /// Add an optional goal whose non-achievement costs `penalty`
+    ///
+    /// Soft goals are not part of the multigoal's required goals: `find_plan()` and
+    /// `is_satisfied_by()` ignore them. `Planner::find_optimal_plan()` decides which
+    /// of them are worth achieving.
+    pub fn set_soft_goal(&mut self, var_name: impl Into<String>, arg: impl Into<String>, value: StateValue, penalty: f64) {
+        self.soft_goals
+            .entry(var_name.into())
+            .or_default()
+            .insert(arg.into(), (value, penalty));
+    }
+
+    /// Get all soft goals as `(var_name, arg, value, penalty)`, sorted by variable and argument
+    pub fn soft_goals(&self) -> Vec<(String, String, StateValue, f64)> {
+        let mut goals: Vec<_> = self.soft_goals.iter()
+            .flat_map(|(var_name, goals)| goals.iter().map(move |(arg, (value, penalty))| {
+                (var_name.clone(), arg.clone(), value.clone(), *penalty)
+            }))
+            .collect();
+        goals.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        goals
+    }
+
+    /// Get the total penalty of the soft goals not satisfied by `state`
+    pub fn soft_penalty(&self, state: &State) -> f64 {
+        self.soft_goals.iter()
+            .flat_map(|(var_name, goals)| goals.iter().map(move |(arg, goal)| (var_name, arg, goal)))
+            .filter(|(var_name, arg, (value, _))| !state.satisfies_unigoal(var_name, arg, value))
+            .map(|(_, _, (_, penalty))| penalty)
+            .sum()
+    }
+
     /// Get a goal variable value
     pub fn get_goal(&self, var_name: &str, arg: &str) -> Option<&StateValue> {
         self.variables
@@ -78,6 +230,25 @@ impl Multigoal {
         self.variables.insert(var_name.into(), var_map);
     }
 
+    /// Keep only the goals for which `pred(var_name, arg, value)` returns true
+    ///
+    /// Goal variables left without any goals are removed.
+    pub fn retain(&mut self, pred: impl Fn(&str, &str, &StateValue) -> bool) {
+        for (var_name, var_map) in self.variables.iter_mut() {
+            var_map.retain(|arg, value| pred(var_name, arg, value));
+        }
+        self.variables.retain(|_, var_map| !var_map.is_empty());
+    }
+
+    /// Get a copy of the multigoal keeping only the goals matching `pred`
+    ///
+    /// The copy keeps the name and any custom decomposer. See `retain()`.
+    pub fn filtered(&self, pred: impl Fn(&str, &str, &StateValue) -> bool) -> Self {
+        let mut filtered = self.clone();
+        filtered.retain(pred);
+        filtered
+    }
+
     /// Create a deep copy of the multigoal with an optional new name
     pub fn copy(&self, new_name: Option<String>) -> Self {
         let mut copy = self.clone();
@@ -120,7 +291,7 @@ impl Multigoal {
     }
 
     /// Check if this multigoal is satisfied by the given state
-    pub fn is_satisfied_by(&self, state: &crate::core::State) -> bool {
+    pub fn is_satisfied_by(&self, state: &State) -> bool {
         for (var_name, goal_map) in &self.variables {
             for (arg, desired_value) in goal_map {
                 if !state.satisfies_unigoal(var_name, arg, desired_value) {
@@ -132,7 +303,7 @@ impl Multigoal {
     }
 
     /// Get all unsatisfied goals in this multigoal given a state
-    pub fn unsatisfied_goals(&self, state: &crate::core::State) -> HashMap<String, HashMap<String, StateValue>> {
+    pub fn unsatisfied_goals(&self, state: &State) -> HashMap<String, HashMap<String, StateValue>> {
         let mut unsatisfied = HashMap::new();
         
         for (var_name, goal_map) in &self.variables {
@@ -149,6 +320,32 @@ impl Multigoal {
         unsatisfied
     }
 
+    /// Estimate how hard this multigoal is to achieve from `state`, for blocks-style `pos` goals
+    ///
+    /// Counts the misplaced blocks plus, for each of them, the blocks stacked on top
+    /// of it that must be moved out of the way first. This is a cheap ordering
+    /// key for batches of problems, not a bound on the plan length.
+    pub fn difficulty(&self, state: &State) -> usize {
+        let Some(goals) = self.variables.get("pos") else {
+            return 0;
+        };
+        let pos = state.relation("pos");
+        let on_top_of: HashMap<&str, &str> = pos.as_string_map().into_iter()
+            .map(|(block, support)| (support, block))
+            .collect();
+        // Cycles can only come from malformed states, but must not loop forever
+        let blocks_above = |block: &str| {
+            core::iter::successors(on_top_of.get(block).copied(), |above| on_top_of.get(above).copied())
+                .take(pos.len())
+                .count()
+        };
+
+        goals.iter()
+            .filter(|(block, target)| pos.get(block) != Some(*target))
+            .map(|(block, _)| 1 + blocks_above(block))
+            .sum()
+    }
+
     /// Check if this multigoal is empty (has no goals)
     pub fn is_empty(&self) -> bool {
         self.variables.is_empty() || 
@@ -181,6 +378,25 @@ impl Multigoal {
         multigoal
     }
 
+    /// Get all goals as `(var_name, arg, value)` triples sorted by variable and argument
+    ///
+    /// Unlike iterating over `variables`, the order doesn't depend on the order
+    /// goals were added in.
+    pub fn goals(&self) -> Vec<(&str, &str, &StateValue)> {
+        let mut goals: Vec<_> = self
+            .variables
+            .iter()
+            .flat_map(|(var_name, goal_map)| goal_map.iter().map(move |(arg, value)| (var_name.as_str(), arg.as_str(), value)))
+            .collect();
+        goals.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        goals
+    }
+
+    /// Check if two multigoals have the same name and the same goals
+    pub fn eq_with_name(&self, other: &Self) -> bool {
+        self.name == other.name && self == other
+    }
+
     /// Convert this multigoal to a list of individual unigoals
     pub fn to_unigoals(&self) -> Vec<(String, String, StateValue)> {
         let mut unigoals = Vec::new();
@@ -195,8 +411,52 @@ impl Multigoal {
     }
 }
 
-impl std::fmt::Display for Multigoal {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// Builder for multigoals, created by `Multigoal::builder()`
+#[derive(Debug, Clone)]
+pub struct MultigoalBuilder {
+    multigoal: Multigoal,
+}
+
+impl MultigoalBuilder {
+    /// Name the multigoal instead of leaving it anonymous
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.multigoal.name = name.into();
+        self
+    }
+
+    /// Add a goal, see `Multigoal::set_goal()`
+    pub fn goal(mut self, var_name: impl Into<String>, arg: impl Into<String>, value: impl Into<StateValue>) -> Self {
+        self.multigoal.set_goal(var_name, arg, value.into());
+        self
+    }
+
+    /// Build the multigoal
+    pub fn build(self) -> Multigoal {
+        self.multigoal
+    }
+}
+
+impl PartialEq for Multigoal {
+    fn eq(&self, other: &Self) -> bool {
+        self.goals() == other.goals() && self.decomposer == other.decomposer
+    }
+}
+
+impl Eq for Multigoal {}
+
+impl Hash for Multigoal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for (var_name, arg, value) in self.goals() {
+            var_name.hash(state);
+            arg.hash(state);
+            // JSON values aren't hashable; their serialization is canonical
+            value.to_string().hash(state);
+        }
+    }
+}
+
+impl core::fmt::Display for Multigoal {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "<Multigoal {}>", self.name)
     }
 }
@@ -240,6 +500,37 @@ mod tests {
         assert!(!multigoal.is_empty());
     }
 
+    #[test]
+    fn test_multigoal_builder() {
+        let multigoal = Multigoal::builder().goal("pos", "a", "b").goal("pos", "b", "table").build();
+        assert_eq!(multigoal.get_goal("pos", "a"), Some(&"b".into()));
+        assert_eq!(multigoal.get_goal("pos", "b"), Some(&"table".into()));
+        assert_eq!(multigoal.goal_count(), 2);
+        assert!(multigoal.name.starts_with("anonymous_"));
+
+        assert_ne!(Multigoal::anonymous().name, Multigoal::anonymous().name);
+        assert_eq!(Multigoal::builder().name("tower").build().name, "tower");
+    }
+
+    #[test]
+    fn test_multigoal_goals_sorted() {
+        let mut multigoal = Multigoal::new("test");
+        multigoal.set_goal("loc", "bob", "home".into());
+        multigoal.set_goal("cash", "alice", 50.into());
+        multigoal.set_goal("loc", "alice", "park".into());
+
+        let keys = |multigoal: &Multigoal| -> Vec<(String, String)> {
+            multigoal.goals().into_iter().map(|(var_name, arg, _)| (var_name.into(), arg.into())).collect()
+        };
+        assert_eq!(keys(&multigoal), [("cash".into(), "alice".into()), ("loc".into(), "alice".into()), ("loc".into(), "bob".into())]);
+        assert_eq!(multigoal.goals()[1].2, &StateValue::from("park"));
+        assert_eq!(multigoal.goals(), multigoal.goals());
+
+        multigoal.set_goal("age", "carol", 30.into());
+        assert_eq!(keys(&multigoal)[0], ("age".into(), "carol".into()));
+        assert_eq!(keys(&multigoal)[1], ("cash".into(), "alice".into()));
+    }
+
     #[test]
     fn test_multigoal_satisfaction() {
         let mut multigoal = Multigoal::new("test_goal");
@@ -296,4 +587,102 @@ mod tests {
             assert!(converted_back.contains(original_unigoal));
         }
     }
+
+    #[test]
+    fn test_multigoal_decompose() {
+        let mut multigoal = Multigoal::new("test");
+        multigoal.set_goal("loc", "alice", "park".into());
+        let state = State::new("state");
+
+        // Default decomposition yields one unigoal per goal
+        assert!(!multigoal.has_decomposer());
+        assert_eq!(multigoal.decompose(&state), vec![PlanItem::unigoal("loc", "alice", "park".into())]);
+
+        // A custom decomposer takes precedence and survives cloning
+        let custom = multigoal.clone().with_decomposer(Arc::new(|_mg: &Multigoal, _state: &State| {
+            vec![PlanItem::task("achieve_all", vec![])]
+        }));
+        assert!(custom.has_decomposer());
+        assert_eq!(custom.clone().decompose(&state), vec![PlanItem::task("achieve_all", vec![])]);
+
+        // Decomposers are compared by identity
+        assert_eq!(custom, custom.clone());
+        assert_ne!(custom, multigoal);
+        let other = multigoal.clone().with_decomposer(Arc::new(|_mg: &Multigoal, _state: &State| {
+            vec![PlanItem::task("achieve_all", vec![])]
+        }));
+        assert_ne!(custom, other);
+    }
+
+    #[test]
+    fn test_multigoal_eq_and_hash() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let hash = |multigoal: &Multigoal| {
+            let mut hasher = DefaultHasher::new();
+            multigoal.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let mut first = Multigoal::new("first");
+        first.set_goal("loc", "alice", "park".into());
+        first.set_goal("loc", "bob", "home".into());
+        first.set_goal("cash", "alice", 50.into());
+
+        let mut second = Multigoal::new("second");
+        second.set_goal("cash", "alice", 50.into());
+        second.set_goal("loc", "bob", "home".into());
+        second.set_goal("loc", "alice", "park".into());
+
+        assert_eq!(first, second);
+        assert_eq!(hash(&first), hash(&second));
+        assert!(!first.eq_with_name(&second));
+        assert!(first.eq_with_name(&first.copy(Some("first".to_string()))));
+
+        second.set_goal("cash", "alice", 60.into());
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_multigoal_difficulty() {
+        // Sussman anomaly: c on a, a and b on the table
+        let mut state = State::new("sussman");
+        state.set_var("pos", "a", "table".into());
+        state.set_var("pos", "b", "table".into());
+        state.set_var("pos", "c", "a".into());
+
+        let mut sussman = Multigoal::new("sussman");
+        sussman.set_goal("pos", "a", "b".into());
+        sussman.set_goal("pos", "b", "c".into());
+        // `a` and `b` are misplaced, and `c` must be moved off `a`
+        assert_eq!(sussman.difficulty(&state), 3);
+
+        let mut satisfied = Multigoal::new("satisfied");
+        satisfied.set_goal("pos", "c", "a".into());
+        assert_eq!(satisfied.difficulty(&state), 0);
+        assert!(sussman.difficulty(&state) > satisfied.difficulty(&state));
+        assert_eq!(Multigoal::new("empty").difficulty(&state), 0);
+    }
+
+    #[test]
+    fn test_multigoal_retain_and_filtered() {
+        let mut tower = Multigoal::new("tower");
+        tower.set_goal("pos", "a", "b".into());
+        tower.set_goal("pos", "b", "c".into());
+        tower.set_goal("pos", "c", "table".into());
+        tower.set_goal("clear", "a", true.into());
+
+        // Stage one: only the base of the tower
+        let base = ["b", "c"];
+        let staged = tower.filtered(|var_name, arg, _| var_name == "pos" && base.contains(&arg));
+        assert_eq!(staged.name, "tower");
+        assert_eq!(staged.goal_count(), 2);
+        assert_eq!(staged.get_goal("pos", "b"), Some(&"c".into()));
+        assert!(!staged.has_goal_arg("pos", "a"));
+        assert!(!staged.has_goal_var("clear"));
+        assert_eq!(tower.goal_count(), 4);
+
+        tower.retain(|_, _, value| value.as_str() == Some("table"));
+        assert_eq!(tower.to_unigoals(), vec![("pos".to_string(), "c".to_string(), "table".into())]);
+    }
 }
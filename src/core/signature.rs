@@ -0,0 +1,110 @@
+//! Structural domain fingerprints for GTRusthop
+
+use crate::compat::prelude::*;
+use super::Domain;
+use serde::{Deserialize, Serialize};
+use alloc::collections::{BTreeMap, BTreeSet};
+
+/// The names a domain declares, for checking that a refactor kept them
+///
+/// Produced by `Domain::signature()`. Names are sorted so signatures compare
+/// and serialize the same regardless of declaration order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomainSignature {
+    /// Action names
+    pub actions: Vec<String>,
+    /// Command names
+    pub commands: Vec<String>,
+    /// Task names, without the built-in verification tasks, mapped to their method counts
+    pub tasks: BTreeMap<String, usize>,
+    /// State variables with unigoal methods
+    pub unigoals: Vec<String>,
+}
+
+/// The differences between two domain signatures, see `DomainSignature::diff()`
+///
+/// Entries name their kind, as in `action stack` or `task take: 2 -> 1 methods`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DomainDiff {
+    /// Elements only the newer signature has
+    pub added: Vec<String>,
+    /// Elements only the older signature has
+    pub removed: Vec<String>,
+    /// Tasks whose method count changed
+    pub changed: Vec<String>,
+}
+
+impl DomainDiff {
+    /// Check if the signatures were identical
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl DomainSignature {
+    /// Compute the signature of a domain
+    pub fn of(domain: &Domain) -> Self {
+        let sorted = |names: Vec<&String>| names.into_iter().cloned().collect::<BTreeSet<_>>().into_iter().collect();
+        Self {
+            actions: sorted(domain.action_names()),
+            commands: sorted(domain.command_names()),
+            tasks: domain.task_names().into_iter()
+                .map(|name| (name.clone(), domain.get_task_methods(name).map_or(0, Vec::len)))
+                .collect(),
+            unigoals: sorted(domain.unigoal_var_names()),
+        }
+    }
+
+    /// Report what `other` adds to or removes from this signature
+    pub fn diff(&self, other: &DomainSignature) -> DomainDiff {
+        let mut diff = DomainDiff::default();
+        let mut compare = |kind: &str, old: &[String], new: &[String]| {
+            diff.added.extend(new.iter().filter(|name| !old.contains(name)).map(|name| format!("{kind} {name}")));
+            diff.removed.extend(old.iter().filter(|name| !new.contains(name)).map(|name| format!("{kind} {name}")));
+        };
+        compare("action", &self.actions, &other.actions);
+        compare("command", &self.commands, &other.commands);
+        let old_tasks: Vec<String> = self.tasks.keys().cloned().collect();
+        let new_tasks: Vec<String> = other.tasks.keys().cloned().collect();
+        compare("task", &old_tasks, &new_tasks);
+        compare("unigoal", &self.unigoals, &other.unigoals);
+
+        for (task, old_count) in &self.tasks {
+            if let Some(new_count) = other.tasks.get(task).filter(|new_count| *new_count != old_count) {
+                diff.changed.push(format!("task {task}: {old_count} -> {new_count} methods"));
+            }
+        }
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domains::blocks::{install_blocks_actions, install_blocks_methods};
+    use crate::error::Result;
+
+    #[test]
+    fn test_signature_diff_reports_missing_action() -> Result<()> {
+        let mut domain = Domain::new("blocks");
+        install_blocks_actions(&mut domain)?;
+        install_blocks_methods(&mut domain)?;
+        let signature = domain.signature();
+        assert_eq!(signature.actions, ["pickup", "putdown", "stack", "unstack"]);
+        assert_eq!(signature.tasks.get("take"), Some(&1));
+        assert!(signature.diff(&domain.signature()).is_empty());
+
+        let mut variant = Domain::new("blocks_without_stack");
+        for name in domain.action_names().into_iter().filter(|name| *name != "stack") {
+            let action = domain.get_action(name).cloned().expect("listed action exists");
+            variant.declare_action(name.clone(), move |state, args| action(state, args))?;
+        }
+        install_blocks_methods(&mut variant)?;
+
+        let diff = signature.diff(&variant.signature());
+        assert_eq!(diff.removed, ["action stack"]);
+        assert!(diff.added.is_empty());
+        assert!(diff.changed.is_empty());
+        Ok(())
+    }
+}
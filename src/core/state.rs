@@ -1,19 +1,32 @@
 //! State representation for GTRusthop
 
-use super::StateValue;
-use indexmap::IndexMap;
+use crate::compat::prelude::*;
+use super::{values_equal, StateValue, TYPES_VAR};
+use crate::compat::{HashMap, IndexMap};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
 
 /// Represents a state in the planning domain
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+///
+/// Equality ignores the mutation log (see `with_mutation_log()`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct State {
     /// Name of the state
     pub name: String,
     /// State variables as nested maps: var_name -> arg -> value
-    variables: IndexMap<String, HashMap<String, StateValue>>,
+    ///
+    /// Copies of a state share the maps of the variables neither has written
+    /// since; `set_var()` clones a shared map before changing it.
+    variables: IndexMap<String, Arc<HashMap<String, StateValue>>>,
     /// Copy counter for generating unique names
     copy_counter: usize,
+    /// Values returned by `get_var()` for unset arguments: var_name -> value
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    defaults: HashMap<String, StateValue>,
+    /// Writes recorded since `with_mutation_log()` was called
+    #[serde(skip)]
+    mutation_log: Option<Vec<(String, String, StateValue)>>,
 }
 
 impl State {
@@ -21,8 +34,10 @@ impl State {
     pub fn new(name: impl Into<String>) -> Self {
         Self {
             name: name.into(),
-            variables: IndexMap::new(),
+            variables: IndexMap::default(),
             copy_counter: 0,
+            defaults: HashMap::new(),
+            mutation_log: None,
         }
     }
 
@@ -30,25 +45,71 @@ impl State {
     pub fn set_var(&mut self, var_name: impl Into<String>, arg: impl Into<String>, value: StateValue) {
         let var_name = var_name.into();
         let arg = arg.into();
+        if let Some(log) = &mut self.mutation_log {
+            log.push((var_name.clone(), arg.clone(), value.clone()));
+        }
         
-        self.variables
-            .entry(var_name)
-            .or_default()
-            .insert(arg, value);
+        Arc::make_mut(self.variables.entry(var_name).or_default()).insert(arg, value);
     }
 
     /// Get a state variable value
+    ///
+    /// Falls back to the variable's default, if any, when the argument is unset.
     pub fn get_var(&self, var_name: &str, arg: &str) -> Option<&StateValue> {
         self.variables
             .get(var_name)
             .and_then(|var_map| var_map.get(arg))
+            .or_else(|| self.defaults.get(var_name))
+    }
+
+    /// Set the value `get_var()` returns for unset arguments of a state variable
+    ///
+    /// The default is not stored as an entry, so it doesn't show up in
+    /// `get_var_map()`, `relation()` or `fingerprint()`.
+    pub fn set_default(&mut self, var_name: impl Into<String>, value: StateValue) {
+        self.defaults.insert(var_name.into(), value);
+    }
+
+    /// Get the default value of a state variable
+    pub fn get_default(&self, var_name: &str) -> Option<&StateValue> {
+        self.defaults.get(var_name)
+    }
+
+    /// Get a value nested in an object state variable value, e.g. `vehicle[truck1].fuel`
+    ///
+    /// An empty path gets the value itself, like `get_var()`.
+    pub fn get_path(&self, var_name: &str, arg: &str, path: &[&str]) -> Option<&StateValue> {
+        path.iter().try_fold(self.get_var(var_name, arg)?, |value, key| value.get(key))
+    }
+
+    /// Set a value nested in an object state variable value
+    ///
+    /// Missing or non-object values along the path are replaced by objects. An
+    /// empty path sets the value itself, like `set_var()`.
+    pub fn set_path(&mut self, var_name: &str, arg: &str, path: &[&str], value: StateValue) {
+        if path.is_empty() {
+            return self.set_var(var_name, arg, value);
+        }
+        let mut root = self.get_var(var_name, arg).cloned().unwrap_or_default();
+        let mut current = &mut root;
+        for key in path {
+            if !current.is_object() {
+                *current = StateValue::Object(serde_json::Map::new());
+            }
+            current = current
+                .as_object_mut()
+                .map(|object| object.entry(key.to_string()).or_insert(StateValue::Null))
+                .expect("value was just made an object");
+        }
+        *current = value;
+        self.set_var(var_name, arg, root);
     }
 
     /// Get a mutable reference to a state variable value
     pub fn get_var_mut(&mut self, var_name: &str, arg: &str) -> Option<&mut StateValue> {
         self.variables
             .get_mut(var_name)
-            .and_then(|var_map| var_map.get_mut(arg))
+            .and_then(|var_map| Arc::make_mut(var_map).get_mut(arg))
     }
 
     /// Check if a state variable exists
@@ -77,15 +138,123 @@ impl State {
 
     /// Get the entire variable map for a state variable
     pub fn get_var_map(&self, var_name: &str) -> Option<&HashMap<String, StateValue>> {
-        self.variables.get(var_name)
+        self.variables.get(var_name).map(Arc::as_ref)
+    }
+
+    /// Get a read-only view of a single state variable
+    ///
+    /// The view is empty if the variable doesn't exist.
+    pub fn relation(&self, var_name: &str) -> Relation<'_> {
+        Relation {
+            var_map: self.get_var_map(var_name),
+        }
+    }
+
+    /// Count the entries of a state variable matching a predicate
+    ///
+    /// The predicate receives each `(arg, value)` pair; a missing variable counts 0.
+    pub fn count(&self, var_name: &str, pred: impl Fn(&str, &StateValue) -> bool) -> usize {
+        self.relation(var_name).iter().filter(|(arg, value)| pred(arg, value)).count()
+    }
+
+    /// Check whether any entry of a state variable matches a predicate
+    pub fn any(&self, var_name: &str, pred: impl Fn(&str, &StateValue) -> bool) -> bool {
+        self.relation(var_name).iter().any(|(arg, value)| pred(arg, value))
+    }
+
+    /// Check whether every entry of a state variable matches a predicate
+    ///
+    /// Vacuously `true` for a missing variable.
+    pub fn all(&self, var_name: &str, pred: impl Fn(&str, &StateValue) -> bool) -> bool {
+        self.relation(var_name).iter().all(|(arg, value)| pred(arg, value))
+    }
+
+    /// Find the entity of a type closest to `from`
+    ///
+    /// The entities are the members of the `type_var` type declared with
+    /// `declare_type()`, and the arguments of a `type_var` variable whose value is
+    /// `true`, like `trucks[truck1] = true`. `distance_fn` receives the state, an
+    /// entity and `from`, and returns `None` if the entity can't reach `from`. Ties
+    /// go to the entity whose name sorts first, so the choice never depends on map order.
+    pub fn nearest_entity(
+        &self,
+        from: &str,
+        type_var: &str,
+        distance_fn: impl Fn(&State, &str, &str) -> Option<f64>,
+    ) -> Option<String> {
+        let flagged = self.relation(type_var)
+            .iter()
+            .filter(|(_, is_type)| is_type.as_bool() == Some(true))
+            .map(|(entity, _)| entity);
+        self.members_of(type_var)
+            .into_iter()
+            .chain(flagged)
+            .filter_map(|entity| Some((distance_fn(self, entity, from)?, entity)))
+            .min_by(|(d1, e1), (d2, e2)| d1.total_cmp(d2).then_with(|| e1.cmp(e2)))
+            .map(|(_, entity)| entity.to_string())
+    }
+
+    /// Declare the members of an entity type, replacing any earlier declaration
+    ///
+    /// Members are stored as `types[type_name] = [...]` (see `TYPES_VAR`), the
+    /// representation `PlanItem::TaskPattern` wildcards are filled from.
+    pub fn declare_type(&mut self, type_name: &str, members: &[&str]) {
+        self.set_var(TYPES_VAR, type_name, members.iter().map(|&member| StateValue::from(member)).collect());
+    }
+
+    /// Get the members of an entity type in declaration order
+    ///
+    /// Empty if the type is undeclared; non-string members are skipped.
+    pub fn members_of(&self, type_name: &str) -> Vec<&str> {
+        self.get_var(TYPES_VAR, type_name)
+            .and_then(|members| members.as_array())
+            .map(|members| members.iter().filter_map(|member| member.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Check whether an entity is a member of a declared type
+    pub fn is_a(&self, entity: &str, type_name: &str) -> bool {
+        self.members_of(type_name).contains(&entity)
+    }
+
+    /// Start recording every `set_var()` call, clearing any previous record
+    ///
+    /// Copies made with `copy()` keep recording into a fresh log, so the log of
+    /// a state returned by an action lists exactly the writes that action made.
+    pub fn with_mutation_log(&mut self) {
+        self.mutation_log = Some(Vec::new());
+    }
+
+    /// Get the `(var_name, arg, value)` writes recorded since `with_mutation_log()`
+    pub fn mutation_log(&self) -> Option<&[(String, String, StateValue)]> {
+        self.mutation_log.as_deref()
+    }
+
+    /// Replace every value of a state variable equal to `old` with `new`
+    ///
+    /// Numbers are compared by value, as in `satisfies_unigoal()`. Returns the
+    /// number of entries changed.
+    pub fn replace_value(&mut self, var_name: &str, old: &StateValue, new: StateValue) -> usize {
+        let args: Vec<String> = self.relation(var_name)
+            .iter()
+            .filter(|(_, value)| values_equal(value, old))
+            .map(|(arg, _)| arg.to_string())
+            .collect();
+        for arg in &args {
+            self.set_var(var_name, arg.as_str(), new.clone());
+        }
+        args.len()
     }
 
     /// Set an entire variable map for a state variable
     pub fn set_var_map(&mut self, var_name: impl Into<String>, var_map: HashMap<String, StateValue>) {
-        self.variables.insert(var_name.into(), var_map);
+        self.variables.insert(var_name.into(), Arc::new(var_map));
     }
 
-    /// Create a deep copy of the state with an optional new name
+    /// Create a copy of the state with an optional new name
+    ///
+    /// The copy shares its variable maps with this state until either writes
+    /// to them, so copying is cheap however large the state.
     pub fn copy(&self, new_name: Option<String>) -> Self {
         let mut copy = self.clone();
         
@@ -95,40 +264,95 @@ impl State {
             copy.name = format!("{}_copy_{}", self.name, self.copy_counter);
             copy.copy_counter += 1;
         }
+        if let Some(log) = &mut copy.mutation_log {
+            log.clear();
+        }
         
         copy
     }
 
     /// Display the state in a human-readable format
     pub fn display(&self, heading: Option<&str>) {
+        print!("{}", self.render(heading, None));
+    }
+
+    /// Display only the named state variables
+    ///
+    /// Variables are shown in state order; names the state doesn't have are skipped.
+    pub fn display_vars(&self, vars: &[&str], heading: Option<&str>) {
+        print!("{}", self.display_vars_string(vars, heading));
+    }
+
+    /// Get the text `display_vars()` prints
+    pub fn display_vars_string(&self, vars: &[&str], heading: Option<&str>) -> String {
+        self.render(heading, Some(vars))
+    }
+
+    /// Format the state for display, optionally keeping only some variables
+    fn render(&self, heading: Option<&str>, vars: Option<&[&str]>) -> String {
+        use core::fmt::Write;
+
         let heading = heading.unwrap_or("State");
         let title = format!("{} {}:", heading, self.name);
         let dashes = "-".repeat(title.len());
-        
-        println!("{title}");
-        println!("{dashes}");
-        
-        if self.variables.is_empty() {
-            println!("  (no state variables)");
+        let mut out = format!("{title}\n{dashes}\n");
+
+        let shown: Vec<_> = self.variables.iter()
+            .filter(|(var_name, _)| vars.is_none_or(|vars| vars.contains(&var_name.as_str())))
+            .collect();
+        if shown.is_empty() {
+            out.push_str("  (no state variables)\n");
         } else {
-            for (var_name, var_map) in &self.variables {
+            for (var_name, var_map) in shown {
                 if var_map.is_empty() {
-                    println!("  - {var_name} = {{}}");
+                    let _ = writeln!(out, "  - {var_name} = {{}}");
                 } else {
-                    println!("  - {var_name} = {{");
-                    for (arg, value) in var_map {
-                        println!("      '{arg}': {value},");
+                    let _ = writeln!(out, "  - {var_name} = {{");
+                    for (arg, value) in var_map.iter() {
+                        let _ = writeln!(out, "      '{arg}': {value},");
                     }
-                    println!("    }}");
+                    out.push_str("    }\n");
                 }
             }
         }
-        println!();
+        out.push('\n');
+        out
     }
 
     /// Check if this state satisfies a unigoal
+    ///
+    /// Numbers match by value, so a goal of `5` is satisfied by `5.0`.
     pub fn satisfies_unigoal(&self, var_name: &str, arg: &str, desired_value: &StateValue) -> bool {
-        self.get_var(var_name, arg) == Some(desired_value)
+        self.get_var(var_name, arg).is_some_and(|value| values_equal(value, desired_value))
+    }
+
+    /// Build the argument key under which a relation over `args` is stored
+    ///
+    /// String arguments are used verbatim and other values in their JSON form,
+    /// joined with commas, so `connected(a, b)` is stored as `connected["a,b"]`.
+    pub fn rel_key(args: &[StateValue]) -> String {
+        args.iter()
+            .map(|arg| match arg {
+                StateValue::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Set the value of a relation over several arguments
+    pub fn set_rel(&mut self, rel: impl Into<String>, args: &[StateValue], value: StateValue) {
+        self.set_var(rel, Self::rel_key(args), value);
+    }
+
+    /// Get the value of a relation over several arguments
+    pub fn get_rel(&self, rel: &str, args: &[StateValue]) -> Option<&StateValue> {
+        self.get_var(rel, &Self::rel_key(args))
+    }
+
+    /// Check if this state satisfies a relational goal, matching numbers by value
+    pub fn satisfies_relgoal(&self, rel: &str, args: &[StateValue], desired_value: &StateValue) -> bool {
+        self.get_rel(rel, args).is_some_and(|value| values_equal(value, desired_value))
     }
 
     /// Get all state variables that don't match the desired values in a multigoal
@@ -152,12 +376,89 @@ impl State {
     /// Apply changes from another state (for action execution)
     pub fn apply_changes(&mut self, other: &State) {
         for (var_name, var_map) in &other.variables {
-            for (arg, value) in var_map {
+            for (arg, value) in var_map.iter() {
                 self.set_var(var_name, arg, value.clone());
             }
         }
     }
 
+    /// Get a canonical string describing the state variables
+    ///
+    /// Two states have the same fingerprint exactly when they bind the same
+    /// variables and arguments to the same values, regardless of their names
+    /// or insertion order.
+    pub fn fingerprint(&self) -> String {
+        serde_json::to_string(&self.sorted_variables()).unwrap_or_default()
+    }
+
+    /// Get a canonical string describing the per-variable defaults
+    ///
+    /// The counterpart of `fingerprint()`, which leaves defaults out.
+    #[cfg(feature = "std")]
+    pub(crate) fn defaults_fingerprint(&self) -> String {
+        serde_json::to_string(&self.defaults.iter().collect::<BTreeMap<_, _>>()).unwrap_or_default()
+    }
+
+    /// Get a 64-bit hash of `fingerprint()`, for keying external caches
+    ///
+    /// Uses FNV-1a, so equal states hash the same in every process and build,
+    /// unlike `std::hash`, whose output may change between Rust releases.
+    pub fn snapshot_hash(&self) -> u64 {
+        const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+        self.fingerprint().bytes().fold(FNV_OFFSET, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME))
+    }
+
+    /// Get the state variables as `(var_name, arg, value)` ground atoms, sorted by variable and argument
+    ///
+    /// Defaults set with `set_default()` are not atoms.
+    pub fn to_atoms(&self) -> Vec<(String, String, StateValue)> {
+        self.sorted_variables()
+            .into_iter()
+            .flat_map(|(var_name, var_map)| {
+                var_map.into_iter().map(move |(arg, value)| (var_name.clone(), arg.clone(), value.clone()))
+            })
+            .collect()
+    }
+
+    /// Create a state from `(var_name, arg, value)` ground atoms, see `to_atoms()`
+    pub fn from_atoms(name: impl Into<String>, atoms: impl IntoIterator<Item = (String, String, StateValue)>) -> Self {
+        let mut state = Self::new(name);
+        for (var_name, arg, value) in atoms {
+            state.set_var(var_name, arg, value);
+        }
+        state
+    }
+
+    /// Check if two states bind the same variables to the same values, allowing numbers to differ by up to `epsilon`
+    ///
+    /// Like `fingerprint()`, this ignores the state names and defaults. Numbers
+    /// inside arrays and objects are compared within `epsilon` too; all other
+    /// values must be equal.
+    pub fn approx_eq(&self, other: &State, epsilon: f64) -> bool {
+        let (atoms, other_atoms) = (self.to_atoms(), other.to_atoms());
+        atoms.len() == other_atoms.len()
+            && atoms.iter().zip(&other_atoms).all(|((var_name, arg, value), (other_var, other_arg, other_value))| {
+                var_name == other_var && arg == other_arg && values_approx_equal(value, other_value, epsilon)
+            })
+    }
+
+    /// Export the state variables as indented JSON, sorted by variable and argument
+    ///
+    /// Unlike `to_json()`, this contains only the variable maps, laid out like
+    /// `display()`: `{ "clear": { "a": true, ... }, "pos": { "a": "b", ... } }`.
+    pub fn to_pretty_json(&self) -> String {
+        serde_json::to_string_pretty(&self.sorted_variables()).unwrap_or_default()
+    }
+
+    /// Get the state variables as nested sorted maps
+    fn sorted_variables(&self) -> BTreeMap<&String, BTreeMap<&String, &StateValue>> {
+        self.variables
+            .iter()
+            .map(|(var_name, var_map)| (var_name, var_map.iter().collect()))
+            .collect()
+    }
+
     /// Convert to a JSON representation
     pub fn to_json(&self) -> serde_json::Result<String> {
         serde_json::to_string_pretty(self)
@@ -169,8 +470,79 @@ impl State {
     }
 }
 
-impl std::fmt::Display for State {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// Read-only view of one state variable, e.g. all `pos` entries of a state
+///
+/// Created by `State::relation()`.
+#[derive(Debug, Clone, Copy)]
+pub struct Relation<'a> {
+    var_map: Option<&'a HashMap<String, StateValue>>,
+}
+
+impl<'a> Relation<'a> {
+    /// Get the value for an argument
+    pub fn get(&self, arg: &str) -> Option<&'a StateValue> {
+        self.var_map?.get(arg)
+    }
+
+    /// Iterate over `(arg, value)` pairs
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a StateValue)> {
+        self.var_map
+            .into_iter()
+            .flatten()
+            .map(|(arg, value)| (arg.as_str(), value))
+    }
+
+    /// Get all arguments of the relation
+    pub fn args(&self) -> Vec<&'a str> {
+        self.iter().map(|(arg, _)| arg).collect()
+    }
+
+    /// Get the string-valued entries as a map, skipping non-string values
+    pub fn as_string_map(&self) -> HashMap<&'a str, &'a str> {
+        self.iter()
+            .filter_map(|(arg, value)| Some((arg, value.as_str()?)))
+            .collect()
+    }
+
+    /// Get the number of entries in the relation
+    pub fn len(&self) -> usize {
+        self.var_map.map_or(0, |var_map| var_map.len())
+    }
+
+    /// Check if the relation has no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Compare two values, allowing numbers to differ by up to `epsilon`
+fn values_approx_equal(a: &StateValue, b: &StateValue, epsilon: f64) -> bool {
+    match (a, b) {
+        (StateValue::Number(x), StateValue::Number(y)) => match (x.as_f64(), y.as_f64()) {
+            (Some(x), Some(y)) => (x - y).abs() <= epsilon,
+            _ => x == y,
+        },
+        (StateValue::Array(xs), StateValue::Array(ys)) => {
+            xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| values_approx_equal(x, y, epsilon))
+        }
+        (StateValue::Object(xs), StateValue::Object(ys)) => {
+            xs.len() == ys.len() && xs.iter().all(|(key, x)| ys.get(key).is_some_and(|y| values_approx_equal(x, y, epsilon)))
+        }
+        _ => a == b,
+    }
+}
+
+impl PartialEq for State {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.variables == other.variables
+            && self.copy_counter == other.copy_counter
+            && self.defaults == other.defaults
+    }
+}
+
+impl core::fmt::Display for State {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "<State {}>", self.name)
     }
 }
@@ -231,4 +603,314 @@ mod tests {
         assert!(!state.satisfies_unigoal("loc", "alice", &"park".into()));
         assert!(!state.satisfies_unigoal("loc", "bob", &"home".into()));
     }
+
+    #[test]
+    fn test_unigoal_satisfaction_compares_numbers_by_value() {
+        use crate::core::{float_value, int_value};
+
+        let mut state = State::new("test");
+        state.set_var("cash", "alice", float_value(5.0));
+        state.set_var("cash", "bob", int_value(7));
+
+        assert!(state.satisfies_unigoal("cash", "alice", &int_value(5)));
+        assert!(state.satisfies_unigoal("cash", "bob", &float_value(7.0)));
+        assert!(!state.satisfies_unigoal("cash", "bob", &float_value(7.5)));
+        // Only numbers are compared by value
+        assert!(!state.satisfies_unigoal("cash", "bob", &"7".into()));
+    }
+
+    #[test]
+    fn test_state_fingerprint() {
+        let mut state1 = State::new("first");
+        state1.set_var("loc", "alice", "home".into());
+        state1.set_var("cash", "alice", 20.into());
+
+        let mut state2 = State::new("second");
+        state2.set_var("cash", "alice", 20.into());
+        state2.set_var("loc", "alice", "home".into());
+
+        assert_eq!(state1.fingerprint(), state2.fingerprint());
+
+        state2.set_var("loc", "alice", "park".into());
+        assert_ne!(state1.fingerprint(), state2.fingerprint());
+    }
+
+    #[test]
+    fn test_state_relation_view() {
+        let mut state = State::new("blocks");
+        state.set_var("pos", "a", "b".into());
+        state.set_var("pos", "b", "table".into());
+        state.set_var("pos", "c", 3.into());
+
+        let pos = state.relation("pos");
+        assert_eq!(pos.len(), 3);
+        assert_eq!(pos.get("a"), Some(&"b".into()));
+        assert_eq!(pos.get("z"), None);
+
+        let mut args = pos.args();
+        args.sort();
+        assert_eq!(args, vec!["a", "b", "c"]);
+
+        // Blocks that something sits on
+        let supports: Vec<&str> = pos.iter().filter_map(|(_, value)| value.as_str()).filter(|v| *v != "table").collect();
+        assert_eq!(supports, vec!["b"]);
+
+        let strings = pos.as_string_map();
+        assert_eq!(strings.len(), 2);
+        assert_eq!(strings["a"], "b");
+
+        let missing = state.relation("clear");
+        assert!(missing.is_empty());
+        assert_eq!(missing.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_state_aggregate_queries() {
+        let mut state = State::new("blocks");
+        state.set_var("pos", "a", "table".into());
+        state.set_var("pos", "b", "table".into());
+        state.set_var("pos", "c", "a".into());
+        state.set_var("clear", "a", false.into());
+        state.set_var("clear", "b", true.into());
+        state.set_var("clear", "c", true.into());
+
+        assert_eq!(state.count("clear", |_, clear| clear.as_bool() == Some(true)), 2);
+        assert_eq!(state.count("holding", |_, _| true), 0);
+        assert!(state.any("pos", |block, pos| block == "c" && pos == "a"));
+        assert!(!state.all("pos", |_, pos| pos == "table"));
+
+        state.set_var("pos", "c", "table".into());
+        assert!(state.all("pos", |_, pos| pos == "table"));
+        assert!(state.all("holding", |_, _| false));
+    }
+
+    #[test]
+    fn test_state_nearest_entity() {
+        let mut state = State::new("logistics");
+        for (truck, km) in [("truck1", 12), ("truck2", 3), ("truck3", 3), ("truck4", 1)] {
+            state.set_var("trucks", truck, true.into());
+            state.set_var("km_from_depot", truck, km.into());
+        }
+        state.set_var("trucks", "truck4", false.into());
+        let distance = |state: &State, truck: &str, _from: &str| state.get_var("km_from_depot", truck)?.as_f64();
+
+        // truck4 is no longer a truck; truck2 wins the tie with truck3
+        assert_eq!(state.nearest_entity("depot", "trucks", distance), Some("truck2".to_string()));
+        assert_eq!(state.nearest_entity("depot", "trucks", |_, truck, _| (truck == "truck1").then_some(0.0)), Some("truck1".to_string()));
+        assert_eq!(state.nearest_entity("depot", "trucks", |_, _, _| None), None);
+        assert_eq!(state.nearest_entity("depot", "planes", distance), None);
+    }
+
+    #[test]
+    fn test_state_nearest_entity_of_declared_type() {
+        use crate::domains::logistics::create_logistics_state;
+
+        let state = create_logistics_state();
+        let same_city = |state: &State, truck: &str, from: &str| {
+            let truck_city = state.get_var("in_city", state.get_var("truck_at", truck)?.as_str()?)?;
+            (truck_city == state.get_var("in_city", from)?).then_some(0.0)
+        };
+        assert_eq!(state.nearest_entity("location10", "trucks", same_city), Some("truck6".to_string()));
+        assert_eq!(state.nearest_entity("location2", "trucks", same_city), Some("truck1".to_string()));
+        assert_eq!(state.nearest_entity("location2", "airplanes", |_, _, _| Some(1.0)), Some("plane2".to_string()));
+    }
+
+    #[test]
+    fn test_state_to_pretty_json() {
+        let mut state = State::new("blocks");
+        state.set_var("pos", "b", "table".into());
+        state.set_var("pos", "a", "b".into());
+        state.set_var("clear", "b", false.into());
+        state.set_var("clear", "a", true.into());
+
+        let json = state.to_pretty_json();
+        let expected = r#"{
+  "clear": {
+    "a": true,
+    "b": false
+  },
+  "pos": {
+    "a": "b",
+    "b": "table"
+  }
+}"#;
+        assert_eq!(json, expected);
+
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["pos"]["a"], "b");
+    }
+
+    #[test]
+    fn test_replace_value_retargets_support_block() {
+        let mut state = State::new("blocks");
+        state.set_var("pos", "a", "table".into());
+        state.set_var("pos", "b", "a".into());
+        state.set_var("pos", "c", "a".into());
+        state.set_var("pos", "d", "c".into());
+        state.set_var("clear", "a", false.into());
+
+        assert_eq!(state.replace_value("pos", &"a".into(), "table".into()), 2);
+        assert_eq!(state.get_var("pos", "b"), Some(&"table".into()));
+        assert_eq!(state.get_var("pos", "c"), Some(&"table".into()));
+        assert_eq!(state.get_var("pos", "d"), Some(&"c".into()));
+        assert_eq!(state.get_var("clear", "a"), Some(&false.into()));
+        assert_eq!(state.replace_value("pos", &"a".into(), "table".into()), 0);
+    }
+
+    #[test]
+    fn test_state_paths() {
+        let mut state = State::new("vehicles");
+        state.set_path("vehicle", "truck1", &["fuel"], 50.into());
+        state.set_path("vehicle", "truck1", &["capacity"], 10.into());
+        state.set_path("vehicle", "truck2", &["engine", "hours"], 1200.into());
+
+        assert_eq!(state.get_path("vehicle", "truck1", &["fuel"]), Some(&50.into()));
+        assert_eq!(state.get_path("vehicle", "truck1", &["capacity"]), Some(&10.into()));
+        assert_eq!(state.get_path("vehicle", "truck2", &["engine", "hours"]), Some(&1200.into()));
+        assert_eq!(state.get_path("vehicle", "truck1", &["fuel", "level"]), None);
+        assert_eq!(state.get_path("vehicle", "truck3", &["fuel"]), None);
+
+        // A scalar in the way is replaced by an object
+        state.set_path("vehicle", "truck1", &["fuel", "level"], 40.into());
+        assert_eq!(state.get_path("vehicle", "truck1", &["fuel", "level"]), Some(&40.into()));
+        assert_eq!(state.get_path("vehicle", "truck1", &["capacity"]), Some(&10.into()));
+    }
+
+    #[test]
+    fn test_state_atoms_round_trip() {
+        let mut state = State::new("blocks");
+        state.set_var("pos", "b", "table".into());
+        state.set_var("pos", "a", "b".into());
+        state.set_var("clear", "a", true.into());
+
+        let atoms = state.to_atoms();
+        assert_eq!(atoms[0], ("clear".to_string(), "a".to_string(), true.into()));
+        assert_eq!(atoms[1], ("pos".to_string(), "a".to_string(), "b".into()));
+        assert_eq!(atoms.len(), 3);
+        assert_eq!(State::from_atoms("blocks", atoms), state);
+    }
+
+    #[test]
+    fn test_state_defaults() {
+        let mut state = State::new("defaults");
+        state.set_default("clear", true.into());
+        state.set_var("clear", "b", false.into());
+
+        assert_eq!(state.get_var("clear", "a"), Some(&true.into()));
+        assert_eq!(state.get_var("clear", "b"), Some(&false.into()));
+        assert_eq!(state.get_var("pos", "a"), None);
+        assert!(!state.has_var_arg("clear", "a"));
+
+        let restored = State::from_json(&state.to_json().unwrap()).unwrap();
+        assert_eq!(restored.get_var("clear", "c"), Some(&true.into()));
+    }
+
+    #[test]
+    fn test_state_copies_share_unwritten_variables() {
+        let mut state = State::new("blocks");
+        state.set_var("pos", "a", "table".into());
+        state.set_var("clear", "a", true.into());
+
+        let mut copy = state.copy(None);
+        assert!(std::ptr::eq(state.get_var_map("pos").unwrap(), copy.get_var_map("pos").unwrap()));
+
+        // Writing to the copy leaves the original alone
+        copy.set_var("pos", "a", "b".into());
+        *copy.get_var_mut("clear", "a").unwrap() = false.into();
+        assert_eq!(state.get_var("pos", "a"), Some(&"table".into()));
+        assert_eq!(state.get_var("clear", "a"), Some(&true.into()));
+        assert!(!std::ptr::eq(state.get_var_map("pos").unwrap(), copy.get_var_map("pos").unwrap()));
+    }
+
+    #[test]
+    fn test_state_mutation_log() -> crate::error::Result<()> {
+        use crate::core::{string_value, Domain};
+        use crate::domains::blocks::install_blocks_actions;
+
+        let mut domain = Domain::new("blocks");
+        install_blocks_actions(&mut domain)?;
+        let mut state = State::new("blocks");
+        state.set_var("pos", "a", "table".into());
+        state.set_var("clear", "a", true.into());
+        state.set_var("holding", "hand", false.into());
+        assert_eq!(state.mutation_log(), None);
+
+        let unlogged = state.clone();
+        state.with_mutation_log();
+        assert_eq!(state, unlogged);
+
+        // Writes made before the copy are not in the copy's log
+        state.set_var("clear", "b", true.into());
+        let pickup = domain.get_action("pickup").unwrap();
+        let new_state = pickup(&mut state.copy(None), &[string_value("a")]).unwrap();
+
+        let written: Vec<(&str, &str)> = new_state.mutation_log().unwrap().iter()
+            .map(|(var_name, arg, _)| (var_name.as_str(), arg.as_str()))
+            .collect();
+        assert_eq!(written, vec![("pos", "a"), ("clear", "a"), ("holding", "hand")]);
+        assert_eq!(state.mutation_log().map(<[_]>::len), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_vars_string_shows_only_named_vars() {
+        let mut state = State::new("blocks");
+        state.set_var("pos", "a", "table".into());
+        state.set_var("clear", "a", true.into());
+
+        let text = state.display_vars_string(&["pos"], None);
+        assert!(text.starts_with("State blocks:\n"));
+        assert!(text.contains("  - pos = {\n      'a': \"table\",\n    }\n"));
+        assert!(!text.contains("clear"));
+    }
+
+    #[test]
+    fn test_approx_eq_tolerates_float_rounding() {
+        let mut state1 = State::new("taxi");
+        state1.set_var("cash", "alice", 20.0.into());
+        state1.set_var("loc", "alice", "home".into());
+        let mut state2 = state1.clone();
+        state2.set_var("cash", "alice", (20.0 + 1e-9).into());
+
+        assert_ne!(state1, state2);
+        assert!(state1.approx_eq(&state2, 1e-6));
+        assert!(!state1.approx_eq(&state2, 1e-12));
+
+        state2.set_var("loc", "alice", "park".into());
+        assert!(!state1.approx_eq(&state2, 1e-6));
+    }
+
+    #[test]
+    fn test_snapshot_hash_matches_for_equal_states() {
+        let mut state1 = State::new("first");
+        state1.set_var("pos", "a", "table".into());
+        state1.set_var("clear", "a", true.into());
+        let mut state2 = State::new("second");
+        state2.set_var("clear", "a", true.into());
+        state2.set_var("pos", "a", "table".into());
+        assert_eq!(state1.snapshot_hash(), state2.snapshot_hash());
+
+        state2.set_var("pos", "a", "b".into());
+        assert_ne!(state1.snapshot_hash(), state2.snapshot_hash());
+    }
+
+    #[test]
+    fn test_declared_types_and_membership() {
+        let mut state = State::new("rigid");
+        state.declare_type("person", &["alice", "bob"]);
+        state.declare_type("taxi", &["taxi1"]);
+
+        assert_eq!(state.members_of("person"), vec!["alice", "bob"]);
+        assert_eq!(state.get_var(TYPES_VAR, "taxi"), Some(&serde_json::json!(["taxi1"])));
+        assert!(state.members_of("location").is_empty());
+
+        assert!(state.is_a("bob", "person"));
+        assert!(!state.is_a("taxi1", "person"));
+        assert!(!state.is_a("alice", "location"));
+
+        // Redeclaring a type replaces its members
+        state.declare_type("person", &["carol"]);
+        assert!(!state.is_a("alice", "person"));
+        assert!(state.is_a("carol", "person"));
+    }
 }
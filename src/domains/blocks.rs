@@ -0,0 +1,669 @@
+//! Reusable blocks world actions and methods
+//!
+//! These installers add the blocks world actions and the Gupta-Nau HTN methods
+//! to any domain, so a blocks domain can be assembled without copying the
+//! example code:
+//!
+//! ```rust
+//! use gtrusthop::core::{Domain, Multigoal};
+//! use gtrusthop::domains::blocks::{install_achieve_method, install_blocks_actions, install_blocks_methods};
+//! use std::collections::HashMap;
+//!
+//! let mut domain = Domain::new("my_blocks");
+//! install_blocks_actions(&mut domain).unwrap();
+//! install_blocks_methods(&mut domain).unwrap();
+//! install_achieve_method(&mut domain, HashMap::from([("tower".to_string(), Multigoal::new("tower"))])).unwrap();
+//! ```
+//!
+//! States use the same variables as the blocks examples: `pos[b]`, `clear[b]`
+//! and `holding["hand"]`.
+
+use crate::compat::prelude::*;
+use crate::core::{Domain, Multigoal, PlanItem, State, StateValue, string_value};
+use crate::error::Result;
+use crate::compat::HashMap;
+use alloc::sync::Arc;
+
+/// Declare the primitive blocks world actions
+///
+/// - **`pickup(block)`**: Pick up a block from the table
+/// - **`unstack(block, under_block)`**: Remove a block from another block
+/// - **`putdown(block)`**: Put a held block on the table
+/// - **`stack(block, under_block)`**: Put a held block on another block
+pub fn install_blocks_actions(domain: &mut Domain) -> Result<()> {
+    // pickup action: pick up a block from the table
+    domain.declare_action("pickup", |state: &mut State, args: &[StateValue]| {
+        if !args.is_empty() {
+            if let Some(block) = args[0].as_str() {
+                // Check preconditions: block on table, clear, hand empty
+                if let (Some(pos), Some(clear), Some(holding)) = (
+                    state.get_var("pos", block),
+                    state.get_var("clear", block),
+                    state.get_var("holding", "hand")
+                ) {
+                    if pos.as_str() == Some("table") &&
+                       clear.as_bool() == Some(true) &&
+                       holding.as_bool() == Some(false) {
+                        // Apply effects
+                        state.set_var("pos", block, string_value("hand"));
+                        state.set_var("clear", block, false.into());
+                        state.set_var("holding", "hand", string_value(block));
+                        return Some(state.clone());
+                    }
+                }
+            }
+        }
+        None
+    })?;
+
+    // unstack action: remove a block from another block
+    domain.declare_action("unstack", |state: &mut State, args: &[StateValue]| {
+        if args.len() >= 2 {
+            if let (Some(block1), Some(block2)) = (args[0].as_str(), args[1].as_str()) {
+                // Check preconditions: block1 on block2, block1 clear, hand empty
+                if let (Some(pos), Some(clear), Some(holding)) = (
+                    state.get_var("pos", block1),
+                    state.get_var("clear", block1),
+                    state.get_var("holding", "hand")
+                ) {
+                    if pos.as_str() == Some(block2) &&
+                       block2 != "table" &&
+                       clear.as_bool() == Some(true) &&
+                       holding.as_bool() == Some(false) {
+                        // Apply effects
+                        state.set_var("pos", block1, string_value("hand"));
+                        state.set_var("clear", block1, false.into());
+                        state.set_var("holding", "hand", string_value(block1));
+                        state.set_var("clear", block2, true.into());
+                        return Some(state.clone());
+                    }
+                }
+            }
+        }
+        None
+    })?;
+
+    // putdown action: put a block on the table
+    domain.declare_action("putdown", |state: &mut State, args: &[StateValue]| {
+        if !args.is_empty() {
+            if let Some(block) = args[0].as_str() {
+                // Check preconditions: holding block
+                if let Some(pos) = state.get_var("pos", block) {
+                    if pos.as_str() == Some("hand") {
+                        // Apply effects
+                        state.set_var("pos", block, string_value("table"));
+                        state.set_var("clear", block, true.into());
+                        state.set_var("holding", "hand", false.into());
+                        return Some(state.clone());
+                    }
+                }
+            }
+        }
+        None
+    })?;
+
+    // stack action: put a block on another block
+    domain.declare_action("stack", |state: &mut State, args: &[StateValue]| {
+        if args.len() >= 2 {
+            if let (Some(block1), Some(block2)) = (args[0].as_str(), args[1].as_str()) {
+                // Check preconditions: holding block1, block2 clear
+                if let (Some(pos1), Some(clear2)) = (
+                    state.get_var("pos", block1),
+                    state.get_var("clear", block2)
+                ) {
+                    if pos1.as_str() == Some("hand") && clear2.as_bool() == Some(true) {
+                        // Apply effects
+                        state.set_var("pos", block1, string_value(block2));
+                        state.set_var("clear", block1, true.into());
+                        state.set_var("holding", "hand", false.into());
+                        state.set_var("clear", block2, false.into());
+                        return Some(state.clone());
+                    }
+                }
+            }
+        }
+        None
+    })?;
+
+    for action in ["pickup", "unstack", "putdown", "stack"] {
+        domain.declare_action_variables(action, &["pos", "clear", "holding"]);
+    }
+
+    Ok(())
+}
+
+/// Declare the `take` and `put` task methods
+///
+/// ```text
+/// take(block)      → pickup(block) | unstack(block, under)
+/// put(block, dest) → putdown(block) | stack(block, dest)
+/// ```
+pub fn install_blocks_methods(domain: &mut Domain) -> Result<()> {
+    domain.declare_task_method_arity("take", 1, |state: &State, args: &[StateValue]| {
+        m_take(state, args.first()?.as_str()?)
+    })?;
+
+    domain.declare_task_method_arity("put", 2, |state: &State, args: &[StateValue]| {
+        m_put(state, args.first()?.as_str()?, args.get(1)?.as_str()?)
+    })?;
+
+    Ok(())
+}
+
+/// Declare a task that achieves multigoals looked up by id
+///
+/// Task arguments must be `StateValue`s, so multigoals can't be passed to a
+/// task directly. Instead `task_name(goal_id)` looks `goal_id` up in
+/// `multigoals` and calls `method` with the state, the multigoal and its id.
+/// The ids are also registered with `Domain::declare_multigoal_task`, so the
+/// planner reports an unknown id instead of a plain planning failure.
+pub fn install_multigoal_task<F>(
+    domain: &mut Domain,
+    task_name: &str,
+    multigoals: HashMap<String, Multigoal>,
+    method: F,
+) -> Result<()>
+where
+    F: Fn(&State, &Multigoal, &str) -> Option<Vec<PlanItem>> + Send + Sync + 'static,
+{
+    domain.declare_multigoal_task(task_name, multigoals.keys().cloned())?;
+
+    let multigoals = Arc::new(multigoals);
+    domain.declare_task_method(task_name, move |state: &State, args: &[StateValue]| {
+        let goal_id = args.first()?.as_str()?;
+        method(state, multigoals.get(goal_id)?, goal_id)
+    })
+}
+
+/// Declare the Gupta-Nau `achieve(goal_id)` task over the given multigoals
+pub fn install_achieve_method(domain: &mut Domain, multigoals: HashMap<String, Multigoal>) -> Result<()> {
+    install_multigoal_task(domain, "achieve", multigoals, m_moveblocks)
+}
+
+/// Check if a block is "done" and doesn't need to be moved.
+///
+/// A block is considered "done" if it and all blocks below it will never need to be moved
+/// to achieve the multigoal. This is a key optimization in the Gupta-Nau algorithm.
+///
+/// The algorithm works recursively:
+/// 1. If the block is "table", it's always done
+/// 2. If the block has a goal position and isn't there, it's not done
+/// 3. If the block is on the table, it's done
+/// 4. Otherwise, recursively check the block below it
+///
+/// **Python equivalent**: `def is_done(b1,state,mgoal)`
+///
+/// # Arguments
+///
+/// * `b1` - The block to check
+/// * `state` - The current world state
+/// * `mgoal` - The multigoal being achieved
+///
+/// # Returns
+///
+/// `true` if the block doesn't need to be moved, `false` otherwise.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// # use gtrusthop::core::{State, Multigoal};
+/// # let state = State::new("test");
+/// # let goal = Multigoal::new("test");
+/// // Block 'a' is on the table and has no goal - it's done
+/// assert!(is_done("a", &state, &goal));
+///
+/// // Block 'b' needs to move to block 'c' but is currently on table - not done
+/// assert!(!is_done("b", &state, &goal));
+/// ```
+pub fn is_done(b1: &str, state: &State, mgoal: &Multigoal) -> bool {
+    if b1 == "table" {
+        return true;
+    }
+
+    // Check if b1 has a goal position and is not there
+    if let Some(goal_pos) = mgoal.get_goal("pos", b1) {
+        if let Some(current_pos) = state.get_var("pos", b1) {
+            if goal_pos != current_pos {
+                return false;
+            }
+        }
+    }
+
+    // Check if b1 is on table
+    if let Some(current_pos) = state.get_var("pos", b1) {
+        if current_pos.as_str() == Some("table") {
+            return true;
+        }
+
+        // Recursively check the block below
+        if let Some(below_block) = current_pos.as_str() {
+            if below_block != "table" && below_block != "hand" {
+                return is_done(below_block, state, mgoal);
+            }
+        }
+    }
+
+    true
+}
+
+/// Determine the planning status of a block according to the Gupta-Nau algorithm.
+///
+/// This function categorizes blocks into different states that guide the HTN planning
+/// decisions. The status determines what action should be taken with the block:
+///
+/// - **"done"**: Block and all blocks below it never need to be moved
+/// - **"inaccessible"**: Block is not clear (has another block on top)
+/// - **"move-to-table"**: Block should be moved to the table (either has no goal or goal is table)
+/// - **"move-to-block"**: Block can be moved directly to its goal position
+/// - **"waiting"**: Block cannot be moved to its goal yet (target not ready)
+///
+/// This status classification is central to the Gupta-Nau algorithm's efficiency,
+/// allowing it to make optimal decisions about which blocks to move when.
+///
+/// **Python equivalent**: `def status(b1,state,mgoal)`
+///
+/// # Arguments
+///
+/// * `b1` - The block to analyze
+/// * `state` - The current world state
+/// * `mgoal` - The multigoal being achieved
+///
+/// # Returns
+///
+/// A string indicating the block's planning status.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// # use gtrusthop::core::{State, Multigoal};
+/// # let state = State::new("test");
+/// # let goal = Multigoal::new("test");
+/// let status = status("a", &state, &goal);
+/// match status.as_str() {
+///     "move-to-block" => println!("Block a can move to its goal now"),
+///     "waiting" => println!("Block a must wait for other blocks to move first"),
+///     "done" => println!("Block a is already in the right place"),
+///     _ => println!("Block a has status: {}", status),
+/// }
+/// ```
+pub fn status(b1: &str, state: &State, mgoal: &Multigoal) -> String {
+    // Check if block is done (doesn't need to be moved)
+    if is_done(b1, state, mgoal) {
+        return "done".to_string();
+    }
+
+    // Check if block is clear
+    if let Some(clear) = state.get_var("clear", b1) {
+        if clear.as_bool() != Some(true) {
+            return "inaccessible".to_string();
+        }
+    }
+
+    // Check goal position
+    if let Some(goal_pos) = mgoal.get_goal("pos", b1) {
+        if let Some(goal_str) = goal_pos.as_str() {
+            if goal_str == "table" {
+                return "move-to-table".to_string();
+            } else {
+                // Check if target block is done and clear
+                if is_done(goal_str, state, mgoal) {
+                    if let Some(target_clear) = state.get_var("clear", goal_str) {
+                        if target_clear.as_bool() == Some(true) {
+                            return "move-to-block".to_string();
+                        }
+                    }
+                }
+                return "waiting".to_string();
+            }
+        }
+    } else {
+        return "move-to-table".to_string();
+    }
+
+    "waiting".to_string()
+}
+
+/// Get all clear blocks in the current state.
+///
+/// A block is "clear" if no other block is stacked on top of it and it's not being held.
+/// Only clear blocks can be picked up or unstacked, making this function essential
+/// for determining which blocks are available for manipulation.
+///
+/// This function is used by the Gupta-Nau algorithm to iterate through blocks that
+/// could potentially be moved in the current planning step.
+///
+/// **Python equivalent**: `def all_clear_blocks(state)`
+///
+/// # Arguments
+///
+/// * `state` - The current world state
+///
+/// # Returns
+///
+/// A vector of block names that are currently clear and can be manipulated.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// # use gtrusthop::core::State;
+/// # let state = State::new("test");
+/// let clear_blocks = all_clear_blocks(&state);
+/// for block in clear_blocks {
+///     println!("Block {} is available for pickup/unstack", block);
+/// }
+/// ```
+pub fn all_clear_blocks(state: &State) -> Vec<String> {
+    let mut clear_blocks = Vec::new();
+
+    if let Some(clear_data) = state.get_var_map("clear") {
+        for (block, clear_value) in clear_data {
+            if clear_value.as_bool() == Some(true) {
+                clear_blocks.push(block.clone());
+            }
+        }
+    }
+
+    clear_blocks
+}
+
+/// HTN implementation of the Gupta-Nau blocks world planning algorithm.
+///
+/// This is the core method that implements the near-optimal blocks-world planning
+/// algorithm described in Gupta & Nau (1992). It follows the HTN paradigm by
+/// decomposing the multigoal achievement task into sequences of 'take' and 'put' tasks.
+///
+/// ## Algorithm Overview
+///
+/// The method implements the following strategy:
+///
+/// 1. **Direct moves**: Look for clear blocks that can be moved directly to their
+///    final positions without interfering with other goals.
+///
+/// 2. **Clearing moves**: If no direct moves are possible, move blocks to the table
+///    to clear the way for other blocks.
+///
+/// 3. **Termination**: If no blocks need moving, the goal is achieved.
+///
+/// ## HTN Task Decomposition
+///
+/// Unlike the HGN approach which uses multigoal methods, this HTN implementation
+/// decomposes the achievement task into:
+/// - `take(block)` - Pick up a specific block
+/// - `put(block, destination)` - Place a block at its destination
+/// - `achieve(goal_id)` - Recursively achieve the remaining goals
+///
+/// **Python equivalent**: `def m_moveblocks(state,mgoal)`
+///
+/// # Arguments
+///
+/// * `state` - The current world state
+/// * `mgoal` - The multigoal to achieve
+/// * `goal_id` - The registry ID for recursive achieve calls
+///
+/// # Returns
+///
+/// A task decomposition (sequence of `PlanItem::task` calls) or `None` if no decomposition applies.
+///
+/// # References
+///
+/// N. Gupta and D. S. Nau. "On the complexity of blocks-world planning."
+/// *Artificial Intelligence* 56(2-3):223–254, 1992.
+pub fn m_moveblocks(state: &State, mgoal: &Multigoal, goal_id: &str) -> Option<Vec<PlanItem>> {
+    // Look for a clear block that can be moved to its final location
+    for x in all_clear_blocks(state) {
+        let xstat = status(&x, state, mgoal);
+        if xstat == "move-to-block" {
+            if let Some(target_pos) = mgoal.get_goal("pos", &x) {
+                return Some(vec![
+                    PlanItem::task("take", vec![string_value(&x)]),
+                    PlanItem::task("put", vec![string_value(&x), target_pos.clone()]),
+                    PlanItem::task("achieve", vec![string_value(goal_id)])
+                ]);
+            }
+        } else if xstat == "move-to-table" {
+            return Some(vec![
+                PlanItem::task("take", vec![string_value(&x)]),
+                PlanItem::task("put", vec![string_value(&x), string_value("table")]),
+                PlanItem::task("achieve", vec![string_value(goal_id)])
+            ]);
+        }
+    }
+
+    // If we get here, no blocks can be moved to their final locations
+    for x in all_clear_blocks(state) {
+        if status(&x, state, mgoal) == "waiting" {
+            if let Some(pos) = state.get_var("pos", &x) {
+                if pos.as_str() != Some("table") {
+                    return Some(vec![
+                        PlanItem::task("take", vec![string_value(&x)]),
+                        PlanItem::task("put", vec![string_value(&x), string_value("table")]),
+                        PlanItem::task("achieve", vec![string_value(goal_id)])
+                    ]);
+                }
+            }
+        }
+    }
+
+    // If we get here, there are no blocks that need moving
+    Some(vec![])
+}
+
+/// HTN task method for taking (picking up) a block.
+///
+/// This method decomposes the abstract 'take' task into the appropriate primitive action:
+/// - `pickup(block)` if the block is on the table
+/// - `unstack(block, under_block)` if the block is on another block
+///
+/// The method only succeeds if the block is clear (no other block is on top of it).
+/// This ensures that the preconditions for the primitive actions will be satisfied.
+///
+/// ## HTN Decomposition
+///
+/// ```text
+/// take(block) → pickup(block)     [if block is on table]
+///            → unstack(block, X)  [if block is on block X]
+/// ```
+///
+/// **Python equivalent**: `def m_take(state,b1)`
+///
+/// # Arguments
+///
+/// * `state` - The current world state
+/// * `b1` - The block to pick up
+///
+/// # Returns
+///
+/// A single-action plan (`pickup` or `unstack`) or `None` if the block cannot be taken.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// # use gtrusthop::core::{State, PlanItem, string_value};
+/// # let state = State::new("test");
+/// // Block 'a' is on the table and clear
+/// let plan = m_take(&state, "a");
+/// assert_eq!(plan, Some(vec![PlanItem::action("pickup", vec![string_value("a")])]));
+///
+/// // Block 'b' is on block 'c' and clear
+/// let plan = m_take(&state, "b");
+/// assert_eq!(plan, Some(vec![PlanItem::action("unstack", vec![string_value("b"), string_value("c")])]));
+/// ```
+pub fn m_take(state: &State, b1: &str) -> Option<Vec<PlanItem>> {
+    // Generate either a pickup or an unstack subtask for b1
+    if let Some(clear) = state.get_var("clear", b1) {
+        if clear.as_bool() == Some(true) {
+            if let Some(pos) = state.get_var("pos", b1) {
+                if pos.as_str() == Some("table") {
+                    return Some(vec![PlanItem::action("pickup", vec![string_value(b1)])]);
+                } else if let Some(under_block) = pos.as_str() {
+                    if under_block != "hand" {
+                        return Some(vec![PlanItem::action("unstack", vec![
+                            string_value(b1),
+                            string_value(under_block)
+                        ])]);
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// HTN task method for putting (placing) a block at a destination.
+///
+/// This method decomposes the abstract 'put' task into the appropriate primitive action:
+/// - `putdown(block)` if the destination is the table
+/// - `stack(block, destination)` if the destination is another block
+///
+/// The method only succeeds if the block is currently being held (in the hand).
+/// This ensures that the preconditions for the primitive actions will be satisfied.
+///
+/// ## HTN Decomposition
+///
+/// ```text
+/// put(block, table) → putdown(block)
+/// put(block, dest)  → stack(block, dest)  [if dest is another block]
+/// ```
+///
+/// **Python equivalent**: `def m_put(state,b1,b2)`
+///
+/// # Arguments
+///
+/// * `state` - The current world state
+/// * `b1` - The block to place (must be currently held)
+/// * `b2` - The destination ("table" or another block name)
+///
+/// # Returns
+///
+/// A single-action plan (`putdown` or `stack`) or `None` if the block is not being held.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// # use gtrusthop::core::{State, PlanItem, string_value};
+/// # let state = State::new("test");
+/// // Block 'a' is being held, put it on the table
+/// let plan = m_put(&state, "a", "table");
+/// assert_eq!(plan, Some(vec![PlanItem::action("putdown", vec![string_value("a")])]));
+///
+/// // Block 'a' is being held, stack it on block 'b'
+/// let plan = m_put(&state, "a", "b");
+/// assert_eq!(plan, Some(vec![PlanItem::action("stack", vec![string_value("a"), string_value("b")])]));
+/// ```
+pub fn m_put(state: &State, b1: &str, b2: &str) -> Option<Vec<PlanItem>> {
+    // Generate either a putdown or a stack subtask for b1
+    // b2 is b1's destination: either the table or another block
+    if let Some(holding) = state.get_var("holding", "hand") {
+        if holding.as_str() == Some(b1) {
+            if b2 == "table" {
+                return Some(vec![PlanItem::action("putdown", vec![string_value(b1)])]);
+            } else {
+                return Some(vec![PlanItem::action("stack", vec![
+                    string_value(b1),
+                    string_value(b2)
+                ])]);
+            }
+        }
+    }
+    None
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Plan;
+    use crate::planning::{action_counts, PlannerBuilder};
+    use std::collections::BTreeMap;
+
+    /// `c` on `a`, `a` and `b` on the table
+    fn create_sussman_state() -> State {
+        let mut state = State::new("sussman");
+        state.set_var("pos", "a", string_value("table"));
+        state.set_var("pos", "b", string_value("table"));
+        state.set_var("pos", "c", string_value("a"));
+        state.set_var("clear", "a", false.into());
+        state.set_var("clear", "b", true.into());
+        state.set_var("clear", "c", true.into());
+        state.set_var("holding", "hand", false.into());
+        state
+    }
+
+    /// Plan the Sussman anomaly with the installed blocks actions and methods
+    fn solve_sussman() -> Result<Option<Plan>> {
+        let mut goal = Multigoal::new("sussman_goal");
+        goal.set_goal("pos", "a", string_value("b"));
+        goal.set_goal("pos", "b", string_value("c"));
+
+        let mut domain = Domain::new("installed_blocks");
+        install_blocks_actions(&mut domain)?;
+        install_blocks_methods(&mut domain)?;
+        install_achieve_method(&mut domain, HashMap::from([("sussman".to_string(), goal)]))?;
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+
+        planner.find_plan(create_sussman_state(), vec![PlanItem::task("achieve", vec![string_value("sussman")])])
+    }
+
+    #[test]
+    fn test_installed_domain_solves_sussman_anomaly() -> Result<()> {
+        let plan = solve_sussman()?;
+        let expected = vec![
+            PlanItem::action("unstack", vec![string_value("c"), string_value("a")]),
+            PlanItem::action("putdown", vec![string_value("c")]),
+            PlanItem::action("pickup", vec![string_value("b")]),
+            PlanItem::action("stack", vec![string_value("b"), string_value("c")]),
+            PlanItem::action("pickup", vec![string_value("a")]),
+            PlanItem::action("stack", vec![string_value("a"), string_value("b")]),
+        ];
+        assert_eq!(plan, Some(expected));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sussman_action_counts() -> Result<()> {
+        let plan = solve_sussman()?.expect("the Sussman anomaly is solvable");
+        let counts = action_counts(&plan);
+        let expected = [("pickup", 2), ("putdown", 1), ("stack", 2), ("unstack", 1)]
+            .map(|(name, count)| (name.to_string(), count));
+        assert_eq!(counts, BTreeMap::from(expected));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sussman_search_trace_shows_backtracking() -> Result<()> {
+        use crate::planning::SearchEventKind;
+
+        let mut goal = Multigoal::new("sussman_goal");
+        goal.set_goal("pos", "a", string_value("b"));
+        goal.set_goal("pos", "b", string_value("c"));
+
+        let mut domain = Domain::new("installed_blocks");
+        install_blocks_actions(&mut domain)?;
+        install_blocks_methods(&mut domain)?;
+        // A naive method stacking the blocks in goal order is tried first and
+        // fails, since c is still on a
+        domain.declare_task_method_weighted("achieve", 2.0, |_state: &State, _args: &[StateValue]| {
+            Some(vec![
+                PlanItem::task("take", vec![string_value("a")]),
+                PlanItem::task("put", vec![string_value("a"), string_value("b")]),
+            ])
+        })?;
+        install_achieve_method(&mut domain, HashMap::from([("sussman".to_string(), goal)]))?;
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+        let todo = vec![PlanItem::task("achieve", vec![string_value("sussman")])];
+        let (plan, events) = planner.find_plan_trace(create_sussman_state(), todo)?;
+        assert_eq!(plan.map(|plan| plan.len()), Some(4));
+
+        assert!(events.iter().any(|event| event.kind == SearchEventKind::MethodRejected && event.description == "take[0]"));
+        assert!(events.iter().any(|event| event.kind == SearchEventKind::Backtrack && event.description == "no way to refine (take a)"));
+        assert_eq!(events.first().map(|event| event.kind), Some(SearchEventKind::Pop));
+        assert_eq!(events.last().map(|event| event.kind), Some(SearchEventKind::Success));
+        Ok(())
+    }
+}
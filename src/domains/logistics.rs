@@ -0,0 +1,467 @@
+//! Reusable logistics actions and methods
+//!
+//! These installers add the logistics HGN actions and unigoal methods to any
+//! domain, so logistics problems can be built without the example module:
+//!
+//! ```rust
+//! use gtrusthop::core::Domain;
+//! use gtrusthop::domains::logistics::{create_logistics_state, install_actions, install_unigoal_methods};
+//!
+//! let mut domain = Domain::new("my_logistics");
+//! install_actions(&mut domain).unwrap();
+//! install_unigoal_methods(&mut domain).unwrap();
+//! let state = create_logistics_state();
+//! ```
+//!
+//! Entity types (`packages`, `trucks`, `airplanes`, `locations`, `airports`,
+//! `cities`) are declared with `State::declare_type`, so their members are
+//! listed under `types[...]` and checked with `State::is_a`; `in_city` maps each
+//! location to its city.
+
+use crate::compat::prelude::*;
+use crate::core::{Domain, PlanItem, State, StateValue, string_value};
+use crate::error::Result;
+
+/// Declare the logistics actions: drive/load/unload for trucks and fly/load/unload for planes
+pub fn install_actions(domain: &mut Domain) -> Result<()> {
+    // Drive truck action
+    domain.declare_action("drive_truck", |state: &mut State, args: &[StateValue]| {
+        if args.len() >= 2 {
+            if let (Some(truck), Some(location)) = (args[0].as_str(), args[1].as_str()) {
+                state.set_var("truck_at", truck, string_value(location));
+                return Some(state.clone());
+            }
+        }
+        None
+    })?;
+    
+    // Load truck action
+    domain.declare_action("load_truck", |state: &mut State, args: &[StateValue]| {
+        if args.len() >= 2 {
+            if let (Some(package), Some(truck)) = (args[0].as_str(), args[1].as_str()) {
+                state.set_var("at", package, string_value(truck));
+                return Some(state.clone());
+            }
+        }
+        None
+    })?;
+    
+    // Unload truck action
+    domain.declare_action("unload_truck", |state: &mut State, args: &[StateValue]| {
+        if args.len() >= 2 {
+            if let (Some(package), Some(location)) = (args[0].as_str(), args[1].as_str()) {
+                // Check if package is on a truck and truck is at the location
+                if let Some(truck_val) = state.get_var("at", package) {
+                    if let Some(truck) = truck_val.as_str() {
+                        if let Some(truck_loc_val) = state.get_var("truck_at", truck) {
+                            if let Some(truck_loc) = truck_loc_val.as_str() {
+                                if truck_loc == location {
+                                    state.set_var("at", package, string_value(location));
+                                    return Some(state.clone());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    })?;
+    
+    // Fly plane action
+    domain.declare_action("fly_plane", |state: &mut State, args: &[StateValue]| {
+        if args.len() >= 2 {
+            if let (Some(plane), Some(airport)) = (args[0].as_str(), args[1].as_str()) {
+                state.set_var("plane_at", plane, string_value(airport));
+                return Some(state.clone());
+            }
+        }
+        None
+    })?;
+    
+    // Load plane action
+    domain.declare_action("load_plane", |state: &mut State, args: &[StateValue]| {
+        if args.len() >= 2 {
+            if let (Some(package), Some(plane)) = (args[0].as_str(), args[1].as_str()) {
+                state.set_var("at", package, string_value(plane));
+                return Some(state.clone());
+            }
+        }
+        None
+    })?;
+    
+    // Unload plane action
+    domain.declare_action("unload_plane", |state: &mut State, args: &[StateValue]| {
+        if args.len() >= 2 {
+            if let (Some(package), Some(airport)) = (args[0].as_str(), args[1].as_str()) {
+                // Check if package is on a plane and plane is at the airport
+                if let Some(plane_val) = state.get_var("at", package) {
+                    if let Some(plane) = plane_val.as_str() {
+                        if let Some(plane_loc_val) = state.get_var("plane_at", plane) {
+                            if let Some(plane_loc) = plane_loc_val.as_str() {
+                                if plane_loc == airport {
+                                    state.set_var("at", package, string_value(airport));
+                                    return Some(state.clone());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    })?;
+    
+    Ok(())
+}
+
+/// Declare the unigoal methods for `at`, `truck_at` and `plane_at` (HGN approach)
+pub fn install_unigoal_methods(domain: &mut Domain) -> Result<()> {
+    // Unigoal methods for 'at' (package location)
+    
+    // Method: Load truck
+    domain.declare_unigoal_method("at", |state: &State, arg: &str, value: &StateValue| {
+        if let Some(truck) = value.as_str() {
+            // Check if this is a truck and package is at truck location
+            if is_truck(state, truck) {
+                if let Some(package_loc_val) = state.get_var("at", arg) {
+                    if let Some(package_loc) = package_loc_val.as_str() {
+                        if let Some(truck_loc_val) = state.get_var("truck_at", truck) {
+                            if let Some(truck_loc) = truck_loc_val.as_str() {
+                                if package_loc == truck_loc {
+                                    return Some(vec![PlanItem::action("load_truck", vec![string_value(arg), string_value(truck)])]);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    })?;
+    
+    // Method: Unload truck
+    domain.declare_unigoal_method("at", |state: &State, arg: &str, value: &StateValue| {
+        if let Some(location) = value.as_str() {
+            // Check if package is on a truck and we want to unload at a location
+            if is_location(state, location) {
+                if let Some(truck_val) = state.get_var("at", arg) {
+                    if let Some(truck) = truck_val.as_str() {
+                        if is_truck(state, truck) {
+                            return Some(vec![PlanItem::action("unload_truck", vec![string_value(arg), string_value(location)])]);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    })?;
+    
+    // Method: Load plane
+    domain.declare_unigoal_method("at", |state: &State, arg: &str, value: &StateValue| {
+        if let Some(plane) = value.as_str() {
+            // Check if this is a plane and package is at plane location
+            if is_plane(state, plane) {
+                if let Some(package_loc_val) = state.get_var("at", arg) {
+                    if let Some(package_loc) = package_loc_val.as_str() {
+                        if let Some(plane_loc_val) = state.get_var("plane_at", plane) {
+                            if let Some(plane_loc) = plane_loc_val.as_str() {
+                                if package_loc == plane_loc {
+                                    return Some(vec![PlanItem::action("load_plane", vec![string_value(arg), string_value(plane)])]);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    })?;
+    
+    // Method: Unload plane
+    domain.declare_unigoal_method("at", |state: &State, arg: &str, value: &StateValue| {
+        if let Some(airport) = value.as_str() {
+            // Check if package is on a plane and we want to unload at an airport
+            if is_airport(state, airport) {
+                if let Some(plane_val) = state.get_var("at", arg) {
+                    if let Some(plane) = plane_val.as_str() {
+                        if is_plane(state, plane) {
+                            return Some(vec![PlanItem::action("unload_plane", vec![string_value(arg), string_value(airport)])]);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    })?;
+    
+    // Unigoal methods for 'truck_at'
+    domain.declare_unigoal_method("truck_at", |state: &State, arg: &str, value: &StateValue| {
+        if let Some(location) = value.as_str() {
+            if is_truck(state, arg) && is_location(state, location) {
+                // Check if the truck's current location and the target are in the same city
+                let truck_loc = state.get_var("truck_at", arg).and_then(|v| v.as_str());
+                if let (Some(truck_city), Some(loc_city)) = (truck_loc.and_then(|loc| get_city(state, loc)), get_city(state, location)) {
+                    if truck_city == loc_city {
+                        return Some(vec![PlanItem::action("drive_truck", vec![string_value(arg), string_value(location)])]);
+                    }
+                }
+            }
+        }
+        None
+    })?;
+    
+    // Unigoal methods for 'plane_at'
+    domain.declare_unigoal_method("plane_at", |state: &State, arg: &str, value: &StateValue| {
+        if let Some(airport) = value.as_str() {
+            if is_plane(state, arg) && is_airport(state, airport) {
+                return Some(vec![PlanItem::action("fly_plane", vec![string_value(arg), string_value(airport)])]);
+            }
+        }
+        None
+    })?;
+
+    // Complex unigoal methods for package transportation
+
+    // Method: Move within city using truck
+    domain.declare_unigoal_method("at", |state: &State, arg: &str, value: &StateValue| {
+        if let Some(target_location) = value.as_str() {
+            if is_package(state, arg) && is_location(state, target_location) {
+                if let Some(package_loc_val) = state.get_var("at", arg) {
+                    if let Some(package_loc) = package_loc_val.as_str() {
+                        if is_location(state, package_loc) {
+                            // Check if both locations are in the same city
+                            if let (Some(pkg_city), Some(target_city)) = (get_city(state, package_loc), get_city(state, target_location)) {
+                                if pkg_city == target_city {
+                                    // Find a truck in the same city
+                                    if let Some(truck) = find_truck_in_city(state, &pkg_city) {
+                                        return Some(vec![
+                                            PlanItem::unigoal("truck_at".to_string(), truck.clone(), string_value(package_loc)),
+                                            PlanItem::unigoal("at".to_string(), arg.to_string(), string_value(&truck)),
+                                            PlanItem::unigoal("truck_at".to_string(), truck, string_value(target_location)),
+                                            PlanItem::unigoal("at".to_string(), arg.to_string(), string_value(target_location))
+                                        ]);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    })?;
+
+    // Method: Move between airports using plane
+    domain.declare_unigoal_method("at", |state: &State, arg: &str, value: &StateValue| {
+        if let Some(target_airport) = value.as_str() {
+            if is_package(state, arg) && is_airport(state, target_airport) {
+                if let Some(package_loc_val) = state.get_var("at", arg) {
+                    if let Some(package_loc) = package_loc_val.as_str() {
+                        if is_airport(state, package_loc) {
+                            // Check if airports are in different cities
+                            if let (Some(pkg_city), Some(target_city)) = (get_city(state, package_loc), get_city(state, target_airport)) {
+                                if pkg_city != target_city {
+                                    // Find a plane
+                                    if let Some(plane) = find_plane_in_city(state, &pkg_city) {
+                                        return Some(vec![
+                                            PlanItem::unigoal("plane_at".to_string(), plane.clone(), string_value(package_loc)),
+                                            PlanItem::unigoal("at".to_string(), arg.to_string(), string_value(&plane)),
+                                            PlanItem::unigoal("plane_at".to_string(), plane, string_value(target_airport)),
+                                            PlanItem::unigoal("at".to_string(), arg.to_string(), string_value(target_airport))
+                                        ]);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    })?;
+
+    // Method: Move between cities (location to location)
+    domain.declare_unigoal_method("at", |state: &State, arg: &str, value: &StateValue| {
+        if let Some(target_location) = value.as_str() {
+            if is_package(state, arg) && is_location(state, target_location) {
+                if let Some(package_loc_val) = state.get_var("at", arg) {
+                    if let Some(package_loc) = package_loc_val.as_str() {
+                        if is_location(state, package_loc) {
+                            // Check if locations are in different cities
+                            if let (Some(pkg_city), Some(target_city)) = (get_city(state, package_loc), get_city(state, target_location)) {
+                                if pkg_city != target_city {
+                                    // Find airports in both cities
+                                    if let (Some(source_airport), Some(target_airport)) = (find_airport_in_city(state, &pkg_city), find_airport_in_city(state, &target_city)) {
+                                        return Some(vec![
+                                            PlanItem::unigoal("at".to_string(), arg.to_string(), string_value(&source_airport)),
+                                            PlanItem::unigoal("at".to_string(), arg.to_string(), string_value(&target_airport)),
+                                            PlanItem::unigoal("at".to_string(), arg.to_string(), string_value(target_location))
+                                        ]);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None
+    })?;
+
+    Ok(())
+}
+
+/// Create the initial logistics state: two cities, two packages, two trucks and a plane
+pub fn create_logistics_state() -> State {
+    let mut state = State::new("state1");
+    
+    // Set up packages
+    state.set_var("at", "package1", string_value("location1"));
+    state.set_var("at", "package2", string_value("location2"));
+    
+    // Set up trucks
+    state.set_var("truck_at", "truck1", string_value("location3"));
+    state.set_var("truck_at", "truck6", string_value("location10"));
+    
+    // Set up planes
+    state.set_var("plane_at", "plane2", string_value("airport2"));
+    
+    // Set up city mappings
+    state.set_var("in_city", "location1", string_value("city1"));
+    state.set_var("in_city", "location2", string_value("city1"));
+    state.set_var("in_city", "location3", string_value("city1"));
+    state.set_var("in_city", "airport1", string_value("city1"));
+    state.set_var("in_city", "location10", string_value("city2"));
+    state.set_var("in_city", "airport2", string_value("city2"));
+    
+    // Set up entity types
+    state.declare_type("packages", &["package1", "package2"]);
+    state.declare_type("trucks", &["truck1", "truck6"]);
+    state.declare_type("airplanes", &["plane2"]);
+    state.declare_type("locations", &["location1", "location2", "location3", "airport1", "location10", "airport2"]);
+    state.declare_type("airports", &["airport1", "airport2"]);
+    state.declare_type("cities", &["city1", "city2"]);
+    
+    state
+}
+
+/// Check if an entity is a package
+pub fn is_package(state: &State, entity: &str) -> bool {
+    state.is_a(entity, "packages")
+}
+
+/// Check if an entity is a truck
+pub fn is_truck(state: &State, entity: &str) -> bool {
+    state.is_a(entity, "trucks")
+}
+
+/// Check if an entity is a plane
+pub fn is_plane(state: &State, entity: &str) -> bool {
+    state.is_a(entity, "airplanes")
+}
+
+/// Check if an entity is a location (airports included)
+pub fn is_location(state: &State, entity: &str) -> bool {
+    state.is_a(entity, "locations")
+}
+
+/// Check if an entity is an airport
+pub fn is_airport(state: &State, entity: &str) -> bool {
+    state.is_a(entity, "airports")
+}
+
+/// Get the city a location is in
+pub fn get_city(state: &State, entity: &str) -> Option<String> {
+    state.get_var("in_city", entity)?.as_str().map(|s| s.to_string())
+}
+
+/// Find a truck in the same city as the given city
+pub fn find_truck_in_city(state: &State, city: &str) -> Option<String> {
+    state.members_of("trucks").into_iter()
+        .find(|truck| {
+            let truck_loc = state.get_var("truck_at", truck).and_then(|loc| loc.as_str());
+            truck_loc.and_then(|loc| get_city(state, loc)).is_some_and(|truck_city| truck_city == city)
+        })
+        .map(str::to_string)
+}
+
+/// Find a plane in the same city as the given city, or any plane if none available
+pub fn find_plane_in_city(state: &State, city: &str) -> Option<String> {
+    let planes = state.members_of("airplanes");
+    // First try to find a plane in the same city
+    planes.iter()
+        .find(|plane| {
+            let plane_loc = state.get_var("plane_at", plane).and_then(|loc| loc.as_str());
+            plane_loc.and_then(|loc| get_city(state, loc)).is_some_and(|plane_city| plane_city == city)
+        })
+        .or(planes.first())
+        .map(|plane| plane.to_string())
+}
+
+/// Find an airport in the same city as the given city
+pub fn find_airport_in_city(state: &State, city: &str) -> Option<String> {
+    state.members_of("airports").into_iter()
+        .find(|airport| get_city(state, airport).is_some_and(|airport_city| airport_city == city))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Multigoal;
+    use crate::planning::PlannerBuilder;
+
+    #[test]
+    fn test_installed_domain_transports_between_cities() -> Result<()> {
+        let mut domain = Domain::new("installed_logistics");
+        install_actions(&mut domain)?;
+        install_unigoal_methods(&mut domain)?;
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain.clone())
+            .with_verbose_level(0)?
+            .build()?;
+
+        let mut goal = Multigoal::new("inter_city");
+        goal.set_goal("at", "package1", string_value("location10"));
+
+        let state = create_logistics_state();
+        let plan = planner.find_plan(state.clone(), vec![PlanItem::multigoal(goal)])?
+            .expect("package1 can be flown to city2");
+        assert!(plan.contains(&PlanItem::action("fly_plane", vec![string_value("plane2"), string_value("airport2")])));
+
+        let final_state = domain.apply_plan(state, &plan)?;
+        assert_eq!(final_state.get_var("at", "package1"), Some(&string_value("location10")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unigoal_action_matches_plane_at_method() -> Result<()> {
+        let mut with_method = Domain::new("plane_at_method");
+        install_actions(&mut with_method)?;
+        install_unigoal_methods(&mut with_method)?;
+
+        let mut with_action = Domain::new("plane_at_action");
+        install_actions(&mut with_action)?;
+        with_action.declare_unigoal_action("plane_at", |arg, value| {
+            PlanItem::action("fly_plane", vec![string_value(arg), value.clone()])
+        })?;
+
+        let mut goal = Multigoal::new("fly");
+        goal.set_goal("plane_at", "plane2", string_value("airport1"));
+        let todo = vec![PlanItem::multigoal(goal)];
+
+        let mut plans = Vec::new();
+        for domain in [with_method, with_action] {
+            let planner = PlannerBuilder::new()
+                .with_domain(domain)
+                .with_verbose_level(0)?
+                .build()?;
+            plans.push(planner.find_plan(create_logistics_state(), todo.clone())?);
+        }
+        assert_eq!(plans[0], Some(vec![PlanItem::action("fly_plane", vec![string_value("plane2"), string_value("airport1")])]));
+        assert_eq!(plans[0], plans[1]);
+        Ok(())
+    }
+}
@@ -3,16 +3,20 @@
 pub mod simple_htn;
 pub mod simple_hgn;
 pub mod blocks_htn;
+pub mod blocks;
+pub mod logistics;
 
 // Re-export common domain utilities
 pub use simple_htn::create_simple_htn_domain;
 pub use simple_hgn::create_simple_hgn_domain;
 pub use blocks_htn::create_blocks_htn_domain;
 
+use crate::compat::prelude::*;
+use crate::compat::HashMap;
 use crate::core::{State, StateValue};
 
 /// Helper function to check if a variable is of a specific type
-pub fn is_a(variable: &str, var_type: &str, rigid_types: &std::collections::HashMap<String, Vec<String>>) -> bool {
+pub fn is_a(variable: &str, var_type: &str, rigid_types: &HashMap<String, Vec<String>>) -> bool {
     rigid_types
         .get(var_type)
         .is_some_and(|type_list| type_list.contains(&variable.to_string()))
@@ -22,7 +26,7 @@ pub fn is_a(variable: &str, var_type: &str, rigid_types: &std::collections::Hash
 pub fn distance(
     x: &str,
     y: &str,
-    distances: &std::collections::HashMap<(String, String), f64>,
+    distances: &HashMap<(String, String), f64>,
 ) -> Option<f64> {
     distances
         .get(&(x.to_string(), y.to_string()))
@@ -40,22 +44,12 @@ pub fn create_rigid_relations() -> State {
     let mut rigid = State::new("rigid_relations");
     
     // Types
-    let mut types = std::collections::HashMap::new();
-    types.insert("person".to_string(), vec!["alice".to_string(), "bob".to_string()]);
-    types.insert("location".to_string(), vec![
-        "home_a".to_string(),
-        "home_b".to_string(),
-        "park".to_string(),
-        "station".to_string(),
-    ]);
-    types.insert("taxi".to_string(), vec!["taxi1".to_string(), "taxi2".to_string()]);
-    
-    rigid.set_var("types", "person", serde_json::to_value(&types["person"]).unwrap());
-    rigid.set_var("types", "location", serde_json::to_value(&types["location"]).unwrap());
-    rigid.set_var("types", "taxi", serde_json::to_value(&types["taxi"]).unwrap());
+    rigid.declare_type("person", &["alice", "bob"]);
+    rigid.declare_type("location", &["home_a", "home_b", "park", "station"]);
+    rigid.declare_type("taxi", &["taxi1", "taxi2"]);
     
     // Distances
-    let mut distances = std::collections::HashMap::new();
+    let mut distances = HashMap::new();
     distances.insert(("home_a".to_string(), "park".to_string()), 8.0);
     distances.insert(("home_b".to_string(), "park".to_string()), 2.0);
     distances.insert(("station".to_string(), "home_a".to_string()), 1.0);
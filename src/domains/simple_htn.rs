@@ -1,5 +1,6 @@
 //! Simple HTN domain implementation
 
+use crate::compat::prelude::*;
 use crate::core::{Domain, State, StateValue, PlanItem, string_value};
 use crate::error::Result;
 use super::{get_state_var_string, get_state_var_f64};
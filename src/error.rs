@@ -1,9 +1,10 @@
 //! Error types for GTRusthop
 
+use crate::compat::prelude::*;
 use thiserror::Error;
 
 /// Result type alias for GTRusthop operations
-pub type Result<T> = std::result::Result<T, GTRustHopError>;
+pub type Result<T> = core::result::Result<T, GTRustHopError>;
 
 /// Main error type for GTRusthop operations
 #[derive(Error, Debug, Clone, PartialEq)]
@@ -24,6 +25,10 @@ pub enum GTRustHopError {
     #[error("Verbose level must be between 0 and 3, got {level}")]
     InvalidVerboseLevel { level: i32 },
 
+    /// Domain has no actions or methods
+    #[error("Domain '{name}' has no actions or methods declared")]
+    EmptyDomain { name: String },
+
     /// Planning failed
     #[error("Planning failed: {reason}")]
     PlanningFailed { reason: String },
@@ -48,6 +53,78 @@ pub enum GTRustHopError {
     #[error("Item '{item}' isn't an action, task, unigoal, or multigoal at depth {depth}")]
     InvalidItemType { item: String, depth: usize },
 
+    /// Multigoal id not registered
+    #[error("Unknown multigoal id '{id}'")]
+    UnknownMultigoal { id: String },
+
+    /// Two different multigoals were registered under the same id
+    #[error("Multigoal id '{id}' is already registered with different goals")]
+    MultigoalIdConflict { id: String },
+
+    /// Unigoal methods were declared for a variable that no action or goal uses
+    #[error("Unigoal methods for '{var_name}' can never apply: no action or registered goal uses it")]
+    UnusedGoalVariable { var_name: String },
+
+    /// Lazy lookahead in strict mode found no command for an action
+    #[error("No command c_{action} for action '{action}'")]
+    MissingCommand { action: String },
+
+    /// Lazy lookahead found an empty plan, but goals of its todo list are unmet
+    #[error("Acting ended with an empty plan but unmet goals: {}", unmet.join(", "))]
+    ActingIncomplete { unmet: Vec<String> },
+
+    /// A task was called with a different number of arguments than declared
+    #[error("Task '{task}' takes {expected} arguments, got {got}")]
+    TaskArityMismatch { task: String, expected: usize, got: usize },
+
+    /// The recursive strategy reached the recursion limit
+    #[error("Recursive search reached depth {limit}; use the Auto or Iterative strategy for deeper problems")]
+    RecursionLimit { limit: usize },
+
+    /// The search was cancelled, see `PlannerBuilder::with_cancel_token()`
+    #[error("Planning was cancelled")]
+    Cancelled,
+
+    /// An initial state failed a domain invariant, see `Domain::add_state_invariant()`
+    #[error("State invariant violated: {message}")]
+    InvariantViolated { message: String },
+
+    /// No plan exists, see `Planner::find_plan_or_explain()`
+    #[error("No plan found: {summary}")]
+    NoPlan { summary: String },
+
+    /// Plan exceeds the allowed length
+    #[error("Plan has {len} actions, more than allowed")]
+    PlanTooLong { len: usize },
+
+    /// A deadlined goal was not satisfied in time
+    #[error("Goal {goal} was not achieved by its deadline")]
+    DeadlineMissed { goal: String },
+
+    /// Both a built-in and a custom planning strategy were configured
+    #[error("Both a planning strategy and a custom strategy are configured")]
+    ConflictingStrategyConfig,
+
+    /// A user action or method closure panicked while panic isolation was enabled
+    #[error("Method '{name}' panicked: {message}")]
+    MethodPanicked { name: String, message: String },
+
+    /// An action or command name was declared twice
+    #[error("Action '{name}' is already declared")]
+    DuplicateAction { name: String },
+
+    /// The same method was declared twice for a task, goal variable or relation
+    #[error("A method for '{name}' is already declared")]
+    DuplicateMethod { name: String },
+
+    /// Data could not be encoded to or decoded from the binary format
+    #[error("Invalid binary encoding: {reason}")]
+    InvalidEncoding { reason: String },
+
+    /// A plan being replayed hit an inapplicable action
+    #[error("Plan execution failed at item {index}")]
+    PlanExecutionFailed { index: usize },
+
     /// Action execution failed
     #[error("Action '{action}' is not applicable in current state")]
     ActionNotApplicable { action: String },
@@ -82,6 +159,11 @@ impl GTRustHopError {
         Self::DomainNotFound { name: name.into() }
     }
 
+    /// Create a new EmptyDomain error
+    pub fn empty_domain(name: impl Into<String>) -> Self {
+        Self::EmptyDomain { name: name.into() }
+    }
+
     /// Create a new PlanningFailed error
     pub fn planning_failed(reason: impl Into<String>) -> Self {
         Self::PlanningFailed {
@@ -123,6 +205,99 @@ impl GTRustHopError {
         }
     }
 
+    /// Create a new UnknownMultigoal error
+    pub fn unknown_multigoal(id: impl Into<String>) -> Self {
+        Self::UnknownMultigoal { id: id.into() }
+    }
+
+    /// Create a new MultigoalIdConflict error
+    pub fn multigoal_id_conflict(id: impl Into<String>) -> Self {
+        Self::MultigoalIdConflict { id: id.into() }
+    }
+
+    /// Create a new UnusedGoalVariable error
+    pub fn unused_goal_variable(var_name: impl Into<String>) -> Self {
+        Self::UnusedGoalVariable { var_name: var_name.into() }
+    }
+
+    /// Create a new MissingCommand error
+    pub fn missing_command(action: impl Into<String>) -> Self {
+        Self::MissingCommand { action: action.into() }
+    }
+
+    /// Create a new ActingIncomplete error
+    pub fn acting_incomplete(unmet: Vec<String>) -> Self {
+        Self::ActingIncomplete { unmet }
+    }
+
+    /// Create a new TaskArityMismatch error
+    pub fn task_arity_mismatch(task: impl Into<String>, expected: usize, got: usize) -> Self {
+        Self::TaskArityMismatch { task: task.into(), expected, got }
+    }
+
+    /// Create a new RecursionLimit error
+    pub fn recursion_limit(limit: usize) -> Self {
+        Self::RecursionLimit { limit }
+    }
+
+    /// Create a new Cancelled error
+    pub fn cancelled() -> Self {
+        Self::Cancelled
+    }
+
+    /// Create a new InvariantViolated error
+    pub fn invariant_violated(message: impl Into<String>) -> Self {
+        Self::InvariantViolated { message: message.into() }
+    }
+
+    /// Create a new NoPlan error
+    pub fn no_plan(summary: impl Into<String>) -> Self {
+        Self::NoPlan { summary: summary.into() }
+    }
+
+    /// Create a new PlanTooLong error
+    pub fn plan_too_long(len: usize) -> Self {
+        Self::PlanTooLong { len }
+    }
+
+    /// Create a new DeadlineMissed error
+    pub fn deadline_missed(goal: impl Into<String>) -> Self {
+        Self::DeadlineMissed { goal: goal.into() }
+    }
+
+    /// Create a new ConflictingStrategyConfig error
+    pub fn conflicting_strategy_config() -> Self {
+        Self::ConflictingStrategyConfig
+    }
+
+    /// Create a new MethodPanicked error
+    pub fn method_panicked(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::MethodPanicked {
+            name: name.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Create a new DuplicateAction error
+    pub fn duplicate_action(name: impl Into<String>) -> Self {
+        Self::DuplicateAction { name: name.into() }
+    }
+
+    /// Create a new DuplicateMethod error
+    pub fn duplicate_method(name: impl Into<String>) -> Self {
+        Self::DuplicateMethod { name: name.into() }
+    }
+
+    /// Create a new InvalidEncoding error
+    pub fn invalid_encoding(reason: impl Into<String>) -> Self {
+        Self::InvalidEncoding { reason: reason.into() }
+    }
+
+    /// Create a new PlanExecutionFailed error
+    pub fn plan_execution_failed(index: usize) -> Self {
+        Self::PlanExecutionFailed { index }
+    }
+
     /// Create a new ActionNotApplicable error
     pub fn action_not_applicable(action: impl Into<String>) -> Self {
         Self::ActionNotApplicable {
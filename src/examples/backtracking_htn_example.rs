@@ -123,7 +123,7 @@ pub fn create_backtracking_htn_domain() -> Result<Domain> {
 fn declare_backtracking_actions(domain: &mut Domain) -> Result<()> {
     // putv action: set the flag value
     domain.declare_action("putv", |state: &mut State, args: &[StateValue]| {
-        if args.len() >= 1 {
+        if !args.is_empty() {
             if let Some(flag_val) = args[0].as_i64() {
                 state.set_var("flag", "value", flag_val.into());
                 return Some(state.clone());
@@ -134,7 +134,7 @@ fn declare_backtracking_actions(domain: &mut Domain) -> Result<()> {
     
     // getv action: succeed only if flag matches the expected value
     domain.declare_action("getv", |state: &mut State, args: &[StateValue]| {
-        if args.len() >= 1 {
+        if !args.is_empty() {
             if let Some(expected_val) = args[0].as_i64() {
                 if let Some(current_val) = state.get_var("flag", "value") {
                     if current_val.as_i64() == Some(expected_val) {
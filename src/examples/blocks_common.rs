@@ -0,0 +1,145 @@
+//! Shared helpers for the blocks world examples
+//!
+//! The blocks examples represent a state with three state variables:
+//! - `pos[b]`: what block `b` is on (`"table"`, `"hand"`, or another block)
+//! - `clear[b]`: whether nothing is on top of `b`
+//! - `holding["hand"]`: the block being held, or `false` if the hand is empty
+
+use crate::core::State;
+use std::collections::BTreeSet;
+
+/// Check that a blocks world state is internally consistent
+///
+/// Hand-built states easily end up with contradictory `pos`/`clear`/`holding`
+/// values, which makes the planner behave in confusing ways. This checks that:
+/// - every block has a `pos` and a `clear` value, and `pos` refers to the table,
+///   the hand, or another block;
+/// - no two blocks sit on the same block;
+/// - `clear[b]` is true exactly when nothing is on `b` and `b` isn't held;
+/// - `holding["hand"]` names the one block whose `pos` is `"hand"`, or is
+///   `false` when no block is in the hand.
+///
+/// # Returns
+///
+/// `Ok(())` if the state is consistent, or the list of violations found.
+pub fn validate_blocks_state(state: &State) -> Result<(), Vec<String>> {
+    let mut violations = Vec::new();
+
+    let pos = state.get_var_map("pos");
+    let clear = state.get_var_map("clear");
+
+    // Every block mentioned in either variable
+    let blocks: BTreeSet<&String> = pos
+        .into_iter()
+        .chain(clear)
+        .flat_map(|var_map| var_map.keys())
+        .collect();
+
+    let position = |block: &str| pos.and_then(|pos| pos.get(block)).and_then(|v| v.as_str());
+    let in_hand: Vec<&String> = blocks.iter().copied().filter(|b| position(b) == Some("hand")).collect();
+
+    for block in &blocks {
+        let Some(support) = position(block) else {
+            violations.push(format!("block '{block}' has no valid pos"));
+            continue;
+        };
+
+        if support == block.as_str() {
+            violations.push(format!("block '{block}' is on itself"));
+        } else if support != "table" && support != "hand" && !blocks.contains(&support.to_string()) {
+            violations.push(format!("block '{block}' is on unknown block '{support}'"));
+        }
+    }
+
+    for block in &blocks {
+        let on_top: Vec<&String> = blocks.iter().copied().filter(|b| position(b) == Some(block.as_str())).collect();
+        if on_top.len() > 1 {
+            let names: Vec<&str> = on_top.iter().map(|b| b.as_str()).collect();
+            violations.push(format!("blocks {} all claim to be on '{block}'", names.join(", ")));
+        }
+
+        let expected = on_top.is_empty() && !in_hand.contains(block);
+        match clear.and_then(|clear| clear.get(block.as_str())).and_then(|v| v.as_bool()) {
+            Some(actual) if actual != expected => {
+                violations.push(format!("clear['{block}'] is {actual} but should be {expected}"));
+            }
+            Some(_) => {}
+            None => violations.push(format!("block '{block}' has no valid clear value")),
+        }
+    }
+
+    match state.get_var("holding", "hand") {
+        Some(held) if held.as_bool() == Some(false) => {
+            if !in_hand.is_empty() {
+                let names: Vec<&str> = in_hand.iter().map(|b| b.as_str()).collect();
+                violations.push(format!("hand is empty but {} at pos 'hand'", names.join(", ")));
+            }
+        }
+        Some(held) => match held.as_str() {
+            Some(block) if in_hand.len() == 1 && in_hand[0] == block => {}
+            Some(block) => {
+                violations.push(format!("hand holds '{block}' but {} block(s) are at pos 'hand'", in_hand.len()));
+            }
+            None => violations.push(format!("holding['hand'] has invalid value {held}")),
+        },
+        None => violations.push("holding['hand'] is not set".to_string()),
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::string_value;
+
+    /// `a` on `b`, `b` on the table, `c` in the hand
+    fn create_valid_state() -> State {
+        let mut state = State::new("valid");
+        state.set_var("pos", "a", string_value("b"));
+        state.set_var("pos", "b", string_value("table"));
+        state.set_var("pos", "c", string_value("hand"));
+        state.set_var("clear", "a", true.into());
+        state.set_var("clear", "b", false.into());
+        state.set_var("clear", "c", false.into());
+        state.set_var("holding", "hand", string_value("c"));
+        state
+    }
+
+    #[test]
+    fn test_valid_blocks_state() {
+        assert_eq!(validate_blocks_state(&create_valid_state()), Ok(()));
+    }
+
+    #[test]
+    fn test_corrupted_blocks_states() {
+        // Two blocks on the same support
+        let mut state = create_valid_state();
+        state.set_var("pos", "c", string_value("b"));
+        state.set_var("holding", "hand", false.into());
+        let violations = validate_blocks_state(&state).unwrap_err();
+        assert!(violations.iter().any(|v| v.contains("all claim to be on 'b'")));
+
+        // A clear block with something on it
+        let mut state = create_valid_state();
+        state.set_var("clear", "b", true.into());
+        let violations = validate_blocks_state(&state).unwrap_err();
+        assert_eq!(violations, vec!["clear['b'] is true but should be false".to_string()]);
+
+        // Holding a block that isn't in the hand
+        let mut state = create_valid_state();
+        state.set_var("holding", "hand", string_value("a"));
+        let violations = validate_blocks_state(&state).unwrap_err();
+        assert!(violations.iter().any(|v| v.contains("hand holds 'a'")));
+
+        // A block without a position
+        let mut state = create_valid_state();
+        state.set_var("clear", "d", true.into());
+        let violations = validate_blocks_state(&state).unwrap_err();
+        assert_eq!(violations, vec!["block 'd' has no valid pos".to_string()]);
+    }
+}
@@ -56,11 +56,11 @@
 //! - **Complex planning scenarios**: Including the famous Sussman Anomaly
 //! - **Near-optimal planning**: Using the Gupta-Nau algorithm for efficient block manipulation
 
-use crate::core::{State, Domain, PlanItem, Multigoal, string_value, StateValue};
+use crate::core::{State, Domain, PlanItem, Multigoal, string_value};
+use crate::domains::blocks::{install_achieve_method, install_blocks_actions, install_blocks_methods};
 use crate::planning::PlannerBuilder;
 use crate::error::Result;
 use std::collections::HashMap;
-use std::sync::Arc;
 
 /// Run comprehensive blocks HTN examples demonstrating the Gupta-Nau algorithm.
 ///
@@ -170,127 +170,14 @@ pub fn create_blocks_htn_domain_with_multigoals(multigoals: HashMap<String, Mult
 
 /// Declare all primitive actions for the blocks world domain.
 ///
-/// This function defines the four fundamental blocks world actions with their
-/// preconditions and effects:
-///
-/// - **`pickup(block)`**: Pick up a block from the table
-/// - **`unstack(block, under_block)`**: Remove a block from another block
-/// - **`putdown(block)`**: Put a held block on the table
-/// - **`stack(block, under_block)`**: Put a held block on another block
-///
-/// Each action includes proper precondition checking and state updates following
-/// the standard blocks world semantics.
-///
-/// # Arguments
-///
-/// * `domain` - The domain to add the actions to
-///
-/// # Returns
-///
-/// `Ok(())` if all actions are declared successfully, or an error if any declaration fails.
+/// The actions (pickup, unstack, putdown, stack) live in `domains::blocks` so
+/// other domains can reuse them.
 fn declare_blocks_actions(domain: &mut Domain) -> Result<()> {
-    // pickup action: pick up a block from the table
-    domain.declare_action("pickup", |state: &mut State, args: &[crate::core::StateValue]| {
-        if args.len() >= 1 {
-            if let Some(block) = args[0].as_str() {
-                // Check preconditions: block on table, clear, hand empty
-                if let (Some(pos), Some(clear), Some(holding)) = (
-                    state.get_var("pos", block),
-                    state.get_var("clear", block),
-                    state.get_var("holding", "hand")
-                ) {
-                    if pos.as_str() == Some("table") &&
-                       clear.as_bool() == Some(true) &&
-                       holding.as_bool() == Some(false) {
-                        // Apply effects
-                        state.set_var("pos", block, string_value("hand"));
-                        state.set_var("clear", block, false.into());
-                        state.set_var("holding", "hand", string_value(block));
-                        return Some(state.clone());
-                    }
-                }
-            }
-        }
-        None
-    })?;
-
-    // unstack action: remove a block from another block
-    domain.declare_action("unstack", |state: &mut State, args: &[crate::core::StateValue]| {
-        if args.len() >= 2 {
-            if let (Some(block1), Some(block2)) = (args[0].as_str(), args[1].as_str()) {
-                // Check preconditions: block1 on block2, block1 clear, hand empty
-                if let (Some(pos), Some(clear), Some(holding)) = (
-                    state.get_var("pos", block1),
-                    state.get_var("clear", block1),
-                    state.get_var("holding", "hand")
-                ) {
-                    if pos.as_str() == Some(block2) &&
-                       block2 != "table" &&
-                       clear.as_bool() == Some(true) &&
-                       holding.as_bool() == Some(false) {
-                        // Apply effects
-                        state.set_var("pos", block1, string_value("hand"));
-                        state.set_var("clear", block1, false.into());
-                        state.set_var("holding", "hand", string_value(block1));
-                        state.set_var("clear", block2, true.into());
-                        return Some(state.clone());
-                    }
-                }
-            }
-        }
-        None
-    })?;
-
-    // putdown action: put a block on the table
-    domain.declare_action("putdown", |state: &mut State, args: &[crate::core::StateValue]| {
-        if args.len() >= 1 {
-            if let Some(block) = args[0].as_str() {
-                // Check preconditions: holding block
-                if let Some(pos) = state.get_var("pos", block) {
-                    if pos.as_str() == Some("hand") {
-                        // Apply effects
-                        state.set_var("pos", block, string_value("table"));
-                        state.set_var("clear", block, true.into());
-                        state.set_var("holding", "hand", false.into());
-                        return Some(state.clone());
-                    }
-                }
-            }
-        }
-        None
-    })?;
-
-    // stack action: put a block on another block
-    domain.declare_action("stack", |state: &mut State, args: &[crate::core::StateValue]| {
-        if args.len() >= 2 {
-            if let (Some(block1), Some(block2)) = (args[0].as_str(), args[1].as_str()) {
-                // Check preconditions: holding block1, block2 clear
-                if let (Some(pos1), Some(clear2)) = (
-                    state.get_var("pos", block1),
-                    state.get_var("clear", block2)
-                ) {
-                    if pos1.as_str() == Some("hand") && clear2.as_bool() == Some(true) {
-                        // Apply effects
-                        state.set_var("pos", block1, string_value(block2));
-                        state.set_var("clear", block1, true.into());
-                        state.set_var("holding", "hand", false.into());
-                        state.set_var("clear", block2, false.into());
-                        return Some(state.clone());
-                    }
-                }
-            }
-        }
-        None
-    })?;
-
-    Ok(())
+    install_blocks_actions(domain)
 }
 
 /// Declare all HTN task methods for the blocks world domain.
 ///
-/// This function defines the three core HTN task methods that implement hierarchical
-/// task decomposition for blocks world planning:
-///
 /// - **`achieve`**: The main planning method that implements the Gupta-Nau algorithm
 /// - **`take`**: Decomposes block pickup into primitive actions (pickup/unstack)
 /// - **`put`**: Decomposes block placement into primitive actions (putdown/stack)
@@ -306,469 +193,9 @@ fn declare_blocks_actions(domain: &mut Domain) -> Result<()> {
 /// take(block)        → pickup(block) | unstack(block, under)
 /// put(block, dest)   → putdown(block) | stack(block, dest)
 /// ```
-///
-/// # Arguments
-///
-/// * `domain` - The domain to add the task methods to
-///
-/// # Returns
-///
-/// `Ok(())` if all task methods are declared successfully, or an error if any declaration fails.
 fn declare_blocks_task_methods(domain: &mut Domain, multigoals: HashMap<String, Multigoal>) -> Result<()> {
-    // Create a shared reference to multigoals for the closures
-    let multigoals_ref = Arc::new(multigoals);
-    let multigoals_for_achieve = multigoals_ref.clone();
-
-    // Task method for 'achieve' - Python equivalent: gtpyhop.declare_task_methods('achieve',m_moveblocks)
-    // This implements the core HTN planning logic using only task methods
-    domain.declare_task_method("achieve", move |state: &State, args: &[StateValue]| {
-        if args.len() >= 1 {
-            if let Some(goal_id) = args[0].as_str() {
-                // Retrieve the multigoal from our captured multigoals
-                if let Some(mgoal) = multigoals_for_achieve.get(goal_id) {
-                    // Use the exact same logic as Python m_moveblocks
-                    return m_moveblocks_htn(state, mgoal, goal_id);
-                }
-            }
-        }
-        None
-    })?;
-
-    // Task method for 'take' - Python equivalent: gtpyhop.declare_task_methods('take',m_take)
-    domain.declare_task_method("take", |state: &State, args: &[StateValue]| {
-        if args.len() >= 1 {
-            if let Some(b1) = args[0].as_str() {
-                return m_take(state, b1);
-            }
-        }
-        None
-    })?;
-
-    // Task method for 'put' - Python equivalent: gtpyhop.declare_task_methods('put',m_put)
-    domain.declare_task_method("put", |state: &State, args: &[StateValue]| {
-        if args.len() >= 2 {
-            if let (Some(b1), Some(b2)) = (args[0].as_str(), args[1].as_str()) {
-                return m_put(state, b1, b2);
-            }
-        }
-        None
-    })?;
-
-    // Task method for 'take' - picking up a block
-    domain.declare_task_method("take", |state: &State, args: &[crate::core::StateValue]| {
-        if args.len() >= 1 {
-            if let Some(block) = args[0].as_str() {
-                return m_take(state, block);
-            }
-        }
-        None
-    })?;
-
-    // Task method for 'put' - putting down a block
-    domain.declare_task_method("put", |state: &State, args: &[crate::core::StateValue]| {
-        if args.len() >= 2 {
-            if let (Some(block1), Some(block2)) = (args[0].as_str(), args[1].as_str()) {
-                return m_put(state, block1, block2);
-            }
-        }
-        None
-    })?;
-
-    Ok(())
-}
-
-/// Check if a block is "done" and doesn't need to be moved.
-///
-/// A block is considered "done" if it and all blocks below it will never need to be moved
-/// to achieve the multigoal. This is a key optimization in the Gupta-Nau algorithm.
-///
-/// The algorithm works recursively:
-/// 1. If the block is "table", it's always done
-/// 2. If the block has a goal position and isn't there, it's not done
-/// 3. If the block is on the table, it's done
-/// 4. Otherwise, recursively check the block below it
-///
-/// **Python equivalent**: `def is_done(b1,state,mgoal)`
-///
-/// # Arguments
-///
-/// * `b1` - The block to check
-/// * `state` - The current world state
-/// * `mgoal` - The multigoal being achieved
-///
-/// # Returns
-///
-/// `true` if the block doesn't need to be moved, `false` otherwise.
-///
-/// # Example
-///
-/// ```rust,no_run
-/// # use gtrusthop::core::{State, Multigoal};
-/// # let state = State::new("test");
-/// # let goal = Multigoal::new("test");
-/// // Block 'a' is on the table and has no goal - it's done
-/// assert!(is_done("a", &state, &goal));
-///
-/// // Block 'b' needs to move to block 'c' but is currently on table - not done
-/// assert!(!is_done("b", &state, &goal));
-/// ```
-fn is_done(b1: &str, state: &State, mgoal: &Multigoal) -> bool {
-    if b1 == "table" {
-        return true;
-    }
-
-    // Check if b1 has a goal position and is not there
-    if let Some(goal_pos) = mgoal.get_goal("pos", b1) {
-        if let Some(current_pos) = state.get_var("pos", b1) {
-            if goal_pos != current_pos {
-                return false;
-            }
-        }
-    }
-
-    // Check if b1 is on table
-    if let Some(current_pos) = state.get_var("pos", b1) {
-        if current_pos.as_str() == Some("table") {
-            return true;
-        }
-
-        // Recursively check the block below
-        if let Some(below_block) = current_pos.as_str() {
-            if below_block != "table" && below_block != "hand" {
-                return is_done(below_block, state, mgoal);
-            }
-        }
-    }
-
-    true
-}
-
-/// Determine the planning status of a block according to the Gupta-Nau algorithm.
-///
-/// This function categorizes blocks into different states that guide the HTN planning
-/// decisions. The status determines what action should be taken with the block:
-///
-/// - **"done"**: Block and all blocks below it never need to be moved
-/// - **"inaccessible"**: Block is not clear (has another block on top)
-/// - **"move-to-table"**: Block should be moved to the table (either has no goal or goal is table)
-/// - **"move-to-block"**: Block can be moved directly to its goal position
-/// - **"waiting"**: Block cannot be moved to its goal yet (target not ready)
-///
-/// This status classification is central to the Gupta-Nau algorithm's efficiency,
-/// allowing it to make optimal decisions about which blocks to move when.
-///
-/// **Python equivalent**: `def status(b1,state,mgoal)`
-///
-/// # Arguments
-///
-/// * `b1` - The block to analyze
-/// * `state` - The current world state
-/// * `mgoal` - The multigoal being achieved
-///
-/// # Returns
-///
-/// A string indicating the block's planning status.
-///
-/// # Example
-///
-/// ```rust,no_run
-/// # use gtrusthop::core::{State, Multigoal};
-/// # let state = State::new("test");
-/// # let goal = Multigoal::new("test");
-/// let status = status("a", &state, &goal);
-/// match status.as_str() {
-///     "move-to-block" => println!("Block a can move to its goal now"),
-///     "waiting" => println!("Block a must wait for other blocks to move first"),
-///     "done" => println!("Block a is already in the right place"),
-///     _ => println!("Block a has status: {}", status),
-/// }
-/// ```
-fn status(b1: &str, state: &State, mgoal: &Multigoal) -> String {
-    // Check if block is done (doesn't need to be moved)
-    if is_done(b1, state, mgoal) {
-        return "done".to_string();
-    }
-
-    // Check if block is clear
-    if let Some(clear) = state.get_var("clear", b1) {
-        if clear.as_bool() != Some(true) {
-            return "inaccessible".to_string();
-        }
-    }
-
-    // Check goal position
-    if let Some(goal_pos) = mgoal.get_goal("pos", b1) {
-        if let Some(goal_str) = goal_pos.as_str() {
-            if goal_str == "table" {
-                return "move-to-table".to_string();
-            } else {
-                // Check if target block is done and clear
-                if is_done(goal_str, state, mgoal) {
-                    if let Some(target_clear) = state.get_var("clear", goal_str) {
-                        if target_clear.as_bool() == Some(true) {
-                            return "move-to-block".to_string();
-                        }
-                    }
-                }
-                return "waiting".to_string();
-            }
-        }
-    } else {
-        return "move-to-table".to_string();
-    }
-
-    "waiting".to_string()
-}
-
-
-
-
-
-/// Get all clear blocks in the current state.
-///
-/// A block is "clear" if no other block is stacked on top of it and it's not being held.
-/// Only clear blocks can be picked up or unstacked, making this function essential
-/// for determining which blocks are available for manipulation.
-///
-/// This function is used by the Gupta-Nau algorithm to iterate through blocks that
-/// could potentially be moved in the current planning step.
-///
-/// **Python equivalent**: `def all_clear_blocks(state)`
-///
-/// # Arguments
-///
-/// * `state` - The current world state
-///
-/// # Returns
-///
-/// A vector of block names that are currently clear and can be manipulated.
-///
-/// # Example
-///
-/// ```rust,no_run
-/// # use gtrusthop::core::State;
-/// # let state = State::new("test");
-/// let clear_blocks = all_clear_blocks(&state);
-/// for block in clear_blocks {
-///     println!("Block {} is available for pickup/unstack", block);
-/// }
-/// ```
-fn all_clear_blocks(state: &State) -> Vec<String> {
-    let mut clear_blocks = Vec::new();
-
-    if let Some(clear_data) = state.get_var_map("clear") {
-        for (block, clear_value) in clear_data {
-            if clear_value.as_bool() == Some(true) {
-                clear_blocks.push(block.clone());
-            }
-        }
-    }
-
-    clear_blocks
-}
-
-/// HTN implementation of the Gupta-Nau blocks world planning algorithm.
-///
-/// This is the core method that implements the near-optimal blocks-world planning
-/// algorithm described in Gupta & Nau (1992). It follows the HTN paradigm by
-/// decomposing the multigoal achievement task into sequences of 'take' and 'put' tasks.
-///
-/// ## Algorithm Overview
-///
-/// The method implements the following strategy:
-///
-/// 1. **Direct moves**: Look for clear blocks that can be moved directly to their
-///    final positions without interfering with other goals.
-///
-/// 2. **Clearing moves**: If no direct moves are possible, move blocks to the table
-///    to clear the way for other blocks.
-///
-/// 3. **Termination**: If no blocks need moving, the goal is achieved.
-///
-/// ## HTN Task Decomposition
-///
-/// Unlike the HGN approach which uses multigoal methods, this HTN implementation
-/// decomposes the achievement task into:
-/// - `take(block)` - Pick up a specific block
-/// - `put(block, destination)` - Place a block at its destination
-/// - `achieve(goal_id)` - Recursively achieve the remaining goals
-///
-/// **Python equivalent**: `def m_moveblocks(state,mgoal)`
-///
-/// # Arguments
-///
-/// * `state` - The current world state
-/// * `mgoal` - The multigoal to achieve
-/// * `goal_id` - The registry ID for recursive achieve calls
-///
-/// # Returns
-///
-/// A task decomposition (sequence of `PlanItem::task` calls) or `None` if no decomposition applies.
-///
-/// # References
-///
-/// N. Gupta and D. S. Nau. "On the complexity of blocks-world planning."
-/// *Artificial Intelligence* 56(2-3):223–254, 1992.
-fn m_moveblocks_htn(state: &State, mgoal: &Multigoal, goal_id: &str) -> Option<Vec<PlanItem>> {
-    // Look for a clear block that can be moved to its final location
-    for x in all_clear_blocks(state) {
-        let xstat = status(&x, state, mgoal);
-        if xstat == "move-to-block" {
-            if let Some(target_pos) = mgoal.get_goal("pos", &x) {
-                return Some(vec![
-                    PlanItem::task("take", vec![string_value(&x)]),
-                    PlanItem::task("put", vec![string_value(&x), target_pos.clone()]),
-                    PlanItem::task("achieve", vec![string_value(goal_id)])
-                ]);
-            }
-        } else if xstat == "move-to-table" {
-            return Some(vec![
-                PlanItem::task("take", vec![string_value(&x)]),
-                PlanItem::task("put", vec![string_value(&x), string_value("table")]),
-                PlanItem::task("achieve", vec![string_value(goal_id)])
-            ]);
-        }
-    }
-
-    // If we get here, no blocks can be moved to their final locations
-    for x in all_clear_blocks(state) {
-        if status(&x, state, mgoal) == "waiting" {
-            if let Some(pos) = state.get_var("pos", &x) {
-                if pos.as_str() != Some("table") {
-                    return Some(vec![
-                        PlanItem::task("take", vec![string_value(&x)]),
-                        PlanItem::task("put", vec![string_value(&x), string_value("table")]),
-                        PlanItem::task("achieve", vec![string_value(goal_id)])
-                    ]);
-                }
-            }
-        }
-    }
-
-    // If we get here, there are no blocks that need moving
-    Some(vec![])
-}
-
-
-
-/// HTN task method for taking (picking up) a block.
-///
-/// This method decomposes the abstract 'take' task into the appropriate primitive action:
-/// - `pickup(block)` if the block is on the table
-/// - `unstack(block, under_block)` if the block is on another block
-///
-/// The method only succeeds if the block is clear (no other block is on top of it).
-/// This ensures that the preconditions for the primitive actions will be satisfied.
-///
-/// ## HTN Decomposition
-///
-/// ```text
-/// take(block) → pickup(block)     [if block is on table]
-///            → unstack(block, X)  [if block is on block X]
-/// ```
-///
-/// **Python equivalent**: `def m_take(state,b1)`
-///
-/// # Arguments
-///
-/// * `state` - The current world state
-/// * `b1` - The block to pick up
-///
-/// # Returns
-///
-/// A single-action plan (`pickup` or `unstack`) or `None` if the block cannot be taken.
-///
-/// # Example
-///
-/// ```rust,no_run
-/// # use gtrusthop::core::{State, PlanItem, string_value};
-/// # let state = State::new("test");
-/// // Block 'a' is on the table and clear
-/// let plan = m_take(&state, "a");
-/// assert_eq!(plan, Some(vec![PlanItem::action("pickup", vec![string_value("a")])]));
-///
-/// // Block 'b' is on block 'c' and clear
-/// let plan = m_take(&state, "b");
-/// assert_eq!(plan, Some(vec![PlanItem::action("unstack", vec![string_value("b"), string_value("c")])]));
-/// ```
-fn m_take(state: &State, b1: &str) -> Option<Vec<PlanItem>> {
-    // Generate either a pickup or an unstack subtask for b1
-    if let Some(clear) = state.get_var("clear", b1) {
-        if clear.as_bool() == Some(true) {
-            if let Some(pos) = state.get_var("pos", b1) {
-                if pos.as_str() == Some("table") {
-                    return Some(vec![PlanItem::action("pickup", vec![string_value(b1)])]);
-                } else if let Some(under_block) = pos.as_str() {
-                    if under_block != "hand" {
-                        return Some(vec![PlanItem::action("unstack", vec![
-                            string_value(b1),
-                            string_value(under_block)
-                        ])]);
-                    }
-                }
-            }
-        }
-    }
-    None
-}
-
-/// HTN task method for putting (placing) a block at a destination.
-///
-/// This method decomposes the abstract 'put' task into the appropriate primitive action:
-/// - `putdown(block)` if the destination is the table
-/// - `stack(block, destination)` if the destination is another block
-///
-/// The method only succeeds if the block is currently being held (in the hand).
-/// This ensures that the preconditions for the primitive actions will be satisfied.
-///
-/// ## HTN Decomposition
-///
-/// ```text
-/// put(block, table) → putdown(block)
-/// put(block, dest)  → stack(block, dest)  [if dest is another block]
-/// ```
-///
-/// **Python equivalent**: `def m_put(state,b1,b2)`
-///
-/// # Arguments
-///
-/// * `state` - The current world state
-/// * `b1` - The block to place (must be currently held)
-/// * `b2` - The destination ("table" or another block name)
-///
-/// # Returns
-///
-/// A single-action plan (`putdown` or `stack`) or `None` if the block is not being held.
-///
-/// # Example
-///
-/// ```rust,no_run
-/// # use gtrusthop::core::{State, PlanItem, string_value};
-/// # let state = State::new("test");
-/// // Block 'a' is being held, put it on the table
-/// let plan = m_put(&state, "a", "table");
-/// assert_eq!(plan, Some(vec![PlanItem::action("putdown", vec![string_value("a")])]));
-///
-/// // Block 'a' is being held, stack it on block 'b'
-/// let plan = m_put(&state, "a", "b");
-/// assert_eq!(plan, Some(vec![PlanItem::action("stack", vec![string_value("a"), string_value("b")])]));
-/// ```
-fn m_put(state: &State, b1: &str, b2: &str) -> Option<Vec<PlanItem>> {
-    // Generate either a putdown or a stack subtask for b1
-    // b2 is b1's destination: either the table or another block
-    if let Some(holding) = state.get_var("holding", "hand") {
-        if holding.as_str() == Some(b1) {
-            if b2 == "table" {
-                return Some(vec![PlanItem::action("putdown", vec![string_value(b1)])]);
-            } else {
-                return Some(vec![PlanItem::action("stack", vec![
-                    string_value(b1),
-                    string_value(b2)
-                ])]);
-            }
-        }
-    }
-    None
+    install_achieve_method(domain, multigoals)?;
+    install_blocks_methods(domain)
 }
 
 /// Test simple actions that should fail
@@ -1070,12 +497,210 @@ fn create_complex_state() -> State {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domains::blocks::{all_clear_blocks, is_done, m_take, status};
 
     #[test]
     fn test_run_blocks_htn_examples() -> Result<()> {
         run_blocks_htn_examples()
     }
 
+    #[test]
+    fn test_sussman_anomaly_via_builtin_achieve_task() -> Result<()> {
+        use crate::core::ACHIEVE_MULTIGOAL_TASK;
+        use crate::error::GTRustHopError;
+        use crate::domains::blocks::m_moveblocks;
+
+        let mut domain = Domain::new("blocks_hgn");
+        install_blocks_actions(&mut domain)?;
+        install_blocks_methods(&mut domain)?;
+        domain.declare_multigoal_method(|state: &State, mgoal: &Multigoal| {
+            let mut todo = m_moveblocks(state, mgoal, "")?;
+            // Continue with the multigoal itself rather than an `achieve` task
+            todo.pop()?;
+            todo.push(PlanItem::multigoal(mgoal.clone()));
+            Some(todo)
+        })?;
+        let mut goal = Multigoal::new("sussman_goal");
+        goal.set_goal("pos", "a", string_value("b"));
+        goal.set_goal("pos", "b", string_value("c"));
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_multigoal(goal)
+            .with_verbose_level(0)?
+            .build()?;
+
+        let todo = vec![PlanItem::task(ACHIEVE_MULTIGOAL_TASK, vec![string_value("goal_sussman_goal")])];
+        let plan = planner.find_plan(create_sussman_state(), todo)?;
+        crate::examples::assert_plan_eq(&plan.unwrap(), &[
+            "(unstack c a)",
+            "(putdown c)",
+            "(pickup b)",
+            "(stack b c)",
+            "(pickup a)",
+            "(stack a b)",
+        ]);
+
+        let unknown = vec![PlanItem::task(ACHIEVE_MULTIGOAL_TASK, vec![string_value("goal_missing")])];
+        assert_eq!(planner.find_plan(create_sussman_state(), unknown), Err(GTRustHopError::unknown_multigoal("goal_missing")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sussman_anomaly_choice_trace() -> Result<()> {
+        let mut goal = Multigoal::new("sussman_goal");
+        goal.set_goal("pos", "a", string_value("b"));
+        goal.set_goal("pos", "b", string_value("c"));
+
+        let planner = PlannerBuilder::new()
+            .with_domain(create_blocks_htn_domain()?)
+            .with_multigoal(goal)
+            .with_verbose_level(0)?
+            .build()?;
+
+        let todo = vec![PlanItem::task("achieve", vec![string_value("goal_sussman_goal")])];
+        let (plan, choices) = planner.find_plan_with_choices(create_sussman_state(), todo.clone())?;
+        assert_eq!(plan, planner.find_plan(create_sussman_state(), todo.clone())?);
+        // Three moves, each a take and a put, then the final check that the goal holds
+        let mut expected: Vec<(String, usize)> = ["achieve", "take", "put"].repeat(3).into_iter().map(|name| (name.to_string(), 0)).collect();
+        expected.push(("achieve".to_string(), 0));
+        assert_eq!(choices, expected);
+        assert_eq!(planner.find_plan_with_choices(create_sussman_state(), todo)?.1, choices);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sussman_anomaly_replay_choices() -> Result<()> {
+        use crate::planning::PlanningStrategy;
+
+        let mut goal = Multigoal::new("sussman_goal");
+        goal.set_goal("pos", "a", string_value("b"));
+        goal.set_goal("pos", "b", string_value("c"));
+        let todo = vec![PlanItem::task("achieve", vec![string_value("goal_sussman_goal")])];
+
+        let recorder = PlannerBuilder::new()
+            .with_domain(create_blocks_htn_domain()?)
+            .with_multigoal(goal.clone())
+            .with_verbose_level(0)?
+            .build()?;
+        let (plan, choices) = recorder.find_plan_with_choices(create_sussman_state(), todo.clone())?;
+
+        let replayer = PlannerBuilder::new()
+            .with_domain(create_blocks_htn_domain()?)
+            .with_multigoal(goal)
+            .with_strategy(PlanningStrategy::Recursive)
+            .with_verbose_level(0)?
+            .build()?;
+        assert_eq!(replayer.replay_choices(create_sussman_state(), todo.clone(), &choices)?, plan);
+
+        // `take` has a single method
+        let mut wrong = choices.clone();
+        wrong[1].1 = 1;
+        assert!(replayer.replay_choices(create_sussman_state(), todo.clone(), &wrong).is_err());
+        assert!(replayer.replay_choices(create_sussman_state(), todo, &choices[..4]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_plan_or_explain_names_stuck_task() -> Result<()> {
+        let planner = PlannerBuilder::new()
+            .with_domain(create_blocks_htn_domain()?)
+            .with_verbose_level(0)?
+            .build()?;
+        let sussman = create_sussman_state();
+        let plan = planner.find_plan_or_explain(sussman.clone(), vec![PlanItem::task("take", vec![string_value("c")])])?;
+        assert_eq!(plan.len(), 1);
+
+        // c is on a, so a can't be taken
+        let error = planner.find_plan_or_explain(sussman, vec![PlanItem::task("take", vec![string_value("a")])]).unwrap_err();
+        assert!(matches!(&error, crate::error::GTRustHopError::NoPlan { summary } if summary.contains("stuck at (take a)")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_take_without_block_is_arity_error() -> Result<()> {
+        let planner = PlannerBuilder::new()
+            .with_domain(create_blocks_htn_domain()?)
+            .with_verbose_level(0)?
+            .build()?;
+        let result = planner.find_plan(create_sussman_state(), vec![PlanItem::task("take", vec![])]);
+        assert_eq!(result, Err(crate::error::GTRustHopError::task_arity_mismatch("take", 1, 0)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_hierarchical_plan_keeps_decomposition() -> Result<()> {
+        use crate::planning::HPlanNode;
+
+        let mut state = State::new("two_blocks");
+        state.set_var("pos", "a", string_value("table"));
+        state.set_var("pos", "b", string_value("table"));
+        state.set_var("clear", "a", true.into());
+        state.set_var("clear", "b", true.into());
+        state.set_var("holding", "hand", false.into());
+        let mut goal = Multigoal::new("a_on_b");
+        goal.set_goal("pos", "a", string_value("b"));
+        let todo = vec![PlanItem::task("achieve", vec![string_value("goal_a_on_b")])];
+
+        let planner = PlannerBuilder::new()
+            .with_domain(create_blocks_htn_domain()?)
+            .with_multigoal(goal)
+            .with_verbose_level(0)?
+            .build()?;
+        let hplan = planner.find_hierarchical_plan(state.clone(), todo.clone())?.expect("plan");
+
+        let take = &hplan.roots[0].children()[0];
+        assert_eq!(hplan.roots[0].name(), "achieve");
+        assert_eq!(take.name(), "take");
+        assert!(matches!(&take.children()[0], HPlanNode::Action { name, .. } if name == "pickup"));
+        assert_eq!(Some(hplan.flatten()), planner.find_plan(state, todo)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sussman_anomaly_exact_plan() -> Result<()> {
+        let mut goal = Multigoal::new("sussman_goal");
+        goal.set_goal("pos", "a", string_value("b"));
+        goal.set_goal("pos", "b", string_value("c"));
+
+        let planner = PlannerBuilder::new()
+            .with_domain(create_blocks_htn_domain()?)
+            .with_multigoal(goal)
+            .with_verbose_level(0)?
+            .build()?;
+
+        let plan = planner.find_plan(create_sussman_state(), vec![PlanItem::task("achieve", vec![string_value("goal_sussman_goal")])])?;
+        crate::examples::assert_plan_eq(&plan.unwrap(), &[
+            "(unstack c a)",
+            "(putdown c)",
+            "(pickup b)",
+            "(stack b c)",
+            "(pickup a)",
+            "(stack a b)",
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_achieve_unknown_multigoal_id() -> Result<()> {
+        let mut goal = Multigoal::new("goal1a");
+        goal.set_goal("pos", "c", string_value("b"));
+
+        let planner = PlannerBuilder::new()
+            .with_domain(create_blocks_htn_domain()?)
+            .with_multigoal(goal)
+            .with_verbose_level(0)?
+            .build()?;
+
+        // The builder registers the goal as "goal_goal1a"
+        let result = planner.find_plan(create_test_state1(), vec![PlanItem::task("achieve", vec![string_value("goal1a")])]);
+        assert!(matches!(result, Err(crate::error::GTRustHopError::UnknownMultigoal { ref id }) if id == "goal1a"));
+
+        let plan = planner.find_plan(create_test_state1(), vec![PlanItem::task("achieve", vec![string_value("goal_goal1a")])])?;
+        assert!(plan.is_some());
+        Ok(())
+    }
+
     #[test]
     fn test_create_blocks_htn_domain() -> Result<()> {
         let domain = create_blocks_htn_domain()?;
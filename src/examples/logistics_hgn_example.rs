@@ -30,7 +30,8 @@
 //! 
 //! **Python equivalent**: `logistics_hgn.py`
 
-use crate::core::{State, Domain, PlanItem, Multigoal, string_value, StateValue};
+use crate::core::{Domain, PlanItem, Multigoal, string_value};
+use crate::domains::logistics::{create_logistics_state, install_actions, install_unigoal_methods};
 use crate::planning::PlannerBuilder;
 use crate::error::Result;
 
@@ -108,418 +109,12 @@ pub fn create_logistics_hgn_domain() -> Result<Domain> {
 
 /// Declare actions for the logistics domain
 fn declare_logistics_actions(domain: &mut Domain) -> Result<()> {
-    // Drive truck action
-    domain.declare_action("drive_truck", |state: &mut State, args: &[StateValue]| {
-        if args.len() >= 2 {
-            if let (Some(truck), Some(location)) = (args[0].as_str(), args[1].as_str()) {
-                state.set_var("truck_at", truck, string_value(location));
-                return Some(state.clone());
-            }
-        }
-        None
-    })?;
-    
-    // Load truck action
-    domain.declare_action("load_truck", |state: &mut State, args: &[StateValue]| {
-        if args.len() >= 2 {
-            if let (Some(package), Some(truck)) = (args[0].as_str(), args[1].as_str()) {
-                state.set_var("at", package, string_value(truck));
-                return Some(state.clone());
-            }
-        }
-        None
-    })?;
-    
-    // Unload truck action
-    domain.declare_action("unload_truck", |state: &mut State, args: &[StateValue]| {
-        if args.len() >= 2 {
-            if let (Some(package), Some(location)) = (args[0].as_str(), args[1].as_str()) {
-                // Check if package is on a truck and truck is at the location
-                if let Some(truck_val) = state.get_var("at", package) {
-                    if let Some(truck) = truck_val.as_str() {
-                        if let Some(truck_loc_val) = state.get_var("truck_at", truck) {
-                            if let Some(truck_loc) = truck_loc_val.as_str() {
-                                if truck_loc == location {
-                                    state.set_var("at", package, string_value(location));
-                                    return Some(state.clone());
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        None
-    })?;
-    
-    // Fly plane action
-    domain.declare_action("fly_plane", |state: &mut State, args: &[StateValue]| {
-        if args.len() >= 2 {
-            if let (Some(plane), Some(airport)) = (args[0].as_str(), args[1].as_str()) {
-                state.set_var("plane_at", plane, string_value(airport));
-                return Some(state.clone());
-            }
-        }
-        None
-    })?;
-    
-    // Load plane action
-    domain.declare_action("load_plane", |state: &mut State, args: &[StateValue]| {
-        if args.len() >= 2 {
-            if let (Some(package), Some(plane)) = (args[0].as_str(), args[1].as_str()) {
-                state.set_var("at", package, string_value(plane));
-                return Some(state.clone());
-            }
-        }
-        None
-    })?;
-    
-    // Unload plane action
-    domain.declare_action("unload_plane", |state: &mut State, args: &[StateValue]| {
-        if args.len() >= 2 {
-            if let (Some(package), Some(airport)) = (args[0].as_str(), args[1].as_str()) {
-                // Check if package is on a plane and plane is at the airport
-                if let Some(plane_val) = state.get_var("at", package) {
-                    if let Some(plane) = plane_val.as_str() {
-                        if let Some(plane_loc_val) = state.get_var("plane_at", plane) {
-                            if let Some(plane_loc) = plane_loc_val.as_str() {
-                                if plane_loc == airport {
-                                    state.set_var("at", package, string_value(airport));
-                                    return Some(state.clone());
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        None
-    })?;
-    
-    Ok(())
+    install_actions(domain)
 }
 
 /// Declare unigoal methods for the logistics domain (HGN approach)
 fn declare_logistics_unigoal_methods(domain: &mut Domain) -> Result<()> {
-    // Unigoal methods for 'at' (package location)
-    
-    // Method: Load truck
-    domain.declare_unigoal_method("at", |state: &State, arg: &str, value: &StateValue| {
-        if let Some(truck) = value.as_str() {
-            // Check if this is a truck and package is at truck location
-            if is_truck(state, truck) {
-                if let Some(package_loc_val) = state.get_var("at", arg) {
-                    if let Some(package_loc) = package_loc_val.as_str() {
-                        if let Some(truck_loc_val) = state.get_var("truck_at", truck) {
-                            if let Some(truck_loc) = truck_loc_val.as_str() {
-                                if package_loc == truck_loc {
-                                    return Some(vec![PlanItem::action("load_truck", vec![string_value(arg), string_value(truck)])]);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        None
-    })?;
-    
-    // Method: Unload truck
-    domain.declare_unigoal_method("at", |state: &State, arg: &str, value: &StateValue| {
-        if let Some(location) = value.as_str() {
-            // Check if package is on a truck and we want to unload at a location
-            if is_location(state, location) {
-                if let Some(truck_val) = state.get_var("at", arg) {
-                    if let Some(truck) = truck_val.as_str() {
-                        if is_truck(state, truck) {
-                            return Some(vec![PlanItem::action("unload_truck", vec![string_value(arg), string_value(location)])]);
-                        }
-                    }
-                }
-            }
-        }
-        None
-    })?;
-    
-    // Method: Load plane
-    domain.declare_unigoal_method("at", |state: &State, arg: &str, value: &StateValue| {
-        if let Some(plane) = value.as_str() {
-            // Check if this is a plane and package is at plane location
-            if is_plane(state, plane) {
-                if let Some(package_loc_val) = state.get_var("at", arg) {
-                    if let Some(package_loc) = package_loc_val.as_str() {
-                        if let Some(plane_loc_val) = state.get_var("plane_at", plane) {
-                            if let Some(plane_loc) = plane_loc_val.as_str() {
-                                if package_loc == plane_loc {
-                                    return Some(vec![PlanItem::action("load_plane", vec![string_value(arg), string_value(plane)])]);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        None
-    })?;
-    
-    // Method: Unload plane
-    domain.declare_unigoal_method("at", |state: &State, arg: &str, value: &StateValue| {
-        if let Some(airport) = value.as_str() {
-            // Check if package is on a plane and we want to unload at an airport
-            if is_airport(state, airport) {
-                if let Some(plane_val) = state.get_var("at", arg) {
-                    if let Some(plane) = plane_val.as_str() {
-                        if is_plane(state, plane) {
-                            return Some(vec![PlanItem::action("unload_plane", vec![string_value(arg), string_value(airport)])]);
-                        }
-                    }
-                }
-            }
-        }
-        None
-    })?;
-    
-    // Unigoal methods for 'truck_at'
-    domain.declare_unigoal_method("truck_at", |state: &State, arg: &str, value: &StateValue| {
-        if let Some(location) = value.as_str() {
-            if is_truck(state, arg) && is_location(state, location) {
-                // Check if truck and location are in the same city
-                if let (Some(truck_city), Some(loc_city)) = (get_city(state, arg), get_city(state, location)) {
-                    if truck_city == loc_city {
-                        return Some(vec![PlanItem::action("drive_truck", vec![string_value(arg), string_value(location)])]);
-                    }
-                }
-            }
-        }
-        None
-    })?;
-    
-    // Unigoal methods for 'plane_at'
-    domain.declare_unigoal_method("plane_at", |state: &State, arg: &str, value: &StateValue| {
-        if let Some(airport) = value.as_str() {
-            if is_plane(state, arg) && is_airport(state, airport) {
-                return Some(vec![PlanItem::action("fly_plane", vec![string_value(arg), string_value(airport)])]);
-            }
-        }
-        None
-    })?;
-
-    // Complex unigoal methods for package transportation
-
-    // Method: Move within city using truck
-    domain.declare_unigoal_method("at", |state: &State, arg: &str, value: &StateValue| {
-        if let Some(target_location) = value.as_str() {
-            if is_package(state, arg) && is_location(state, target_location) {
-                if let Some(package_loc_val) = state.get_var("at", arg) {
-                    if let Some(package_loc) = package_loc_val.as_str() {
-                        if is_location(state, package_loc) {
-                            // Check if both locations are in the same city
-                            if let (Some(pkg_city), Some(target_city)) = (get_city(state, package_loc), get_city(state, target_location)) {
-                                if pkg_city == target_city {
-                                    // Find a truck in the same city
-                                    if let Some(truck) = find_truck_in_city(state, &pkg_city) {
-                                        return Some(vec![
-                                            PlanItem::unigoal("truck_at".to_string(), truck.clone(), string_value(package_loc)),
-                                            PlanItem::unigoal("at".to_string(), arg.to_string(), string_value(&truck)),
-                                            PlanItem::unigoal("truck_at".to_string(), truck, string_value(target_location)),
-                                            PlanItem::unigoal("at".to_string(), arg.to_string(), string_value(target_location))
-                                        ]);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        None
-    })?;
-
-    // Method: Move between airports using plane
-    domain.declare_unigoal_method("at", |state: &State, arg: &str, value: &StateValue| {
-        if let Some(target_airport) = value.as_str() {
-            if is_package(state, arg) && is_airport(state, target_airport) {
-                if let Some(package_loc_val) = state.get_var("at", arg) {
-                    if let Some(package_loc) = package_loc_val.as_str() {
-                        if is_airport(state, package_loc) {
-                            // Check if airports are in different cities
-                            if let (Some(pkg_city), Some(target_city)) = (get_city(state, package_loc), get_city(state, target_airport)) {
-                                if pkg_city != target_city {
-                                    // Find a plane
-                                    if let Some(plane) = find_plane_in_city(state, &pkg_city) {
-                                        return Some(vec![
-                                            PlanItem::unigoal("plane_at".to_string(), plane.clone(), string_value(package_loc)),
-                                            PlanItem::unigoal("at".to_string(), arg.to_string(), string_value(&plane)),
-                                            PlanItem::unigoal("plane_at".to_string(), plane, string_value(target_airport)),
-                                            PlanItem::unigoal("at".to_string(), arg.to_string(), string_value(target_airport))
-                                        ]);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        None
-    })?;
-
-    // Method: Move between cities (location to location)
-    domain.declare_unigoal_method("at", |state: &State, arg: &str, value: &StateValue| {
-        if let Some(target_location) = value.as_str() {
-            if is_package(state, arg) && is_location(state, target_location) {
-                if let Some(package_loc_val) = state.get_var("at", arg) {
-                    if let Some(package_loc) = package_loc_val.as_str() {
-                        if is_location(state, package_loc) {
-                            // Check if locations are in different cities
-                            if let (Some(pkg_city), Some(target_city)) = (get_city(state, package_loc), get_city(state, target_location)) {
-                                if pkg_city != target_city {
-                                    // Find airports in both cities
-                                    if let (Some(source_airport), Some(target_airport)) = (find_airport_in_city(state, &pkg_city), find_airport_in_city(state, &target_city)) {
-                                        return Some(vec![
-                                            PlanItem::unigoal("at".to_string(), arg.to_string(), string_value(&source_airport)),
-                                            PlanItem::unigoal("at".to_string(), arg.to_string(), string_value(&target_airport)),
-                                            PlanItem::unigoal("at".to_string(), arg.to_string(), string_value(target_location))
-                                        ]);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        None
-    })?;
-
-    Ok(())
-}
-
-/// Create the initial logistics state
-fn create_logistics_state() -> State {
-    let mut state = State::new("state1");
-    
-    // Set up packages
-    state.set_var("at", "package1", string_value("location1"));
-    state.set_var("at", "package2", string_value("location2"));
-    
-    // Set up trucks
-    state.set_var("truck_at", "truck1", string_value("location3"));
-    state.set_var("truck_at", "truck6", string_value("location10"));
-    
-    // Set up planes
-    state.set_var("plane_at", "plane2", string_value("airport2"));
-    
-    // Set up city mappings
-    state.set_var("in_city", "location1", string_value("city1"));
-    state.set_var("in_city", "location2", string_value("city1"));
-    state.set_var("in_city", "location3", string_value("city1"));
-    state.set_var("in_city", "airport1", string_value("city1"));
-    state.set_var("in_city", "location10", string_value("city2"));
-    state.set_var("in_city", "airport2", string_value("city2"));
-    
-    // Set up entity types
-    state.set_var("packages", "package1", true.into());
-    state.set_var("packages", "package2", true.into());
-    state.set_var("trucks", "truck1", true.into());
-    state.set_var("trucks", "truck6", true.into());
-    state.set_var("airplanes", "plane2", true.into());
-    state.set_var("locations", "location1", true.into());
-    state.set_var("locations", "location2", true.into());
-    state.set_var("locations", "location3", true.into());
-    state.set_var("locations", "airport1", true.into());
-    state.set_var("locations", "location10", true.into());
-    state.set_var("locations", "airport2", true.into());
-    state.set_var("airports", "airport1", true.into());
-    state.set_var("airports", "airport2", true.into());
-    state.set_var("cities", "city1", true.into());
-    state.set_var("cities", "city2", true.into());
-    
-    state
-}
-
-/// Helper functions for domain logic
-
-fn is_package(state: &State, entity: &str) -> bool {
-    state.get_var("packages", entity).map_or(false, |v| v.as_bool().unwrap_or(false))
-}
-
-fn is_truck(state: &State, entity: &str) -> bool {
-    state.get_var("trucks", entity).map_or(false, |v| v.as_bool().unwrap_or(false))
-}
-
-fn is_plane(state: &State, entity: &str) -> bool {
-    state.get_var("airplanes", entity).map_or(false, |v| v.as_bool().unwrap_or(false))
-}
-
-fn is_location(state: &State, entity: &str) -> bool {
-    state.get_var("locations", entity).map_or(false, |v| v.as_bool().unwrap_or(false))
-}
-
-fn is_airport(state: &State, entity: &str) -> bool {
-    state.get_var("airports", entity).map_or(false, |v| v.as_bool().unwrap_or(false))
-}
-
-fn get_city(state: &State, entity: &str) -> Option<String> {
-    state.get_var("in_city", entity)?.as_str().map(|s| s.to_string())
-}
-
-/// Find a truck in the same city as the given city
-fn find_truck_in_city(state: &State, city: &str) -> Option<String> {
-    // Check all trucks
-    for truck in ["truck1", "truck6"] {
-        if is_truck(state, truck) {
-            if let Some(truck_loc_val) = state.get_var("truck_at", truck) {
-                if let Some(truck_loc) = truck_loc_val.as_str() {
-                    if let Some(truck_city) = get_city(state, truck_loc) {
-                        if truck_city == city {
-                            return Some(truck.to_string());
-                        }
-                    }
-                }
-            }
-        }
-    }
-    None
-}
-
-/// Find a plane in the same city as the given city, or any plane if none available
-fn find_plane_in_city(state: &State, city: &str) -> Option<String> {
-    // First try to find a plane in the same city
-    for plane in ["plane2"] {
-        if is_plane(state, plane) {
-            if let Some(plane_loc_val) = state.get_var("plane_at", plane) {
-                if let Some(plane_loc) = plane_loc_val.as_str() {
-                    if let Some(plane_city) = get_city(state, plane_loc) {
-                        if plane_city == city {
-                            return Some(plane.to_string());
-                        }
-                    }
-                }
-            }
-        }
-    }
-    // If no plane in the same city, return any plane
-    for plane in ["plane2"] {
-        if is_plane(state, plane) {
-            return Some(plane.to_string());
-        }
-    }
-    None
-}
-
-/// Find an airport in the same city as the given city
-fn find_airport_in_city(state: &State, city: &str) -> Option<String> {
-    for airport in ["airport1", "airport2"] {
-        if is_airport(state, airport) {
-            if let Some(airport_city) = get_city(state, airport) {
-                if airport_city == city {
-                    return Some(airport.to_string());
-                }
-            }
-        }
-    }
-    None
+    install_unigoal_methods(domain)
 }
 
 /// Print the result of a planning attempt
@@ -540,6 +135,7 @@ fn print_result(result: &Option<Vec<PlanItem>>, test_name: &str) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domains::logistics::{get_city, is_airport, is_location, is_plane, is_truck};
 
     #[test]
     fn test_run_logistics_hgn_examples() -> Result<()> {
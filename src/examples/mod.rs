@@ -4,6 +4,7 @@
 
 pub mod simple_htn_example;
 pub mod simple_hgn_example;
+pub mod blocks_common;
 pub mod blocks_htn_example;
 pub mod lazy_lookahead_example;
 pub mod backtracking_htn_example;
@@ -20,7 +21,7 @@ pub use logistics_hgn_example::run_logistics_hgn_examples;
 pub use regression_tests::{run_regression_tests, run_domain_regression_tests};
 
 use crate::core::{State, PlanItem, TodoList, Domain};
-use crate::planning::PlannerBuilder;
+use crate::planning::{PlannerBuilder, item_to_string};
 use crate::error::Result;
 
 /// Helper function to run an example with error handling
@@ -164,6 +165,15 @@ pub fn plans_equal(plan1: &[PlanItem], plan2: &[PlanItem]) -> bool {
     plan1.iter().zip(plan2.iter()).all(|(a, b)| a == b)
 }
 
+/// Assert that a plan is exactly the expected sequence of items
+///
+/// Expected items are written as S-expressions, as produced by `item_to_string()`,
+/// e.g. `"(pickup a)"`. Panics listing both plans if they differ.
+pub fn assert_plan_eq(actual: &[PlanItem], expected: &[&str]) {
+    let actual: Vec<String> = actual.iter().map(item_to_string).collect();
+    assert_eq!(actual, expected, "plan differs from the expected plan");
+}
+
 /// Helper function to run a planning test case
 pub fn run_planning_test(
     name: &str,
@@ -257,6 +267,19 @@ mod tests {
         assert!(!plans_equal(&plan1, &[]));
     }
 
+    #[test]
+    fn test_assert_plan_eq() {
+        let plan = vec![create_move_action("obj1", "loc2"), create_move_action("obj2", "loc3")];
+        assert_plan_eq(&plan, &["(move obj1 loc2)", "(move obj2 loc3)"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "plan differs from the expected plan")]
+    fn test_assert_plan_eq_detects_different_plan() {
+        let plan = vec![create_move_action("obj1", "loc2"), create_move_action("obj2", "loc3")];
+        assert_plan_eq(&plan, &["(move obj1 loc3)", "(move obj2 loc3)"]);
+    }
+
     #[test]
     fn test_helper_functions() {
         let travel_task = create_travel_task("alice", "home", "park");
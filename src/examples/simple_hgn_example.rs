@@ -382,12 +382,9 @@ fn find_airport_in_city(state: &State, city: &str) -> Option<String> {
 /// Helper function to find any available plane
 fn find_plane(state: &State) -> Option<String> {
     // Look for planes in the state
-    if let Some(planes_data) = state.get_var_map("plane_at") {
-        for (plane, _) in planes_data {
-            return Some(plane.clone());
-        }
-    }
-    None
+    state
+        .get_var_map("plane_at")
+        .and_then(|planes_data| planes_data.keys().next().cloned())
 }
 
 /// Create initial state for logistics domain
@@ -422,7 +419,7 @@ fn create_blocks_hgn_domain() -> Result<Domain> {
 
     // Actions for blocks domain
     domain.declare_action("pickup", |state: &mut State, args: &[crate::core::StateValue]| {
-        if args.len() >= 1 {
+        if !args.is_empty() {
             if let Some(block) = args[0].as_str() {
                 // Check preconditions: block on table, clear, hand empty
                 if let (Some(pos), Some(clear), Some(holding)) = (
@@ -470,7 +467,7 @@ fn create_blocks_hgn_domain() -> Result<Domain> {
     })?;
 
     domain.declare_action("putdown", |state: &mut State, args: &[crate::core::StateValue]| {
-        if args.len() >= 1 {
+        if !args.is_empty() {
             if let Some(block) = args[0].as_str() {
                 // Check preconditions: holding block
                 if let Some(pos) = state.get_var("pos", block) {
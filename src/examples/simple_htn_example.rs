@@ -217,10 +217,8 @@ mod tests {
         // Test planner.pyhop() method
         let plan = planner.pyhop(state, todo_list)?;
 
-        // Should find a plan
-        assert!(plan.is_some());
-        let plan = plan.unwrap();
-        assert!(!plan.is_empty());
+        // The park is within walking distance
+        crate::examples::assert_plan_eq(&plan.unwrap(), &["(walk alice home_a park)"]);
 
         Ok(())
     }
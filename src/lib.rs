@@ -168,26 +168,81 @@
 //! - **⚡ Performance**: Compiled Rust code with multiple planning strategies
 //! - **🎯 Type Safety**: Compile-time error checking and memory safety
 //! - **🧪 Comprehensive Testing**: Extensive test suite with parallel execution support
+//!
+//! ## `no_std` Support
+//!
+//! The `std` feature is enabled by default. Without it, GTRusthop builds on `alloc`
+//! and `hashbrown` alone: domains, states, multigoals and `Planner` work the same,
+//! printed output is dropped, and what needs the standard library (the examples,
+//! the process-wide Pyhop compatibility functions, the plan cache, panic isolation
+//! and search timing) is left out.
+//!
+//! ```toml
+//! gtrusthop = { version = "1.2", default-features = false }
+//! ```
+//!
+//! `cargo test -- --ignored test_builds_without_std` checks that the library
+//! still builds this way.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+/// Without `std` there is nowhere to print to, so output is dropped
+#[cfg(not(feature = "std"))]
+macro_rules! println {
+    () => {};
+    ($($arg:tt)*) => {{ let _ = format_args!($($arg)*); }};
+}
+
+/// Like the `println!` above, without the newline
+#[cfg(not(feature = "std"))]
+macro_rules! print {
+    ($($arg:tt)*) => {{ let _ = format_args!($($arg)*); }};
+}
 
+mod compat;
 pub mod core;
 pub mod planning;
 pub mod domains;
+#[cfg(feature = "std")]
 pub mod examples;
 pub mod error;
 
 // Re-export main types for convenience
-pub use core::{Domain, State, Multigoal, PlanItem};
+pub use core::{Domain, State, Multigoal, PlanItem, PlanItemKind};
 pub use planning::{
     // New builder pattern API
     PlannerBuilder, Planner,
+    // Common types
+    PlanningStrategy
+};
+#[cfg(feature = "std")]
+pub use planning::{
     // Global configuration (still needed for some functionality)
     set_verbose_level,
     // Pyhop compatibility
     pyhop,
-    // Common types
-    PlanningStrategy
 };
 pub use error::{GTRustHopError, Result};
 
 /// Current version of GTRusthop
 pub const VERSION: &str = "1.2.1";
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    #[test]
+    #[ignore = "runs a nested cargo build, see the no_std section of the crate docs"]
+    fn test_builds_without_std() {
+        // A separate target directory keeps this build from waiting on the one running the tests
+        let status = Command::new(env!("CARGO"))
+            .args(["build", "--lib", "--no-default-features", "--quiet", "--target-dir"])
+            .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/target/no_std"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .status()
+            .expect("cargo should run");
+        assert!(status.success(), "the library should build with --no-default-features");
+    }
+}
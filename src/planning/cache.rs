@@ -0,0 +1,90 @@
+//! Plan cache for GTRusthop planner instances
+
+use crate::compat::prelude::*;
+use crate::core::{Plan, PlanItem, State};
+use alloc::collections::VecDeque;
+
+/// Least recently used cache of search results, keyed by state and todo list
+///
+/// Lookups scan all entries, which is fine for the small capacities an
+/// interactive tool needs.
+#[derive(Debug)]
+pub(crate) struct PlanCache {
+    capacity: usize,
+    /// Entries from least to most recently used
+    entries: VecDeque<(String, Option<Plan>)>,
+}
+
+impl PlanCache {
+    /// Create an empty cache holding up to `capacity` results
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self { capacity, entries: VecDeque::with_capacity(capacity) }
+    }
+
+    /// Create an empty cache with the same capacity as this one
+    pub(crate) fn emptied(&self) -> Self {
+        Self::new(self.capacity)
+    }
+
+    /// Get the cache key of a query: equal states and todo lists share a key
+    ///
+    /// State defaults are part of the key, since they change what unset
+    /// variables read as.
+    pub(crate) fn key(state: &State, todo_list: &[PlanItem]) -> String {
+        format!(
+            "{}|{}|{}",
+            state.fingerprint(),
+            state.defaults_fingerprint(),
+            serde_json::to_string(todo_list).unwrap_or_default()
+        )
+    }
+
+    /// Get the cached result of a query, marking it as most recently used
+    pub(crate) fn get(&mut self, key: &str) -> Option<Option<Plan>> {
+        let index = self.entries.iter().position(|(entry_key, _)| entry_key == key)?;
+        let entry = self.entries.remove(index)?;
+        let plan = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(plan)
+    }
+
+    /// Store the result of a query, evicting the least recently used one if full
+    pub(crate) fn insert(&mut self, key: String, plan: Option<Plan>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some(index) = self.entries.iter().position(|(entry_key, _)| *entry_key == key) {
+            self.entries.remove(index);
+        } else if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key, plan));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_cache_evicts_least_recently_used() {
+        let mut cache = PlanCache::new(2);
+        cache.insert("a".into(), Some(vec![PlanItem::action("step", vec![])]));
+        cache.insert("b".into(), None);
+        assert_eq!(cache.get("a").map(|plan| plan.map(|p| p.len())), Some(Some(1)));
+
+        // "b" is now the least recently used entry
+        cache.insert("c".into(), None);
+        assert_eq!(cache.get("b"), None);
+        assert!(cache.get("a").is_some());
+        assert_eq!(cache.get("c"), Some(None));
+    }
+
+    #[test]
+    fn test_plan_cache_key_includes_defaults() {
+        let state = State::new("s");
+        let mut with_default = State::new("s");
+        with_default.set_default("clear", true.into());
+        assert_ne!(PlanCache::key(&state, &[]), PlanCache::key(&with_default, &[]));
+    }
+}
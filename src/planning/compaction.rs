@@ -0,0 +1,91 @@
+//! Plan compaction for GTRusthop
+
+use crate::compat::prelude::*;
+use crate::core::{Domain, Plan, PlanItem, State};
+use crate::error::Result;
+
+/// Remove the stretches of a plan that bring the state back to an earlier state
+///
+/// Replays `plan` from `initial` and, scanning from the start, drops the longest
+/// run of actions after each state that ends in an equal state (see
+/// `State::fingerprint()`), such as `pickup(a); putdown(a)`. Fails like
+/// `Domain::apply_plan()` if the plan can't be replayed. If the compacted plan
+/// doesn't reach the same final state, which only happens with actions that
+/// depend on more than their state and arguments, the plan is returned unchanged.
+///
+/// Use `Planner::compact_plan()` for plans with rigid actions or resources.
+pub fn compact_plan(domain: &Domain, initial: State, plan: &[PlanItem]) -> Result<Plan> {
+    compact_replayed(initial, plan, |state, plan| domain.replay_with_states(state, plan))
+}
+
+/// Compact a plan, replaying it with `replay`, which returns every intermediate state
+pub(crate) fn compact_replayed(
+    initial: State,
+    plan: &[PlanItem],
+    replay: impl Fn(State, &[PlanItem]) -> Result<Vec<State>>,
+) -> Result<Plan> {
+    let fingerprints: Vec<String> = replay(initial.clone(), plan)?
+        .iter()
+        .map(State::fingerprint)
+        .collect();
+
+    let mut compacted = Vec::with_capacity(plan.len());
+    let mut index = 0;
+    while index < plan.len() {
+        match fingerprints.iter().rposition(|fingerprint| *fingerprint == fingerprints[index]) {
+            Some(last) if last > index => index = last,
+            _ => {
+                compacted.push(plan[index].clone());
+                index += 1;
+            }
+        }
+    }
+
+    if compacted.len() < plan.len() {
+        let replayed = replay(initial, &compacted)?;
+        if replayed.last().map(State::fingerprint).as_ref() != Some(&fingerprints[plan.len()]) {
+            return Ok(plan.to_vec());
+        }
+    }
+    Ok(compacted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{string_value, StateValue};
+
+    #[test]
+    fn test_compact_plan_removes_no_op_cycle() -> Result<()> {
+        let mut domain = Domain::new("compaction");
+        domain.declare_action("go", |state: &mut State, args: &[StateValue]| {
+            state.set_var("loc", "robot", args[0].clone());
+            Some(state.clone())
+        })?;
+        domain.declare_action("pickup", |state: &mut State, args: &[StateValue]| {
+            state.set_var("holding", "hand", args[0].clone());
+            Some(state.clone())
+        })?;
+        domain.declare_action("putdown", |state: &mut State, _args: &[StateValue]| {
+            state.set_var("holding", "hand", false.into());
+            Some(state.clone())
+        })?;
+
+        let mut state = State::new("s");
+        state.set_var("loc", "robot", string_value("home"));
+        state.set_var("holding", "hand", false.into());
+
+        let go = |place: &str| PlanItem::action("go", vec![string_value(place)]);
+        let plan = vec![
+            go("shop"),
+            PlanItem::action("pickup", vec![string_value("a")]),
+            PlanItem::action("putdown", vec![string_value("a")]),
+            go("park"),
+        ];
+        assert_eq!(compact_plan(&domain, state.clone(), &plan)?, vec![go("shop"), go("park")]);
+
+        // A plan that ends where it started compacts to nothing
+        assert_eq!(compact_plan(&domain, state, &[go("shop"), go("home")])?, vec![]);
+        Ok(())
+    }
+}
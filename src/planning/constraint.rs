@@ -0,0 +1,32 @@
+//! State constraints for GTRusthop planner instances
+
+use crate::core::State;
+use alloc::sync::Arc;
+
+/// Type alias for state constraint functions
+/// Constraints return `true` if a state is allowed and `false` if it is forbidden
+pub type StateConstraintFn = Arc<dyn Fn(&State) -> bool + Send + Sync>;
+
+/// An invariant every state reached by applying an action must satisfy
+#[derive(Clone)]
+pub struct StateConstraint {
+    function: StateConstraintFn,
+}
+
+impl StateConstraint {
+    /// Wrap a constraint function
+    pub fn new(function: StateConstraintFn) -> Self {
+        Self { function }
+    }
+
+    /// Check if `state` is allowed by the constraint
+    pub fn allows(&self, state: &State) -> bool {
+        (self.function)(state)
+    }
+}
+
+impl core::fmt::Debug for StateConstraint {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StateConstraint").finish_non_exhaustive()
+    }
+}
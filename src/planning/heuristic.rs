@@ -0,0 +1,32 @@
+//! Heuristic hook for GTRusthop planner instances
+
+use crate::core::State;
+use alloc::sync::Arc;
+
+/// Type alias for heuristic functions
+/// Heuristics estimate how far a state is from the goals; lower is closer
+pub type HeuristicFn = Arc<dyn Fn(&State) -> f64 + Send + Sync>;
+
+/// A goal-distance heuristic supplied to the planner
+#[derive(Clone)]
+pub struct Heuristic {
+    function: HeuristicFn,
+}
+
+impl Heuristic {
+    /// Wrap a heuristic function
+    pub fn new(function: HeuristicFn) -> Self {
+        Self { function }
+    }
+
+    /// Estimate the distance from `state` to the goals
+    pub fn distance(&self, state: &State) -> f64 {
+        (self.function)(state)
+    }
+}
+
+impl core::fmt::Debug for Heuristic {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Heuristic").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,68 @@
+//! Hierarchical plans for GTRusthop planner instances
+
+use crate::compat::prelude::*;
+use crate::core::{Plan, PlanItem, StateValue};
+
+/// A node of a hierarchical plan
+#[derive(Debug, Clone, PartialEq)]
+pub enum HPlanNode {
+    /// A task or goal with the items it was refined into
+    Task {
+        /// Name of the task, or of the goal's state variable, relation or multigoal
+        name: String,
+        /// Arguments, as returned by `PlanItem::args()`
+        args: Vec<StateValue>,
+        /// Items the task or goal was refined into, in plan order
+        children: Vec<HPlanNode>,
+    },
+    /// A primitive action
+    Action {
+        /// Name of the action
+        name: String,
+        /// Arguments of the action
+        args: Vec<StateValue>,
+    },
+}
+
+impl HPlanNode {
+    /// Get the name of this node
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Task { name, .. } | Self::Action { name, .. } => name,
+        }
+    }
+
+    /// Get the children of this node; actions have none
+    pub fn children(&self) -> &[HPlanNode] {
+        match self {
+            Self::Task { children, .. } => children,
+            Self::Action { .. } => &[],
+        }
+    }
+
+    fn flatten_into(&self, plan: &mut Plan) {
+        match self {
+            Self::Task { children, .. } => children.iter().for_each(|child| child.flatten_into(plan)),
+            Self::Action { name, args } => plan.push(PlanItem::action(name.clone(), args.clone())),
+        }
+    }
+}
+
+/// A plan that keeps the decomposition of each todo list item, returned by
+/// `Planner::find_hierarchical_plan()`
+///
+/// Verification tasks added by the planner are left out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HPlan {
+    /// One node per item of the todo list
+    pub roots: Vec<HPlanNode>,
+}
+
+impl HPlan {
+    /// Get the actions of the plan in order, as `find_plan()` returns them
+    pub fn flatten(&self) -> Plan {
+        let mut plan = Vec::new();
+        self.roots.iter().for_each(|root| root.flatten_into(&mut plan));
+        plan
+    }
+}
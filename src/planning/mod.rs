@@ -4,16 +4,49 @@ pub mod planner;
 pub mod planner_instance;
 pub mod strategy;
 pub mod verification;
+pub mod stats;
+pub mod heuristic;
+pub mod constraint;
+pub mod hierarchical;
+pub mod compaction;
+pub mod trace;
+#[cfg(feature = "std")]
+mod cache;
 
 
-pub use strategy::{PlanningStrategy, set_planning_strategy, get_planning_strategy};
+pub use strategy::PlanningStrategy;
+#[cfg(feature = "std")]
+pub use strategy::{set_planning_strategy, get_planning_strategy};
+pub use stats::{PlanMeta, PlanningStats, ProgressCallback, ProgressReporter, StrategyComparison};
+pub use heuristic::{Heuristic, HeuristicFn};
+pub use constraint::{StateConstraint, StateConstraintFn};
+pub use hierarchical::{HPlan, HPlanNode};
+pub use compaction::compact_plan;
+pub use trace::{SearchEvent, SearchEventKind};
 
+use crate::compat::prelude::*;
 use crate::core::{State, Domain, PlanItem, TodoList, Plan, StateValue, Multigoal};
 use crate::error::{GTRustHopError, Result};
-use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
+#[cfg(feature = "std")]
+use cache::PlanCache;
+use crate::compat::HashMap;
+use core::sync::atomic::AtomicBool;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+
+/// Whether goals are verified when neither the builder nor the domain says otherwise
+const DEFAULT_VERIFY_GOALS: bool = true;
+
+/// Deepest node `PlanningStrategy::Auto` expands recursively unless `with_recursion_limit()` says otherwise
+pub const DEFAULT_RECURSION_LIMIT: usize = 1000;
+
+/// Fewest plans `Planner::find_plan_stable()` compares, whatever `with_max_plans()` says
+pub const STABLE_PLAN_CANDIDATES: usize = 8;
 
 /// Global verbose level for debugging output
+#[cfg(feature = "std")]
 static VERBOSE_LEVEL: Mutex<i32> = Mutex::new(1);
 
 /// Set the verbosity level for planning output
@@ -21,6 +54,7 @@ static VERBOSE_LEVEL: Mutex<i32> = Mutex::new(1);
 /// - level = 1: print the initial parameters and the answer
 /// - level = 2: also print a message on each recursive call
 /// - level = 3: also print some info about intermediate computations
+#[cfg(feature = "std")]
 pub fn set_verbose_level(level: i32) -> Result<()> {
     if !(0..=3).contains(&level) {
         return Err(GTRustHopError::InvalidVerboseLevel { level });
@@ -33,16 +67,19 @@ pub fn set_verbose_level(level: i32) -> Result<()> {
 }
 
 /// Get the current verbosity level
+#[cfg(feature = "std")]
 pub fn get_verbose_level() -> i32 {
     *VERBOSE_LEVEL.lock().unwrap()
 }
 
 /// Check if verbose output is enabled at the given level
+#[cfg(feature = "std")]
 pub fn is_verbose(level: i32) -> bool {
     get_verbose_level() >= level
 }
 
 /// Print a message if verbose level is sufficient
+#[cfg(feature = "std")]
 pub fn verbose_print(level: i32, message: &str) {
     if is_verbose(level) {
         println!("{message}");
@@ -93,6 +130,7 @@ pub fn verbose_print(level: i32, message: &str) {
 /// # Ok(())
 /// # }
 /// ```
+#[cfg(feature = "std")]
 pub fn pyhop(domain: Domain, state: State, todo_list: Vec<PlanItem>) -> Result<Option<Plan>> {
     use crate::planning::PlannerBuilder;
 
@@ -113,7 +151,8 @@ pub fn pyhop(domain: Domain, state: State, todo_list: Vec<PlanItem>) -> Result<O
 }
 
 /// Print a formatted message if verbose level is sufficient
-pub fn verbose_printf(level: i32, _format: &str, args: std::fmt::Arguments) {
+#[cfg(feature = "std")]
+pub fn verbose_printf(level: i32, _format: &str, args: core::fmt::Arguments) {
     if is_verbose(level) {
         println!("{args}");
     }
@@ -122,7 +161,7 @@ pub fn verbose_printf(level: i32, _format: &str, args: std::fmt::Arguments) {
 /// Convert a plan item to a string representation for debugging
 pub fn item_to_string(item: &PlanItem) -> String {
     match item {
-        PlanItem::Task(name, args) => {
+        PlanItem::Task(name, args) | PlanItem::TaskPattern(name, args) => {
             let args_str: Vec<String> = args.iter().map(value_to_string).collect();
             format!("({})", [name.clone()].into_iter().chain(args_str).collect::<Vec<_>>().join(" "))
         }
@@ -133,7 +172,20 @@ pub fn item_to_string(item: &PlanItem) -> String {
         PlanItem::Unigoal(var_name, arg, value) => {
             format!("({} {} {})", var_name, arg, value_to_string(value))
         }
+        PlanItem::RelGoal { rel, args, value } => {
+            let args_str: Vec<String> = args.iter().map(value_to_string).collect();
+            format!("({} {} {})", rel, args_str.join(" "), value_to_string(value))
+        }
         PlanItem::Multigoal(mg) => format!("{mg}"),
+        PlanItem::OneOf(alternatives) => {
+            let alternatives_str: Vec<String> = alternatives.iter().map(item_to_string).collect();
+            format!("(one_of {})", alternatives_str.join(" "))
+        }
+        PlanItem::Unordered(items) => {
+            let items_str: Vec<String> = items.iter().map(item_to_string).collect();
+            format!("(unordered {})", items_str.join(" "))
+        }
+        PlanItem::Maintain(goal) => format!("(maintain {})", item_to_string(goal)),
     }
 }
 
@@ -155,6 +207,19 @@ pub fn todo_list_to_string(todo_list: &TodoList) -> String {
     format!("[{}]", items.join(", "))
 }
 
+/// Count the occurrences of each action in a plan, by action name
+///
+/// Items that are not actions are not counted.
+pub fn action_counts(plan: &[PlanItem]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for item in plan {
+        if let PlanItem::Action(name, _) = item {
+            *counts.entry(name.clone()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
 /// Planning context that holds the current domain and other global state
 #[derive(Debug, Clone)]
 pub struct PlanningContext {
@@ -164,6 +229,8 @@ pub struct PlanningContext {
     pub verify_goals: bool,
     /// Current planning strategy
     pub strategy: PlanningStrategy,
+    /// Verbosity of the planning output, see `PlannerBuilder::with_verbose_level()`
+    pub verbose_level: i32,
 }
 
 impl PlanningContext {
@@ -173,6 +240,19 @@ impl PlanningContext {
             domain,
             verify_goals: true,
             strategy: PlanningStrategy::Iterative,
+            verbose_level: 1,
+        }
+    }
+
+    /// Check if verbose output is enabled at the given level
+    pub fn is_verbose(&self, level: i32) -> bool {
+        self.verbose_level >= level
+    }
+
+    /// Print a message if verbose level is sufficient
+    pub fn verbose_print(&self, level: i32, message: &str) {
+        if self.is_verbose(level) {
+            println!("{message}");
         }
     }
 
@@ -188,15 +268,18 @@ impl PlanningContext {
 }
 
 /// Global planning context
+#[cfg(feature = "std")]
 static PLANNING_CONTEXT: Mutex<Option<PlanningContext>> = Mutex::new(None);
 
 /// Set the current planning context
+#[cfg(feature = "std")]
 pub fn set_planning_context(context: PlanningContext) {
     let mut ctx = PLANNING_CONTEXT.lock().unwrap();
     *ctx = Some(context);
 }
 
 /// Get the current planning context
+#[cfg(feature = "std")]
 pub fn get_planning_context() -> Result<PlanningContext> {
     let ctx = PLANNING_CONTEXT.lock().unwrap();
     ctx.clone().ok_or_else(|| GTRustHopError::generic("No planning context has been set"))
@@ -228,7 +311,8 @@ pub enum PlanningResult {
     Failure,
     /// Planning needs to continue with new state
     Continue {
-        state: State,
+        /// Boxed, since a state is much larger than the other variants
+        state: Box<State>,
         todo_list: TodoList,
         plan: Plan,
         depth: usize,
@@ -248,6 +332,71 @@ pub trait PlanningStrategyTrait {
     ) -> Result<PlanningResult>;
 }
 
+/// Type alias for plan postprocessors
+/// Postprocessors receive a plan found by the planner and return the plan to report
+pub type PlanPostprocessorFn = Arc<dyn Fn(Plan) -> Plan + Send + Sync>;
+
+/// Type alias for goal predicates
+/// Goal predicates return `true` for a state the planner may stop in, see `PlannerBuilder::with_goal_predicate()`
+pub type GoalPredicateFn = Arc<dyn Fn(&State) -> bool + Send + Sync>;
+
+/// A decomposition choice: the name of a task, state variable, relation or
+/// multigoal and the index of the method applied to it
+pub type MethodChoice = (String, usize);
+
+/// A postprocessor set with `PlannerBuilder::with_plan_postprocessor()`
+#[derive(Clone)]
+struct PlanPostprocessor(PlanPostprocessorFn);
+
+impl core::fmt::Debug for PlanPostprocessor {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PlanPostprocessor").finish_non_exhaustive()
+    }
+}
+
+/// A predicate set with `PlannerBuilder::with_goal_predicate()`
+#[derive(Clone)]
+struct GoalPredicate(GoalPredicateFn);
+
+impl core::fmt::Debug for GoalPredicate {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("GoalPredicate").finish_non_exhaustive()
+    }
+}
+
+/// A user-supplied strategy set with `PlannerBuilder::with_custom_strategy()`
+#[derive(Clone)]
+struct CustomStrategy(Arc<dyn PlanningStrategyTrait + Send + Sync>);
+
+impl core::fmt::Debug for CustomStrategy {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CustomStrategy").finish_non_exhaustive()
+    }
+}
+
+/// What the planner does when a user closure panics under panic isolation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicPolicy {
+    /// Abort the search with `GTRustHopError::MethodPanicked`
+    #[default]
+    Fail,
+    /// Treat the panicking action or method as not applicable and keep searching
+    ///
+    /// A panicking state constraint forbids the state, and a panicking heuristic
+    /// turns thrashing detection off for the task being refined.
+    Skip,
+}
+
+/// What lazy lookahead executes for an action without a `c_<action>` command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommandPolicy {
+    /// Execute the action itself, as if commands were deterministic
+    #[default]
+    Fallback,
+    /// Stop with `GTRustHopError::MissingCommand`
+    Strict,
+}
+
 /// Builder for creating isolated planner instances
 ///
 /// This builder provides a fluent interface for configuring planning parameters
@@ -256,9 +405,35 @@ pub trait PlanningStrategyTrait {
 pub struct PlannerBuilder {
     domain: Option<Domain>,
     verbose_level: i32,
-    strategy: PlanningStrategy,
-    verify_goals: bool,
+    strategy: Option<PlanningStrategy>,
+    custom_strategy: Option<CustomStrategy>,
+    verify_goals: Option<bool>,
     multigoals: HashMap<String, Multigoal>,
+    progress: Option<ProgressReporter>,
+    failure_cache: bool,
+    max_plans: usize,
+    heuristic: Option<Heuristic>,
+    thrashing_limit: Option<usize>,
+    recursion_limit: Option<usize>,
+    constraints: Vec<StateConstraint>,
+    action_cache: bool,
+    strict_declarations: bool,
+    #[cfg(feature = "std")]
+    panic_isolation: bool,
+    #[cfg(feature = "std")]
+    panic_policy: PanicPolicy,
+    postprocessor: Option<PlanPostprocessor>,
+    resources: Vec<(String, f64)>,
+    #[cfg(feature = "std")]
+    plan_cache: Option<usize>,
+    preferred_tags: Vec<String>,
+    cancel_token: Option<Arc<AtomicBool>>,
+    conformant: bool,
+    goal_predicate: Option<GoalPredicate>,
+    rigid_state: State,
+    order_preferences: Vec<(String, String)>,
+    /// First multigoal id registered twice with different goals
+    multigoal_conflict: Option<String>,
 }
 
 impl Default for PlannerBuilder {
@@ -273,9 +448,34 @@ impl PlannerBuilder {
         Self {
             domain: None,
             verbose_level: 1,
-            strategy: PlanningStrategy::Iterative,
-            verify_goals: true,
+            strategy: None,
+            custom_strategy: None,
+            verify_goals: None,
             multigoals: HashMap::new(),
+            progress: None,
+            failure_cache: false,
+            max_plans: 1,
+            heuristic: None,
+            thrashing_limit: None,
+            recursion_limit: None,
+            constraints: Vec::new(),
+            action_cache: false,
+            strict_declarations: false,
+            #[cfg(feature = "std")]
+            panic_isolation: false,
+            #[cfg(feature = "std")]
+            panic_policy: PanicPolicy::default(),
+            postprocessor: None,
+            resources: Vec::new(),
+            #[cfg(feature = "std")]
+            plan_cache: None,
+            preferred_tags: Vec::new(),
+            cancel_token: None,
+            conformant: false,
+            goal_predicate: None,
+            rigid_state: State::new("rigid"),
+            order_preferences: Vec::new(),
+            multigoal_conflict: None,
         }
     }
 
@@ -300,20 +500,265 @@ impl PlannerBuilder {
 
     /// Set the planning strategy
     pub fn with_strategy(mut self, strategy: PlanningStrategy) -> Self {
-        self.strategy = strategy;
+        self.strategy = Some(strategy);
+        self
+    }
+
+    /// Plan with a user-supplied strategy instead of a built-in one
+    ///
+    /// The strategy's `seek_plan()` is called with a `PlanningContext` for the
+    /// planner's domain and goal verification setting. Planner options that only
+    /// apply to the built-in search, like `with_max_plans()`, are ignored. Combining
+    /// this with `with_strategy()` makes `build()` return
+    /// `GTRustHopError::ConflictingStrategyConfig`.
+    pub fn with_custom_strategy(mut self, strategy: Arc<dyn PlanningStrategyTrait + Send + Sync>) -> Self {
+        self.custom_strategy = Some(CustomStrategy(strategy));
         self
     }
 
     /// Set whether to verify goals after method application
+    ///
+    /// This overrides the domain's default (see `Domain::set_default_verify_goals()`),
+    /// which in turn overrides the global default of `true`.
     pub fn with_goal_verification(mut self, verify: bool) -> Self {
-        self.verify_goals = verify;
+        self.verify_goals = Some(verify);
+        self
+    }
+
+    /// Invoke a callback every `every` node expansions during search
+    ///
+    /// The callback receives a snapshot of the current `PlanningStats`
+    /// (nodes expanded, depth, elapsed time), which is useful for driving
+    /// progress displays during long-running searches. An `every` of 0 is
+    /// treated as 1.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use gtrusthop::{Domain, PlannerBuilder};
+    /// use std::sync::Arc;
+    ///
+    /// let planner = PlannerBuilder::new()
+    ///     .with_domain(Domain::new("test"))
+    ///     .with_progress_callback(100, Arc::new(|stats| {
+    ///         println!("{} nodes expanded", stats.nodes_expanded);
+    ///     }))
+    ///     .build()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_progress_callback(mut self, every: usize, callback: ProgressCallback) -> Self {
+        self.progress = Some(ProgressReporter::new(every, callback));
+        self
+    }
+
+    /// Remember search nodes known to fail for the remainder of a search
+    ///
+    /// When enabled, a (state, todo list) pair whose whole subtree was explored
+    /// without finding a plan is recorded, and reaching the same pair again
+    /// through a different decomposition fails immediately. Disabled by default.
+    pub fn with_failure_cache(mut self, enabled: bool) -> Self {
+        self.failure_cache = enabled;
+        self
+    }
+
+    /// Keep searching until up to `max_plans` distinct plans are found (at least 1)
+    ///
+    /// `find_plan()` still returns the first plan found; `find_plan_meta()`
+    /// additionally reports whether another plan exists. Defaults to 1.
+    pub fn with_max_plans(mut self, max_plans: usize) -> Self {
+        self.max_plans = max_plans.max(1);
+        self
+    }
+
+    /// Supply a goal-distance heuristic, used by `with_thrashing_detection()`
+    pub fn with_heuristic(mut self, heuristic: HeuristicFn) -> Self {
+        self.heuristic = Some(Heuristic::new(heuristic));
+        self
+    }
+
+    /// Set the deepest node the recursive strategy expands on the call stack
+    ///
+    /// Past it, `PlanningStrategy::Recursive` fails with `GTRustHopError::RecursionLimit`
+    /// and `PlanningStrategy::Auto` continues with the iterative search. Without
+    /// a limit, `Recursive` goes as deep as the call stack allows and `Auto`
+    /// switches at `DEFAULT_RECURSION_LIMIT`.
+    pub fn with_recursion_limit(mut self, limit: usize) -> Self {
+        self.recursion_limit = Some(limit);
+        self
+    }
+
+    /// Cut branches where two methods keep undoing each other's work
+    ///
+    /// A branch is abandoned, and `possible thrashing` logged at verbose level 2,
+    /// once the same method of the same task has been applied more than
+    /// `max_repeats` times along it without the heuristic distance decreasing.
+    /// Requires `with_heuristic()`.
+    pub fn with_thrashing_detection(mut self, max_repeats: usize) -> Self {
+        self.thrashing_limit = Some(max_repeats);
+        self
+    }
+
+    /// Forbid states for which `constraint` returns `false`
+    ///
+    /// The constraint is checked on every state produced by an action; a branch
+    /// reaching a forbidden state fails and the planner backtracks. Unlike action
+    /// preconditions, this enforces an invariant across the whole domain. May be
+    /// called several times to add several constraints.
+    pub fn with_state_constraint(mut self, constraint: StateConstraintFn) -> Self {
+        self.constraints.push(StateConstraint::new(constraint));
+        self
+    }
+
+    /// Apply the leading action of each task method's subtasks while expanding the task
+    ///
+    /// Methods often start with the same action; with the cache enabled, its result
+    /// is computed once per task expansion and methods whose first action is not
+    /// applicable are pruned immediately. Cache hits are counted in
+    /// `PlanningStats::action_cache_hits`. Disabled by default.
+    pub fn with_action_cache(mut self, enabled: bool) -> Self {
+        self.action_cache = enabled;
+        self
+    }
+
+    /// Reject domains in which an action, command or method was declared more than once
+    ///
+    /// `Domain::declare_action()` and friends silently replace an existing entry of
+    /// the same name, and the method declarers append a method even if it was
+    /// already declared. In strict mode, `build()` returns
+    /// `GTRustHopError::DuplicateAction` for the first redeclared action or command
+    /// name, then `GTRustHopError::DuplicateMethod` for the first task, variable or
+    /// relation given the same method twice (see `Domain::redeclared_method_names()`).
+    /// Methods are told apart by type, so duplicated function pointers and capturing
+    /// closures are not detected.
+    ///
+    /// Strict mode also returns `GTRustHopError::UnusedGoalVariable` for a variable
+    /// with unigoal methods that is neither declared by an action nor part of a
+    /// registered multigoal (see `Domain::unigoal_vars_without_actions()`).
+    pub fn with_strict_declarations(mut self, strict: bool) -> Self {
+        self.strict_declarations = strict;
+        self
+    }
+
+    /// Catch panics in action and method closures instead of unwinding through the planner
+    ///
+    /// Action preconditions, multigoal decomposers, the heuristic and state
+    /// constraints are guarded too. A caught panic is handled according to the
+    /// panic policy (see `with_panic_policy()`), which by default aborts the search
+    /// with `GTRustHopError::MethodPanicked`. Disabled by default.
+    #[cfg(feature = "std")]
+    pub fn with_panic_isolation(mut self, enabled: bool) -> Self {
+        self.panic_isolation = enabled;
+        self
+    }
+
+    /// Set how panics caught by panic isolation are handled
+    #[cfg(feature = "std")]
+    pub fn with_panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
+    }
+
+    /// Track a resource that must stay non-negative along the plan
+    ///
+    /// The planner keeps the resource level in `resources[name]` (see `RESOURCES_VAR`),
+    /// starting from `initial` unless the initial state already holds a level, and
+    /// applies the deltas declared with `Domain::declare_action_resource_delta()`.
+    /// Actions that would drive the level below zero are pruned. Planner helpers
+    /// that replay plans, like `Planner::complete_plan()`, apply the deltas too;
+    /// `Domain::apply_plan()` ignores them.
+    pub fn with_resource(mut self, name: impl Into<String>, initial: f64) -> Self {
+        self.resources.push((name.into(), initial));
+        self
+    }
+
+    /// Transform every plan the planner finds before returning it
+    ///
+    /// Useful to normalize or compress plans, e.g. to drop redundant action pairs.
+    /// It only sees complete plans, never the partial plans of the search, and it
+    /// is up to the postprocessor to keep the plan valid.
+    pub fn with_plan_postprocessor(mut self, postprocessor: PlanPostprocessorFn) -> Self {
+        self.postprocessor = Some(PlanPostprocessor(postprocessor));
+        self
+    }
+
+    /// Set the facts that never change, passed to actions declared with `Domain::declare_action_with_rigid()`
+    pub fn with_rigid_state(mut self, rigid: State) -> Self {
+        self.rigid_state = rigid;
+        self
+    }
+
+    /// Prefer items named `before` to go ahead of items named `after` in unordered blocks
+    ///
+    /// When choosing which item of a `PlanItem::Unordered` block comes first, the
+    /// planner tries items no other item of the block is preferred before first.
+    /// Every order stays possible, so this only changes which plans are found first
+    /// and how much backtracking it takes. Names are compared with `PlanItem::name()`.
+    pub fn with_order_preference(mut self, before: impl Into<String>, after: impl Into<String>) -> Self {
+        self.order_preferences.push((before.into(), after.into()));
+        self
+    }
+
+    /// Stop as soon as the search reaches a state satisfying `predicate`
+    ///
+    /// The plan so far is returned as a solution, ignoring whatever is left of
+    /// the todo list, so a planner can plan "until safe" instead of to completion.
+    pub fn with_goal_predicate(mut self, predicate: GoalPredicateFn) -> Self {
+        self.goal_predicate = Some(GoalPredicate(predicate));
+        self
+    }
+
+    /// Remember the results of the last `capacity` distinct queries
+    ///
+    /// `find_plan()` and `find_plan_with_stats()` calls with a state and todo list
+    /// equal to a remembered query return its result without searching again.
+    /// Planners cloned from this one share the cache.
+    #[cfg(feature = "std")]
+    pub fn with_plan_cache(mut self, capacity: usize) -> Self {
+        self.plan_cache = Some(capacity);
+        self
+    }
+
+    /// Stop searching with `GTRustHopError::Cancelled` once `token` is set
+    ///
+    /// The token is checked before each node is expanded, so another thread can
+    /// cancel a long search promptly. Planners cloned from this one share it.
+    pub fn with_cancel_token(mut self, token: Arc<AtomicBool>) -> Self {
+        self.cancel_token = Some(token);
+        self
+    }
+
+    /// Only accept plans that work for every outcome of nondeterministic actions
+    ///
+    /// Actions declared with `Domain::declare_nondet_action()` are planned with
+    /// their first outcome; in conformant mode each plan found is then replayed
+    /// from every combination of outcomes, and rejected unless all its actions
+    /// apply and the goals of the todo list (or the goal predicate, see
+    /// `with_goal_predicate()`) hold at the end. Outcomes are checked like the
+    /// actions of the search: resources, state constraints and goals maintained
+    /// from the todo list apply to each of them. The failure cache is not used,
+    /// since whether a plan is accepted depends on the actions before the node
+    /// as well as its state.
+    pub fn with_conformant(mut self, conformant: bool) -> Self {
+        self.conformant = conformant;
+        self
+    }
+
+    /// Try first the task methods whose first action has one of `tags`
+    ///
+    /// Among the applicable methods of a task, those starting with an action
+    /// tagged (see `Domain::tag_action()`) with a preferred tag are tried before
+    /// the others; each group keeps the usual method order.
+    pub fn prefer_tags(mut self, tags: &[&str]) -> Self {
+        self.preferred_tags = tags.iter().map(|tag| tag.to_string()).collect();
         self
     }
 
     /// Register a multigoal with the planner
     ///
     /// This replaces the global `register_multigoal()` function by storing
-    /// multigoals as instance data in the planner.
+    /// multigoals as instance data in the planner. The multigoal gets the id
+    /// `goal_{name}`, which the built-in `_achieve_multigoal` task accepts (see
+    /// `ACHIEVE_MULTIGOAL_TASK`).
     ///
     /// # Arguments
     ///
@@ -321,7 +766,9 @@ impl PlannerBuilder {
     ///
     /// # Returns
     ///
-    /// The builder with the multigoal registered
+    /// The builder with the multigoal registered. Registering a different
+    /// multigoal with the same name makes `build()` fail with
+    /// `GTRustHopError::MultigoalIdConflict`.
     ///
     /// # Example
     ///
@@ -340,8 +787,7 @@ impl PlannerBuilder {
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     pub fn with_multigoal(mut self, multigoal: Multigoal) -> Self {
-        let goal_id = format!("goal_{}", multigoal.name);
-        self.multigoals.insert(goal_id, multigoal);
+        self.insert_multigoal(multigoal);
         self
     }
 
@@ -356,29 +802,92 @@ impl PlannerBuilder {
     /// The builder with all multigoals registered
     pub fn with_multigoals(mut self, multigoals: Vec<Multigoal>) -> Self {
         for multigoal in multigoals {
-            let goal_id = format!("goal_{}", multigoal.name);
-            self.multigoals.insert(goal_id, multigoal);
+            self.insert_multigoal(multigoal);
         }
         self
     }
 
+    fn insert_multigoal(&mut self, multigoal: Multigoal) {
+        let goal_id = format!("goal_{}", multigoal.name);
+        if let Some(previous) = self.multigoals.insert(goal_id.clone(), multigoal) {
+            if previous != self.multigoals[&goal_id] && self.multigoal_conflict.is_none() {
+                self.multigoal_conflict = Some(goal_id);
+            }
+        }
+    }
+
     /// Build the planner instance
     pub fn build(self) -> Result<Planner> {
-        let mut domain = self.domain.ok_or_else(||
+        let domain = self.domain.ok_or_else(||
             GTRustHopError::generic("Domain is required for planner")
         )?;
 
-        // If this is a blocks domain and we have multigoals, create a new domain with multigoals baked in
-        if domain.name == "blocks_htn" && !self.multigoals.is_empty() {
-            domain = crate::examples::blocks_htn_example::create_blocks_htn_domain_with_multigoals(self.multigoals.clone())?;
+        if self.strategy.is_some() && self.custom_strategy.is_some() {
+            return Err(GTRustHopError::conflicting_strategy_config());
+        }
+
+        if let Some(id) = self.multigoal_conflict {
+            return Err(GTRustHopError::multigoal_id_conflict(id));
+        }
+
+        if let (true, Some(name)) = (self.strict_declarations, domain.redeclared_names().first()) {
+            return Err(GTRustHopError::duplicate_action(name.clone()));
+        }
+        if let (true, Some(name)) = (self.strict_declarations, domain.redeclared_method_names().first()) {
+            return Err(GTRustHopError::duplicate_method(name.clone()));
         }
 
+        if self.strict_declarations {
+            let unused = domain.unigoal_vars_without_actions().into_iter()
+                .find(|var| self.multigoals.values().all(|goal| goal.get_goal_map(var).is_none_or(HashMap::is_empty)));
+            if let Some(var_name) = unused {
+                return Err(GTRustHopError::unused_goal_variable(var_name.clone()));
+            }
+        }
+
+        if self.thrashing_limit.is_some() && self.heuristic.is_none() {
+            return Err(GTRustHopError::generic("Thrashing detection requires a heuristic"));
+        }
+
+        let verify_goals = self.verify_goals
+            .or(domain.default_verify_goals())
+            .unwrap_or(DEFAULT_VERIFY_GOALS);
+
+        // If this is a blocks domain and we have multigoals, create a new domain with multigoals baked in
+        #[cfg(feature = "std")]
+        let domain = if domain.name == "blocks_htn" && !self.multigoals.is_empty() {
+            crate::examples::blocks_htn_example::create_blocks_htn_domain_with_multigoals(self.multigoals.clone())?
+        } else {
+            domain
+        };
+
         Ok(Planner {
             domain: Arc::new(domain),
             verbose_level: self.verbose_level,
-            strategy: self.strategy,
-            verify_goals: self.verify_goals,
+            strategy: self.strategy.unwrap_or(PlanningStrategy::Iterative),
+            custom_strategy: self.custom_strategy,
+            verify_goals,
             multigoals: Arc::new(self.multigoals),
+            progress: self.progress,
+            failure_cache: self.failure_cache,
+            max_plans: self.max_plans,
+            heuristic: self.heuristic,
+            thrashing_limit: self.thrashing_limit,
+            recursion_limit: self.recursion_limit,
+            constraints: self.constraints,
+            action_cache: self.action_cache,
+            #[cfg(feature = "std")]
+            panic_policy: self.panic_isolation.then_some(self.panic_policy),
+            postprocessor: self.postprocessor,
+            resources: self.resources,
+            #[cfg(feature = "std")]
+            plan_cache: self.plan_cache.map(|capacity| Arc::new(Mutex::new(PlanCache::new(capacity)))),
+            preferred_tags: self.preferred_tags,
+            cancel_token: self.cancel_token,
+            conformant: self.conformant,
+            goal_predicate: self.goal_predicate,
+            rigid_state: Arc::new(self.rigid_state),
+            order_preferences: self.order_preferences,
         })
     }
 }
@@ -392,19 +901,62 @@ pub struct Planner {
     domain: Arc<Domain>,
     verbose_level: i32,
     strategy: PlanningStrategy,
+    custom_strategy: Option<CustomStrategy>,
     verify_goals: bool,
     multigoals: Arc<HashMap<String, Multigoal>>,
+    progress: Option<ProgressReporter>,
+    failure_cache: bool,
+    max_plans: usize,
+    heuristic: Option<Heuristic>,
+    thrashing_limit: Option<usize>,
+    recursion_limit: Option<usize>,
+    constraints: Vec<StateConstraint>,
+    action_cache: bool,
+    #[cfg(feature = "std")]
+    panic_policy: Option<PanicPolicy>,
+    postprocessor: Option<PlanPostprocessor>,
+    resources: Vec<(String, f64)>,
+    #[cfg(feature = "std")]
+    plan_cache: Option<Arc<Mutex<PlanCache>>>,
+    preferred_tags: Vec<String>,
+    cancel_token: Option<Arc<AtomicBool>>,
+    conformant: bool,
+    goal_predicate: Option<GoalPredicate>,
+    rigid_state: Arc<State>,
+    order_preferences: Vec<(String, String)>,
 }
 
 impl Planner {
     /// Create a new planner with the given domain and default settings
     pub fn new(domain: Domain) -> Self {
+        let verify_goals = domain.default_verify_goals().unwrap_or(DEFAULT_VERIFY_GOALS);
         Self {
             domain: Arc::new(domain),
             verbose_level: 1,
             strategy: PlanningStrategy::Iterative,
-            verify_goals: true,
+            custom_strategy: None,
+            verify_goals,
             multigoals: Arc::new(HashMap::new()),
+            progress: None,
+            failure_cache: false,
+            max_plans: 1,
+            heuristic: None,
+            thrashing_limit: None,
+            recursion_limit: None,
+            constraints: Vec::new(),
+            action_cache: false,
+            #[cfg(feature = "std")]
+            panic_policy: None,
+            postprocessor: None,
+            resources: Vec::new(),
+            #[cfg(feature = "std")]
+            plan_cache: None,
+            preferred_tags: Vec::new(),
+            cancel_token: None,
+            conformant: false,
+            goal_predicate: None,
+            rigid_state: Arc::new(State::new("rigid")),
+            order_preferences: Vec::new(),
         }
     }
 
@@ -413,6 +965,14 @@ impl Planner {
         &self.domain
     }
 
+    /// Get the captured domain's name and revision, formatted as `name@revision`
+    ///
+    /// The planner holds its own copy of the domain, so later edits to the
+    /// original `Domain` are not seen; comparing revisions reveals a stale planner.
+    pub fn domain_name_with_version(&self) -> String {
+        format!("{}@{}", self.domain.name, self.domain.revision())
+    }
+
     /// Get the current verbose level
     pub fn verbose_level(&self) -> i32 {
         self.verbose_level
@@ -428,6 +988,11 @@ impl Planner {
         self.verify_goals
     }
 
+    /// Get the maximum number of distinct plans a search looks for
+    pub fn max_plans(&self) -> usize {
+        self.max_plans
+    }
+
     /// Get a multigoal by its ID
     ///
     /// This replaces the global `get_multigoal()` function by accessing
@@ -475,11 +1040,10 @@ impl Planner {
         new_multigoals.insert(goal_id, multigoal);
 
         Self {
-            domain: self.domain,
-            verbose_level: self.verbose_level,
-            strategy: self.strategy,
-            verify_goals: self.verify_goals,
             multigoals: Arc::new(new_multigoals),
+            #[cfg(feature = "std")]
+            plan_cache: self.fresh_plan_cache(),
+            ..self
         }
     }
 
@@ -489,35 +1053,42 @@ impl Planner {
             return Err(GTRustHopError::InvalidVerboseLevel { level });
         }
         Ok(Self {
-            domain: Arc::clone(&self.domain),
             verbose_level: level,
-            strategy: self.strategy,
-            verify_goals: self.verify_goals,
-            multigoals: Arc::clone(&self.multigoals),
+            ..self.clone()
         })
     }
 
     /// Create a new planner with different strategy
+    ///
+    /// The new planner uses the built-in strategy even if this one has a custom strategy.
+    /// It starts with an empty plan cache, since its plans may differ.
     pub fn with_strategy(&self, strategy: PlanningStrategy) -> Self {
         Self {
-            domain: Arc::clone(&self.domain),
-            verbose_level: self.verbose_level,
             strategy,
-            verify_goals: self.verify_goals,
-            multigoals: Arc::clone(&self.multigoals),
+            custom_strategy: None,
+            #[cfg(feature = "std")]
+            plan_cache: self.fresh_plan_cache(),
+            ..self.clone()
         }
     }
 
     /// Create a new planner with different goal verification setting
+    ///
+    /// The new planner starts with an empty plan cache, since its plans may differ.
     pub fn with_goal_verification(&self, verify: bool) -> Self {
         Self {
-            domain: Arc::clone(&self.domain),
-            verbose_level: self.verbose_level,
-            strategy: self.strategy,
             verify_goals: verify,
-            multigoals: Arc::clone(&self.multigoals),
+            #[cfg(feature = "std")]
+            plan_cache: self.fresh_plan_cache(),
+            ..self.clone()
         }
     }
+
+    /// Get an empty plan cache of the same capacity, for a reconfigured planner
+    #[cfg(feature = "std")]
+    fn fresh_plan_cache(&self) -> Option<Arc<Mutex<PlanCache>>> {
+        self.plan_cache.as_ref().map(|cache| Arc::new(Mutex::new(cache.lock().unwrap().emptied())))
+    }
 }
 
 #[cfg(test)]
@@ -557,4 +1128,157 @@ mod tests {
         assert!(context.verify_goals);
         assert_eq!(context.strategy, PlanningStrategy::Iterative);
     }
+
+    #[test]
+    fn test_planner_captures_domain_revision() -> Result<()> {
+        let mut domain = Domain::new("evolving");
+        assert_eq!(domain.revision(), 0);
+        domain.declare_action("noop", |state: &mut State, _args: &[StateValue]| Some(state.clone()))?;
+        assert_eq!(domain.revision(), 1);
+
+        let planner = PlannerBuilder::new().with_domain(domain.clone()).build()?;
+        assert_eq!(planner.domain_name_with_version(), "evolving@1");
+
+        // Further edits to the source domain are not seen by the planner
+        domain.declare_task_method("work", |_state: &State, _args: &[StateValue]| Some(vec![]))?;
+        assert_eq!(domain.revision(), 2);
+        assert_eq!(planner.domain_name_with_version(), "evolving@1");
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_declarations_reject_redeclared_action() -> Result<()> {
+        let mut domain = Domain::new("copy_paste");
+        domain.declare_action("noop", |state: &mut State, _args: &[StateValue]| Some(state.clone()))?;
+        domain.declare_action("noop", |_state: &mut State, _args: &[StateValue]| None)?;
+
+        assert!(PlannerBuilder::new().with_domain(domain.clone()).build().is_ok());
+        let result = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_strict_declarations(true)
+            .build();
+        assert_eq!(result.err(), Some(GTRustHopError::duplicate_action("noop")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_declarations_reject_redeclared_method() -> Result<()> {
+        fn m_work(_state: &State, _args: &[StateValue]) -> Option<Vec<PlanItem>> {
+            Some(Vec::new())
+        }
+        let mut domain = Domain::new("copy_paste");
+        domain.declare_task_method("work", m_work)?;
+        domain.declare_task_method("work", m_work)?;
+
+        assert!(PlannerBuilder::new().with_domain(domain.clone()).build().is_ok());
+        let result = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_strict_declarations(true)
+            .build();
+        assert_eq!(result.err(), Some(GTRustHopError::duplicate_method("work")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_declarations_reject_unused_goal_variable() -> Result<()> {
+        use crate::domains::blocks::install_blocks_actions;
+
+        let mut domain = Domain::new("blocks");
+        install_blocks_actions(&mut domain)?;
+        domain.declare_unigoal_method("pos", |_state: &State, _arg: &str, _value: &StateValue| None)?;
+        domain.declare_unigoal_method("at", |_state: &State, _arg: &str, _value: &StateValue| None)?;
+
+        assert!(PlannerBuilder::new().with_domain(domain.clone()).build().is_ok());
+        let result = PlannerBuilder::new()
+            .with_domain(domain.clone())
+            .with_strict_declarations(true)
+            .build();
+        assert_eq!(result.err(), Some(GTRustHopError::unused_goal_variable("at")));
+
+        // A registered goal on the variable is a use
+        let mut goal = Multigoal::new("somewhere");
+        goal.set_goal("at", "robot", crate::core::string_value("dock"));
+        let result = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_strict_declarations(true)
+            .with_multigoal(goal)
+            .build();
+        assert!(result.is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_multigoal_id_conflict() -> Result<()> {
+        let goal = |block: &str, target: &str| {
+            let mut goal = Multigoal::new("tower");
+            goal.set_goal("pos", block, crate::core::string_value(target));
+            goal
+        };
+
+        let result = PlannerBuilder::new()
+            .with_domain(Domain::new("blocks"))
+            .with_multigoal(goal("a", "b"))
+            .with_multigoals(vec![goal("b", "a")])
+            .build();
+        assert_eq!(result.err(), Some(GTRustHopError::multigoal_id_conflict("goal_tower")));
+
+        // Registering the same goals again is harmless
+        let planner = PlannerBuilder::new()
+            .with_domain(Domain::new("blocks"))
+            .with_multigoal(goal("a", "b"))
+            .with_multigoal(goal("a", "b"))
+            .build()?;
+        assert!(planner.get_multigoal("goal_tower").is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_strategy() -> Result<()> {
+        use crate::domains::create_simple_htn_domain;
+        use crate::examples::create_travel_task;
+
+        let mut state = State::new("travel");
+        state.set_var("loc", "alice", "home_a".into());
+        state.set_var("cash", "alice", 20.0.into());
+        let todo = vec![create_travel_task("alice", "home_a", "park")];
+
+        let builder = PlannerBuilder::new()
+            .with_domain(create_simple_htn_domain()?)
+            .with_verbose_level(0)?;
+        let expected = builder.clone().build()?.find_plan(state.clone(), todo.clone())?;
+        let planner = builder.clone()
+            .with_custom_strategy(Arc::new(strategy::RecursiveStrategy))
+            .build()?;
+        assert_eq!(planner.find_plan(state, todo)?, expected);
+
+        // Setting both kinds of strategy is ambiguous
+        let result = builder
+            .with_strategy(PlanningStrategy::Recursive)
+            .with_custom_strategy(Arc::new(strategy::RecursiveStrategy))
+            .build();
+        assert_eq!(result.err(), Some(GTRustHopError::conflicting_strategy_config()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_goals_precedence() -> Result<()> {
+        // Global default
+        let planner = PlannerBuilder::new().with_domain(Domain::new("plain")).build()?;
+        assert!(planner.verify_goals());
+
+        // Domain default overrides the global default
+        let mut trusted = Domain::new("trusted");
+        trusted.set_default_verify_goals(false);
+        let planner = PlannerBuilder::new().with_domain(trusted.clone()).build()?;
+        assert!(!planner.verify_goals());
+        assert!(!Planner::new(trusted.clone()).verify_goals());
+
+        // Explicit builder setting overrides the domain default
+        let planner = PlannerBuilder::new()
+            .with_domain(trusted)
+            .with_goal_verification(true)
+            .build()?;
+        assert!(planner.verify_goals());
+        Ok(())
+    }
 }
@@ -1,9 +1,9 @@
 //! Main planning functions for GTRusthop
 
-use super::get_planning_context;
-use crate::core::{State, Domain, PlanItem, TodoList, StateValue};
-use crate::error::Result;
-use std::sync::Arc;
+use crate::compat::prelude::*;
+use crate::core::{State, PlanItem, TodoList, StateValue};
+#[cfg(feature = "std")]
+use {super::get_planning_context, crate::core::Domain, crate::error::Result, std::sync::Arc};
 
 
 
@@ -53,6 +53,7 @@ where
 
 
 /// Set the current domain for planning
+#[cfg(feature = "std")]
 pub fn set_current_domain(domain: Domain) -> Result<()> {
     let context = super::PlanningContext::new(Arc::new(domain));
     super::set_planning_context(context);
@@ -60,12 +61,14 @@ pub fn set_current_domain(domain: Domain) -> Result<()> {
 }
 
 /// Get the current domain
+#[cfg(feature = "std")]
 pub fn get_current_domain() -> Result<Arc<Domain>> {
     let context = get_planning_context()?;
     Ok(context.domain.clone())
 }
 
 /// Print information about the current domain
+#[cfg(feature = "std")]
 pub fn print_domain() -> Result<()> {
     let domain = get_current_domain()?;
     domain.display();
@@ -73,6 +76,7 @@ pub fn print_domain() -> Result<()> {
 }
 
 /// Print all action names in the current domain
+#[cfg(feature = "std")]
 pub fn print_actions() -> Result<()> {
     let domain = get_current_domain()?;
     domain.print_actions();
@@ -80,6 +84,7 @@ pub fn print_actions() -> Result<()> {
 }
 
 /// Print all command names in the current domain
+#[cfg(feature = "std")]
 pub fn print_commands() -> Result<()> {
     let domain = get_current_domain()?;
     domain.print_commands();
@@ -87,6 +92,7 @@ pub fn print_commands() -> Result<()> {
 }
 
 /// Print all methods in the current domain
+#[cfg(feature = "std")]
 pub fn print_methods() -> Result<()> {
     let domain = get_current_domain()?;
     domain.print_methods();
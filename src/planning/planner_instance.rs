@@ -3,26 +3,841 @@
 //! This module provides the core planning functionality for isolated planner instances,
 //! eliminating race conditions from global state.
 
-use crate::core::{State, PlanItem, Plan};
-use crate::error::Result;
-use crate::planning::{Planner, PlanningStrategy};
+use crate::compat::prelude::*;
+use crate::core::{Domain, State, StateValue, PlanItem, Plan, Multigoal, string_value, wildcard_type, ACHIEVE_MULTIGOAL_TASK, MAX_SOFT_GOALS, MAX_UNORDERED_ITEMS, RESOURCES_VAR, TYPES_VAR};
+use crate::error::{GTRustHopError, Result};
+use crate::planning::{item_to_string, CommandPolicy, DEFAULT_RECURSION_LIMIT, STABLE_PLAN_CANDIDATES, HPlan, HPlanNode, MethodChoice, Planner, PlanningContext, PlanningResult, PlanningStrategy, PlanMeta, PlanningStats, ProgressReporter, SearchEvent, SearchEventKind, StrategyComparison};
+#[cfg(feature = "std")]
+use crate::planning::cache::PlanCache;
+#[cfg(feature = "std")]
+use crate::planning::PanicPolicy;
+use crate::planning::compaction::compact_replayed;
+use crate::planning::verification::create_multigoal_verification_task;
+use crate::compat::{HashMap, HashSet};
+use core::sync::atomic::Ordering;
+use alloc::sync::Arc;
+
+/// A node in the planning search space
+///
+/// States and plan prefixes are shared with the parent node, so refining a task or
+/// goal never copies them; only applying an action creates a new state.
+#[derive(Debug)]
+struct SearchNode {
+    state: Arc<State>,
+    todo_list: Vec<PlanItem>,
+    plan: PlanPrefix,
+    depth: usize,
+    history: MethodHistory,
+    /// Goals that every state from here on must satisfy, see `PlanItem::Maintain`
+    maintained: Arc<Vec<PlanItem>>,
+    choices: ChoicePrefix,
+    /// Refinements made so far along a branch, recorded only for hierarchical plans
+    refinements: RefinementPrefix,
+    /// Applicable methods after the chosen one, for each method choice in `choices`
+    untried: UntriedPrefix,
+}
+
+/// Persistent list shared between search nodes, such as a plan prefix
+///
+/// Appending an item is O(1) and leaves the original prefix untouched, so
+/// sibling branches never see each other's actions.
+#[derive(Debug)]
+struct Prefix<T> {
+    last: Option<Arc<Link<T>>>,
+    len: usize,
+}
+
+/// The actions chosen so far along a branch
+type PlanPrefix = Prefix<PlanItem>;
+
+/// The `(name, method_index)` choices made so far along a branch
+type ChoicePrefix = Prefix<MethodChoice>;
+
+/// How a todo list item was handled, for rebuilding hierarchical plans
+#[derive(Debug, Clone)]
+enum Refinement {
+    /// An action was applied
+    Action(PlanItem),
+    /// A task or goal was replaced by this many items
+    Task(PlanItem, usize),
+}
+
+/// The `(name, count)` of applicable methods left untried by each method choice of a branch
+type UntriedPrefix = Prefix<(String, usize)>;
+
+/// The refinements made so far along a branch, in the order they were made
+type RefinementPrefix = Prefix<Refinement>;
+
+/// A single item in a `Prefix`, linked to the items before it
+#[derive(Debug)]
+struct Link<T> {
+    item: T,
+    prev: Option<Arc<Link<T>>>,
+}
+
+impl<T> Default for Prefix<T> {
+    fn default() -> Self {
+        Self { last: None, len: 0 }
+    }
+}
+
+impl<T> Clone for Prefix<T> {
+    fn clone(&self) -> Self {
+        Self { last: self.last.clone(), len: self.len }
+    }
+}
+
+impl<T> Prefix<T> {
+    /// Iterate over the items, newest first
+    fn iter_rev(&self) -> impl Iterator<Item = &T> {
+        core::iter::successors(self.last.as_deref(), |link| link.prev.as_deref()).map(|link| &link.item)
+    }
+}
+
+impl<T: Clone> Prefix<T> {
+    /// Return a new prefix extended with `item`
+    fn push(&self, item: T) -> Self {
+        Self {
+            last: Some(Arc::new(Link { item, prev: self.last.clone() })),
+            len: self.len + 1,
+        }
+    }
+
+    /// Materialize the prefix, oldest item first
+    fn to_vec(&self) -> Vec<T> {
+        let mut items = Vec::with_capacity(self.len);
+        let mut link = self.last.as_deref();
+        while let Some(current) = link {
+            items.push(current.item.clone());
+            link = current.prev.as_deref();
+        }
+        items.reverse();
+        items
+    }
+}
+
+impl<T> Drop for Link<T> {
+    /// Unlink iteratively so dropping a long plan cannot overflow the stack
+    fn drop(&mut self) {
+        let mut prev = self.prev.take();
+        while let Some(link) = prev {
+            match Arc::try_unwrap(link) {
+                Ok(mut link) => prev = link.prev.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// Task method applications along a branch, for thrashing detection
+///
+/// The latest use of a `(task_name, method_index)` holds its current counts.
+type MethodHistory = Prefix<MethodUse>;
+
+/// An application of a task method, see `MethodHistory`
+#[derive(Debug, Clone)]
+struct MethodUse {
+    task_name: String,
+    index: usize,
+    /// Applications since the heuristic distance last decreased
+    repeats: usize,
+    /// Lowest heuristic distance seen so far
+    best: f64,
+}
+
+impl MethodHistory {
+    /// Return the history extended with one application, and the new repeat count
+    fn record(&self, task_name: &str, index: usize, distance: f64) -> (Self, usize) {
+        let (repeats, best) = self.iter_rev()
+            .find(|used| used.index == index && used.task_name == task_name)
+            .map_or((0, f64::INFINITY), |used| (used.repeats, used.best));
+        let (repeats, best) = if distance < best { (1, distance) } else { (repeats + 1, best) };
+        (self.push(MethodUse { task_name: task_name.to_string(), index, repeats, best }), repeats)
+    }
+}
+
+/// Result of expanding a single search node
+enum Expansion {
+    /// The todo list is empty and the node's plan is a solution
+    Solved(Solution),
+    /// Successor nodes, in the order they should be explored
+    Successors(Vec<SearchNode>),
+}
+
+/// Entry on the iterative planner's stack
+enum Frame {
+    /// A node still to be expanded
+    Expand(SearchNode),
+    /// All successors of the node with this failure key were explored; they failed
+    /// unless the `branch_marker()` has changed since
+    Exhausted(String, usize),
+}
+
+/// A plan found by a search, with how the search got there
+struct Solution {
+    plan: Plan,
+    choices: Vec<MethodChoice>,
+    refinements: Vec<Refinement>,
+    untried: Vec<(String, usize)>,
+}
+
+/// Plans found by a search, with the method choices that led to each plan
+struct SearchResults {
+    plans: Vec<Plan>,
+    choices: Vec<Vec<MethodChoice>>,
+    refinements: Vec<Vec<Refinement>>,
+    dead_end: Option<DeadEnd>,
+    /// Longest plan prefix of any expanded node
+    longest_prefix: Plan,
+    /// Search events, if recorded
+    events: Vec<SearchEvent>,
+    stats: PlanningStats,
+}
+
+/// Methods a decomposition may apply while a choice trace is replayed
+#[derive(Debug, Clone, Copy)]
+enum ForcedChoice {
+    /// Not replaying: every method is tried
+    Any,
+    /// Only the recorded method
+    Method(usize),
+    /// The branch contradicts the trace, so no method
+    Contradicted,
+}
+
+impl ForcedChoice {
+    fn allows(self, index: usize) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Method(forced) => forced == index,
+            Self::Contradicted => false,
+        }
+    }
+}
+
+/// What a search records besides its plans
+#[derive(Debug, Clone, Copy, Default)]
+struct Recording {
+    /// Refinements of each plan, see `Planner::find_hierarchical_plan()`
+    refinements: bool,
+    /// Every search event, see `Planner::find_plan_trace()`
+    events: bool,
+}
+
+/// The deepest todo list item a search could not refine, and the state at that point
+struct DeadEnd {
+    depth: usize,
+    item: PlanItem,
+    state: Arc<State>,
+}
+
+/// Bookkeeping shared by all nodes of a single search
+struct SearchContext<'a> {
+    stats: PlanningStats,
+    #[cfg(feature = "std")]
+    started: std::time::Instant,
+    progress: Option<&'a ProgressReporter>,
+    /// Failure keys of nodes whose whole subtree failed, if the cache is enabled
+    failures: Option<HashSet<String>>,
+    /// Distinct plans found so far, in the order they were found
+    plans: Vec<Plan>,
+    /// Solutions reached so far, counting plans found more than once
+    solutions_reached: usize,
+    /// Method choices that led to each plan in `plans`
+    choices: Vec<Vec<MethodChoice>>,
+    /// Refinements that led to each plan in `plans`, if recorded
+    refinements: Vec<Vec<Refinement>>,
+    /// Untried methods along the branch of the first plan
+    untried: Vec<(String, usize)>,
+    /// Whether nodes record their refinements, see `Planner::find_hierarchical_plan()`
+    record_refinements: bool,
+    /// Search events so far, if they are recorded
+    events: Option<Vec<SearchEvent>>,
+    max_plans: usize,
+    /// Method choices to follow instead of trying every method, see `Planner::replay_choices()`
+    forced: Option<&'a [MethodChoice]>,
+    /// First way a branch contradicted the forced choices, reported if no plan is found
+    replay_mismatch: Option<GTRustHopError>,
+    /// Deepest node that had no successors
+    dead_end: Option<DeadEnd>,
+    /// Longest plan prefix of any expanded node, the first one found on ties
+    longest_prefix: PlanPrefix,
+    /// Initial state and todo list plans are checked against in conformant mode
+    conformant_root: Option<(State, Vec<PlanItem>)>,
+}
+
+impl<'a> SearchContext<'a> {
+    fn new(progress: Option<&'a ProgressReporter>, failure_cache: bool, max_plans: usize) -> Self {
+        Self {
+            stats: PlanningStats::new(),
+            #[cfg(feature = "std")]
+            started: std::time::Instant::now(),
+            progress,
+            failures: failure_cache.then(HashSet::new),
+            plans: Vec::new(),
+            solutions_reached: 0,
+            choices: Vec::new(),
+            refinements: Vec::new(),
+            untried: Vec::new(),
+            record_refinements: false,
+            events: None,
+            max_plans,
+            forced: None,
+            replay_mismatch: None,
+            dead_end: None,
+            longest_prefix: PlanPrefix::default(),
+            conformant_root: None,
+        }
+    }
+
+    /// Count of solutions reached and branches cut short so far
+    ///
+    /// A subtree during which this changed either contains a plan or was not
+    /// fully explored, so it must not be recorded as a failure. Solutions repeating
+    /// an earlier plan count too: the same node reached with another plan prefix
+    /// leads to a different plan.
+    fn branch_marker(&self) -> usize {
+        self.solutions_reached + self.stats.thrashing_cuts
+    }
+
+    /// Record a solution, returning `true` once enough plans have been found
+    fn record_plan(&mut self, solution: Solution) -> bool {
+        self.solutions_reached += 1;
+        if self.plans.is_empty() {
+            self.untried = solution.untried;
+        }
+        if !self.plans.contains(&solution.plan) {
+            self.plans.push(solution.plan);
+            self.choices.push(solution.choices);
+            self.refinements.push(solution.refinements);
+        }
+        self.plans.len() >= self.max_plans
+    }
+
+    /// Get the method forced for the decomposition of `name` after `position` choices
+    ///
+    /// Any method is allowed unless the search replays a choice trace. A branch
+    /// the trace does not describe, because the trace ends early or names a
+    /// different task or goal, is a dead end: a `OneOf` or `Unordered` branch may
+    /// decompose other items than the one the trace was recorded along.
+    fn forced_choice(&mut self, position: usize, name: &str) -> ForcedChoice {
+        let Some(forced) = self.forced else {
+            return ForcedChoice::Any;
+        };
+        match forced.get(position) {
+            Some((forced_name, index)) if forced_name == name => ForcedChoice::Method(*index),
+            Some((forced_name, _)) => {
+                let message = format!("choice {position} of the trace is for '{forced_name}', not '{name}'");
+                self.record_replay_mismatch(message)
+            }
+            None => self.record_replay_mismatch(format!("choice trace ends before the decomposition of '{name}'")),
+        }
+    }
+
+    /// Record that the forced method for `name` did not apply, making the branch a dead end
+    fn check_forced_choice(&mut self, forced: ForcedChoice, name: &str, applied: bool) {
+        if let ForcedChoice::Method(index) = forced {
+            if !applied {
+                self.record_replay_mismatch(format!("recorded choice {name}[{index}] is not applicable"));
+            }
+        }
+    }
+
+    fn record_replay_mismatch(&mut self, message: String) -> ForcedChoice {
+        self.replay_mismatch.get_or_insert_with(|| GTRustHopError::planning_failed(message));
+        ForcedChoice::Contradicted
+    }
+
+    /// Get the failure cache key for a node, if the cache is enabled
+    fn failure_key(&self, node: &SearchNode) -> Option<String> {
+        self.failures.as_ref()?;
+        let todo = serde_json::to_string(&node.todo_list).unwrap_or_default();
+        let maintained = serde_json::to_string(node.maintained.as_slice()).unwrap_or_default();
+        Some(format!("{}|{}|{}", node.state.fingerprint(), todo, maintained))
+    }
+
+    /// Check if a node with this key is already known to fail
+    fn is_known_failure(&self, key: &str) -> bool {
+        self.failures.as_ref().is_some_and(|failures| failures.contains(key))
+    }
+
+    /// Record that a node's whole subtree was explored without finding a plan
+    ///
+    /// Only call this for exhaustive failures: a subtree cut short by a search
+    /// limit may still contain a plan and must not be recorded.
+    fn record_failure(&mut self, key: String) {
+        if let Some(failures) = self.failures.as_mut() {
+            failures.insert(key);
+        }
+    }
+
+    /// Record a search event, if events are recorded
+    fn record_event(&mut self, kind: SearchEventKind, depth: usize, describe: impl FnOnce() -> String) {
+        if let Some(events) = &mut self.events {
+            events.push(SearchEvent { kind, depth, description: describe() });
+        }
+    }
+
+    /// Record that `item` could not be refined at `depth`, keeping the deepest such item
+    fn record_dead_end(&mut self, depth: usize, item: &PlanItem, state: Arc<State>) {
+        if self.dead_end.as_ref().is_none_or(|dead_end| depth > dead_end.depth) {
+            self.dead_end = Some(DeadEnd { depth, item: item.clone(), state });
+        }
+    }
+
+    /// Record that a node at `depth` is being expanded
+    fn record_expansion(&mut self, depth: usize) {
+        self.stats.nodes_expanded += 1;
+        self.stats.depth = depth;
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+
+        if let Some(progress) = self.progress {
+            self.stats.elapsed = self.elapsed();
+            progress.report(&self.stats);
+        }
+    }
+
+    /// Wall-clock time since the search started, always zero without `std`
+    fn elapsed(&self) -> core::time::Duration {
+        #[cfg(feature = "std")]
+        return self.started.elapsed();
+        #[cfg(not(feature = "std"))]
+        core::time::Duration::ZERO
+    }
+
+    /// Finalize and return the statistics for this search
+    fn finish(mut self) -> PlanningStats {
+        self.stats.elapsed = self.elapsed();
+        self.stats.untried_methods = core::mem::take(&mut self.untried);
+        self.stats
+    }
+}
 
 impl Planner {
     /// Find a plan to achieve the given goals/tasks
     ///
     /// This is the main planning function that uses the planner's isolated state
     /// instead of global variables, making it thread-safe.
+    ///
+    /// Returns `GTRustHopError::EmptyDomain` if the todo list is non-empty but the
+    /// domain declares no actions and no methods.
     pub fn find_plan(&self, state: State, todo_list: Vec<PlanItem>) -> Result<Option<Plan>> {
+        self.find_plan_with_stats(state, todo_list).map(|(plan, _)| plan)
+    }
+
+    /// Find a plan and report statistics about the search
+    ///
+    /// Behaves exactly like `find_plan()`, but also returns a `PlanningStats`
+    /// record describing how much work the search performed.
+    ///
+    /// A result served from the plan cache reports no work at all.
+    pub fn find_plan_with_stats(&self, state: State, todo_list: Vec<PlanItem>) -> Result<(Option<Plan>, PlanningStats)> {
+        #[cfg(feature = "std")]
+        if let Some(cache) = &self.plan_cache {
+            let key = PlanCache::key(&state, &todo_list);
+            if let Some(plan) = cache.lock().unwrap().get(&key) {
+                return Ok((plan, PlanningStats::new()));
+            }
+            let (plans, stats) = self.search_plans(state, todo_list)?;
+            let plan = plans.into_iter().next();
+            cache.lock().unwrap().insert(key, plan.clone());
+            return Ok((plan, stats));
+        }
+
+        let (plans, stats) = self.search_plans(state, todo_list)?;
+        Ok((plans.into_iter().next(), stats))
+    }
+
+    /// Find a plan and the method choices that produced it
+    ///
+    /// Returns the same plan as `find_plan()` with the `(name, method_index)` pairs
+    /// of the decompositions along its branch, in order: task methods are named
+    /// after their task, unigoal methods after their state variable, relational goal
+    /// methods after their relation and multigoal methods after the multigoal.
+    pub fn find_plan_with_choices(&self, state: State, todo_list: Vec<PlanItem>) -> Result<(Option<Plan>, Vec<MethodChoice>)> {
+        let results = self.search(state, todo_list, None, Recording::default())?;
+        Ok((results.plans.into_iter().next(), results.choices.into_iter().next().unwrap_or_default()))
+    }
+
+    /// Find the plan produced by a trace from `find_plan_with_choices()`
+    ///
+    /// Each decomposition applies exactly the recorded method instead of trying
+    /// the methods in order, so the same plan is reproduced whatever the planner's
+    /// settings. Branches the trace doesn't match, such as a `OneOf` alternative
+    /// that decomposes another task, are dead ends. If no branch follows the
+    /// trace, because a recorded method is not applicable or the trace does not
+    /// match the decompositions the planner meets, an error describes the first
+    /// mismatch.
+    pub fn replay_choices(&self, state: State, todo_list: Vec<PlanItem>, choices: &[MethodChoice]) -> Result<Option<Plan>> {
+        let results = self.search(state, todo_list, Some(choices), Recording::default())?;
+        Ok(results.plans.into_iter().next())
+    }
+
+    /// Find a plan, or explain why there is none
+    ///
+    /// Unlike `find_plan()`, a failed search is an error: `GTRustHopError::NoPlan`,
+    /// whose summary names the deepest todo list item that could not be refined
+    /// and, if goals are verified, the goals of `todo_list` still unmet there.
+    pub fn find_plan_or_explain(&self, state: State, todo_list: Vec<PlanItem>) -> Result<Plan> {
+        let goals = todo_list.clone();
+        let results = self.search(state, todo_list, None, Recording::default())?;
+        if let Some(plan) = results.plans.into_iter().next() {
+            return Ok(plan);
+        }
+
+        let stats = &results.stats;
+        let mut summary = format!("nodes expanded: {}, deepest depth: {}", stats.nodes_expanded, stats.max_depth);
+        if let Some(dead_end) = &results.dead_end {
+            summary.push_str(&format!("; stuck at {} at depth {}", item_to_string(&dead_end.item), dead_end.depth));
+            if self.verify_goals {
+                let unmet = unmet_goals(&goals, &dead_end.state);
+                if !unmet.is_empty() {
+                    summary.push_str(&format!("; unmet goals: {}", unmet.join(", ")));
+                }
+            }
+        }
+        Err(GTRustHopError::no_plan(summary))
+    }
+
+    /// Find a plan, along with the longest action prefix the search achieved
+    ///
+    /// If a plan is found it is also the prefix. Otherwise the prefix is the
+    /// longest plan of any branch explored, showing how far the todo list could
+    /// be carried out before the search got stuck.
+    pub fn find_best_partial(&self, state: State, todo_list: Vec<PlanItem>) -> Result<(Option<Plan>, Plan)> {
+        let results = self.search(state, todo_list, None, Recording::default())?;
+        match results.plans.into_iter().next() {
+            Some(plan) => Ok((Some(plan.clone()), plan)),
+            None => Ok((None, results.longest_prefix)),
+        }
+    }
+
+    /// Find a plan and group its actions into layers that can run at the same time
+    ///
+    /// Each action goes into the layer after the last one holding an action it
+    /// doesn't commute with: two actions commute if, from the state before the
+    /// earlier one, applying them in either order succeeds and gives the same
+    /// state. The layers are then checked: every action of a layer must apply to
+    /// the state the layer starts from, and running the layers in order must reach
+    /// the same final state as the plan. If the check fails, every action gets its
+    /// own layer.
+    pub fn find_plan_parallel(&self, state: State, todo_list: Vec<PlanItem>) -> Result<Option<Vec<Vec<PlanItem>>>> {
+        let Some(plan) = self.find_plan(state.copy(None), todo_list)? else {
+            return Ok(None);
+        };
+        let states = self.replay_with_states(state.copy(None), &plan)?;
+        let final_fingerprint = states[plan.len()].fingerprint();
+
+        let mut layer_of: Vec<usize> = Vec::with_capacity(plan.len());
+        let mut layers: Vec<Vec<PlanItem>> = Vec::new();
+        for (later, action) in plan.iter().enumerate() {
+            let layer = (0..later).rev()
+                .filter(|earlier| !self.actions_commute(&states[*earlier], &plan[*earlier], action))
+                .map(|earlier| layer_of[earlier] + 1)
+                .max()
+                .unwrap_or(0);
+            if layer == layers.len() {
+                layers.push(Vec::new());
+            }
+            layer_of.push(layer);
+            layers[layer].push(action.clone());
+        }
+
+        let mut current = state;
+        for layer in &layers {
+            if !layer.iter().all(|action| self.apply_plan(current.copy(None), core::slice::from_ref(action)).is_ok()) {
+                return Ok(Some(plan.into_iter().map(|action| vec![action]).collect()));
+            }
+            current = self.apply_plan(current, layer)?;
+        }
+        if current.fingerprint() != final_fingerprint {
+            return Ok(Some(plan.into_iter().map(|action| vec![action]).collect()));
+        }
+        Ok(Some(layers))
+    }
+
+    /// Remove the stretches of a plan that bring the state back to an earlier state
+    ///
+    /// Works like `compact_plan()`, but replays the plan as the search does, so
+    /// rigid actions get the planner's rigid state and resource deltas apply.
+    pub fn compact_plan(&self, initial: State, plan: &[PlanItem]) -> Result<Plan> {
+        compact_replayed(initial, plan, |state, plan| self.replay_with_states(state, plan))
+    }
+
+    /// Find a plan and log every step of the search
+    ///
+    /// The log covers the whole search, including the branches that failed, in
+    /// the order the steps happened. Useful for teaching or for replaying a
+    /// search in a user interface.
+    pub fn find_plan_trace(&self, state: State, todo_list: Vec<PlanItem>) -> Result<(Option<Plan>, Vec<SearchEvent>)> {
+        let results = self.search(state, todo_list, None, Recording { events: true, ..Recording::default() })?;
+        Ok((results.plans.into_iter().next(), results.events))
+    }
+
+    /// Find a plan that keeps the decomposition of each todo list item
+    ///
+    /// Returns the plan `find_plan()` finds, as a tree: `HPlan::flatten()` gives
+    /// back the flat plan. Plan postprocessors are not applied.
+    pub fn find_hierarchical_plan(&self, state: State, todo_list: Vec<PlanItem>) -> Result<Option<HPlan>> {
+        let roots = todo_list.len();
+        let results = self.search(state, todo_list, None, Recording { refinements: true, ..Recording::default() })?;
+        Ok(results.refinements.into_iter().next().map(|refinements| {
+            HPlan { roots: build_nodes(&mut refinements.into_iter(), roots) }
+        }))
+    }
+
+    /// Find a plan and report whether it is the only one
+    ///
+    /// Returns the same plan as `find_plan()`. With `PlannerBuilder::with_max_plans(2)`
+    /// or more, the search continues past the first plan and `PlanMeta::unique` is
+    /// `false` if a different plan exists, which points at unintended nondeterminism
+    /// in the domain.
+    pub fn find_plan_meta(&self, state: State, todo_list: Vec<PlanItem>) -> Result<(Option<Plan>, PlanMeta)> {
+        let (plans, _) = self.search_plans(state, todo_list)?;
+        let meta = PlanMeta { unique: plans.len() <= 1 };
+        Ok((plans.into_iter().next(), meta))
+    }
+
+    /// Find the plan closest to a reference plan, such as the previous plan when replanning
+    ///
+    /// Compares the plans found by one search, up to `with_max_plans()` of them
+    /// but at least `STABLE_PLAN_CANDIDATES`, and returns the one with the fewest
+    /// action insertions, deletions and substitutions from `reference`; ties go
+    /// to the plan found first.
+    pub fn find_plan_stable(&self, state: State, todo_list: Vec<PlanItem>, reference: &[PlanItem]) -> Result<Option<Plan>> {
+        let candidates = Planner { max_plans: self.max_plans.max(STABLE_PLAN_CANDIDATES), ..self.clone() };
+        let (plans, _) = candidates.search_plans(state, todo_list)?;
+        Ok(plans.into_iter().min_by_key(|plan| plan_edit_distance(plan, reference)))
+    }
+
+    /// Run the same problem with both search strategies and compare the results
+    ///
+    /// The two strategies explore the same tree in the same order, so for a
+    /// deterministic domain they should always agree. A disagreement usually means
+    /// methods or actions depend on something other than their inputs. The
+    /// planner's own strategy setting is ignored.
+    pub fn compare_strategies(&self, state: State, todo_list: Vec<PlanItem>) -> Result<StrategyComparison> {
+        let with_strategy = |strategy| self.with_strategy(strategy);
+        let iterative = with_strategy(PlanningStrategy::Iterative).find_plan(state.copy(None), todo_list.clone())?;
+        let recursive = with_strategy(PlanningStrategy::Recursive).find_plan(state, todo_list)?;
+        let agree = iterative.is_some() == recursive.is_some();
+        Ok(StrategyComparison { iterative, recursive, agree })
+    }
+
+    /// Run the same problem with and without goal verification and compare the results
+    ///
+    /// Returns the verified plan, the unverified plan and whether they differ.
+    /// A sound domain gives the same answer either way, so a difference points at
+    /// a method that claims to achieve a goal it doesn't. Errors, including
+    /// verification failures, count as no plan.
+    pub fn find_plan_verified_vs_unverified(&self, state: State, todo_list: Vec<PlanItem>) -> (Option<Plan>, Option<Plan>, bool) {
+        let verified = self.with_goal_verification(true).find_plan(state.copy(None), todo_list.clone()).ok().flatten();
+        let unverified = self.with_goal_verification(false).find_plan(state, todo_list).ok().flatten();
+        let differ = verified != unverified;
+        (verified, unverified, differ)
+    }
+
+    /// Find the cheapest plan for a multigoal with soft goals
+    ///
+    /// Minimizes the plan's action cost (see `Domain::set_action_cost()`) plus the
+    /// penalties of the soft goals left unsatisfied (see `Multigoal::set_soft_goal()`).
+    /// Every subset of the soft goals is tried as additional required goals, and
+    /// with `PlannerBuilder::with_max_plans()` each search compares several plans.
+    /// Returns the best plan with its total cost, or `Ok(None)` if the required goals
+    /// can't be achieved. At most `MAX_SOFT_GOALS` soft goals are supported.
+    pub fn find_optimal_plan(&self, state: State, multigoal: &Multigoal) -> Result<Option<(Plan, f64)>> {
+        let soft_goals = multigoal.soft_goals();
+        if soft_goals.len() > MAX_SOFT_GOALS {
+            return Err(GTRustHopError::planning_failed(format!(
+                "multigoal has {} soft goals, more than the limit of {MAX_SOFT_GOALS}",
+                soft_goals.len()
+            )));
+        }
+
+        let mut best: Option<(Plan, f64)> = None;
+        for subset in 0..1usize << soft_goals.len() {
+            let mut goal = multigoal.clone();
+            for (index, (var_name, arg, value, _)) in soft_goals.iter().enumerate() {
+                if subset & (1 << index) != 0 {
+                    goal.set_goal(var_name, arg, value.clone());
+                }
+            }
+
+            let (plans, _) = self.search_plans(state.copy(None), vec![PlanItem::multigoal(goal)])?;
+            for plan in plans {
+                let final_state = self.apply_plan(state.copy(None), &plan)?;
+                let cost = self.domain.plan_cost(&plan) + multigoal.soft_penalty(&final_state);
+                if best.as_ref().is_none_or(|(_, best_cost)| cost < *best_cost) {
+                    best = Some((plan, cost));
+                }
+            }
+        }
+        Ok(best)
+    }
+
+    /// Run a search collecting up to `max_plans` distinct plans
+    fn search_plans(&self, state: State, todo_list: Vec<PlanItem>) -> Result<(Vec<Plan>, PlanningStats)> {
+        let results = self.search(state, todo_list, None, Recording::default())?;
+        Ok((results.plans, results.stats))
+    }
+
+    /// Run a search, also returning the method choices that led to each plan
+    fn search(&self, mut state: State, todo_list: Vec<PlanItem>, forced: Option<&[MethodChoice]>, recording: Recording) -> Result<SearchResults> {
         if self.verbose_level >= 1 {
             println!("FP> find_plan, verbose={}:", self.verbose_level);
             println!("    state = {}", state.name);
             println!("    todo_list = {:?}", todo_list);
         }
 
-        match self.strategy {
-            PlanningStrategy::Iterative => self.find_plan_iterative(state, todo_list),
-            PlanningStrategy::Recursive => self.find_plan_recursive(state, todo_list, 0),
+        // Nothing can ever be planned against a domain without actions or methods
+        if !todo_list.is_empty() && self.domain.is_empty() {
+            return Err(GTRustHopError::empty_domain(&self.domain.name));
+        }
+        self.domain.check_state_invariants(&state)?;
+        self.seed_resources(&mut state);
+
+        let mut search = SearchContext::new(self.progress.as_ref(), self.failure_cache && !self.conformant, self.max_plans);
+        search.forced = forced;
+        search.record_refinements = recording.refinements;
+        search.events = recording.events.then(Vec::new);
+        if self.conformant {
+            search.conformant_root = Some((state.clone(), todo_list.clone()));
+        }
+        match (&self.custom_strategy, self.strategy) {
+            (Some(custom), _) => {
+                let context = PlanningContext { domain: Arc::clone(&self.domain), verify_goals: self.verify_goals, strategy: self.strategy, verbose_level: self.verbose_level };
+                match custom.0.seek_plan(&context, state, todo_list, Vec::new(), 0)? {
+                    PlanningResult::Success(plan) => {
+                        search.record_plan(Solution { plan, choices: Vec::new(), refinements: Vec::new(), untried: Vec::new() });
+                    }
+                    PlanningResult::Failure => {}
+                    PlanningResult::Continue { .. } => {
+                        return Err(GTRustHopError::generic("Custom strategy returned Continue instead of a result"));
+                    }
+                }
+            }
+            (None, PlanningStrategy::Iterative) => self.find_plan_iterative(state, todo_list, &mut search)?,
+            (None, PlanningStrategy::Recursive | PlanningStrategy::Auto) => {
+                let root = SearchNode { state: Arc::new(state), todo_list, plan: PlanPrefix::default(), depth: 0, history: MethodHistory::default(), maintained: Arc::default(), choices: ChoicePrefix::default(), refinements: RefinementPrefix::default(), untried: UntriedPrefix::default() };
+                self.find_plan_recursive(root, &mut search)?;
+            }
+        }
+
+        if self.verbose_level >= 1 {
+            match search.plans.first() {
+                Some(plan) => println!("FP> result = {:?}", plan),
+                None => println!("FP> result = None"),
+            }
+        }
+
+        if search.plans.is_empty() {
+            if let Some(mismatch) = search.replay_mismatch.take() {
+                return Err(mismatch);
+            }
+        }
+
+        let mut plans = core::mem::take(&mut search.plans);
+        if let Some(postprocessor) = &self.postprocessor {
+            plans = plans.into_iter().map(|plan| (postprocessor.0)(plan)).collect();
         }
+        let choices = core::mem::take(&mut search.choices);
+        let refinements = core::mem::take(&mut search.refinements);
+        let dead_end = search.dead_end.take();
+        let longest_prefix = search.longest_prefix.to_vec();
+        let events = search.events.take().unwrap_or_default();
+        Ok(SearchResults { plans, choices, refinements, dead_end, longest_prefix, events, stats: search.finish() })
+    }
+
+    /// Find a complete plan but return only its first `horizon` actions
+    ///
+    /// Useful for receding-horizon control: the search still requires a full
+    /// solution, so the returned prefix never leads into a dead end.
+    pub fn find_plan_horizon(&self, state: State, todo_list: Vec<PlanItem>, horizon: usize) -> Result<Option<Plan>> {
+        Ok(self.find_plan(state, todo_list)?.map(|mut plan| {
+            plan.truncate(horizon);
+            plan
+        }))
+    }
+
+    /// Complete a plan whose first actions have already been committed to
+    ///
+    /// The `committed` actions are applied to `state` in order, then the todo list
+    /// is planned from the resulting state. On success the returned plan is the
+    /// committed actions followed by the planned suffix.
+    ///
+    /// Returns `GTRustHopError::ActionNotApplicable` if a committed action can't be
+    /// applied, and `GTRustHopError::InvalidItemType` if a committed item is not an
+    /// action.
+    pub fn complete_plan(&self, state: State, committed: &[PlanItem], todo_list: Vec<PlanItem>) -> Result<Option<Plan>> {
+        let current = self.apply_plan(state, committed).map_err(|err| match err {
+            GTRustHopError::PlanExecutionFailed { index } => GTRustHopError::action_not_applicable(committed[index].to_string()),
+            other => other,
+        })?;
+
+        Ok(self.find_plan(current, todo_list)?.map(|suffix| {
+            let mut plan = committed.to_vec();
+            plan.extend(suffix);
+            plan
+        }))
+    }
+
+    /// Achieve a sequence of multigoals one stage at a time
+    ///
+    /// Each stage is planned from the state reached by the plans of the previous
+    /// stages, and the stage plans are concatenated. Returns `Ok(None)` as soon as
+    /// a stage has no plan.
+    pub fn find_plan_staged(&self, state: State, stages: Vec<Multigoal>) -> Result<Option<Plan>> {
+        let mut current = state;
+        let mut plan = Vec::new();
+        for stage in stages {
+            let Some(stage_plan) = self.find_plan(current.copy(None), vec![PlanItem::multigoal(stage)])? else {
+                return Ok(None);
+            };
+            current = self.apply_plan(current, &stage_plan)?;
+            plan.extend(stage_plan);
+        }
+        Ok(Some(plan))
+    }
+
+    /// Find a plan for a todo list built from the initial state
+    ///
+    /// `todo_fn` is called once, just before planning, which suits goals that
+    /// depend on the state, e.g. bringing every misplaced block home.
+    pub fn find_plan_with(&self, state: State, todo_fn: impl FnOnce(&State) -> Vec<PlanItem>) -> Result<Option<Plan>> {
+        let todo_list = todo_fn(&state);
+        self.find_plan(state, todo_list)
+    }
+
+    /// Get the states reachable from `state` in at most `horizon` actions
+    ///
+    /// Explores breadth first, applying every applicable action instance that
+    /// `ground_fn` proposes for a state as `(action_name, args)` pairs. Actions are
+    /// applied as during planning, so constraints and resources also apply. States
+    /// are de-duplicated by fingerprint and returned in the order they were reached,
+    /// starting with `state`.
+    pub fn reachable_states(
+        &self,
+        state: State,
+        horizon: usize,
+        ground_fn: impl Fn(&State) -> Vec<(String, Vec<StateValue>)>,
+    ) -> Result<Vec<State>> {
+        let mut seen: HashSet<String> = HashSet::from([state.fingerprint()]);
+        let mut reached = vec![state];
+        let mut frontier = 0..1;
+        for _ in 0..horizon {
+            let end = reached.len();
+            for index in frontier {
+                for (action_name, args) in ground_fn(&reached[index]) {
+                    if let Some(next) = self.apply_action(&reached[index], &action_name, &args, &[])? {
+                        if seen.insert(next.fingerprint()) {
+                            reached.push(next);
+                        }
+                    }
+                }
+            }
+            frontier = end..reached.len();
+        }
+        Ok(reached)
     }
 
     /// Pyhop compatibility function
@@ -52,7 +867,7 @@ impl Planner {
     /// # let todo_list: Vec<PlanItem> = vec![];
     /// # let planner = PlannerBuilder::new().with_domain(domain).build().unwrap();
     /// // This is the old Pyhop-style call
-    /// let plan = planner.pyhop(state, todo_list)?;
+    /// let plan = planner.pyhop(state.clone(), todo_list.clone())?;
     ///
     /// // Preferred modern call
     /// let plan = planner.find_plan(state, todo_list)?;
@@ -68,205 +883,947 @@ impl Planner {
     }
     
     /// Iterative planning implementation
-    fn find_plan_iterative(&self, initial_state: State, initial_todo: Vec<PlanItem>) -> Result<Option<Plan>> {
-        use std::collections::VecDeque;
-        
-        #[derive(Debug)]
-        struct PlanningFrame {
-            state: State,
-            todo_list: Vec<PlanItem>,
-            plan: Plan,
-            depth: usize,
-        }
-        
-        let mut stack = VecDeque::new();
-        stack.push_back(PlanningFrame {
-            state: initial_state,
+    fn find_plan_iterative(&self, initial_state: State, initial_todo: Vec<PlanItem>, search: &mut SearchContext) -> Result<()> {
+        self.search_iterative(SearchNode {
+            state: Arc::new(initial_state),
             todo_list: initial_todo,
-            plan: Vec::new(),
+            plan: PlanPrefix::default(),
             depth: 0,
-        });
-        
-        while let Some(frame) = stack.pop_back() {
-            if self.verbose_level >= 2 {
-                println!("FP> depth {}, todo_list = {:?}", frame.depth, frame.todo_list);
-            }
-            
-            if frame.todo_list.is_empty() {
-                if self.verbose_level >= 1 {
-                    println!("FP> result = {:?}", frame.plan);
-                }
-                return Ok(Some(frame.plan));
-            }
-            
-            let current_item = &frame.todo_list[0];
-            let remaining_todo = frame.todo_list[1..].to_vec();
-            
-            match current_item {
-                PlanItem::Action(action_name, args) => {
-                    if let Some(action_fn) = self.domain.get_action(action_name) {
-                        let mut state_copy = frame.state.copy(None);
-                        if let Some(new_state) = action_fn(&mut state_copy, args) {
-                            let mut new_plan = frame.plan.clone();
-                            new_plan.push(current_item.clone());
-                            
-                            stack.push_back(PlanningFrame {
-                                state: new_state,
-                                todo_list: remaining_todo,
-                                plan: new_plan,
-                                depth: frame.depth + 1,
-                            });
-                        }
-                    }
-                }
-                PlanItem::Task(task_name, args) => {
-                    if let Some(methods) = self.domain.get_task_methods(task_name) {
-                        for method in methods.iter().rev() {
-                            if let Some(subtasks) = method(&frame.state, args) {
-                                let mut new_todo = subtasks;
-                                new_todo.extend(remaining_todo.clone());
-                                
-                                stack.push_back(PlanningFrame {
-                                    state: frame.state.copy(None),
-                                    todo_list: new_todo,
-                                    plan: frame.plan.clone(),
-                                    depth: frame.depth + 1,
-                                });
-                            }
-                        }
+            history: MethodHistory::default(),
+            maintained: Arc::default(),
+            choices: ChoicePrefix::default(),
+            refinements: RefinementPrefix::default(),
+            untried: UntriedPrefix::default(),
+        }, search)?;
+        Ok(())
+    }
+
+    /// Explore the subtree below a node with an explicit stack
+    ///
+    /// Returns `true` once enough plans have been found.
+    fn search_iterative(&self, root: SearchNode, search: &mut SearchContext) -> Result<bool> {
+        let mut stack = vec![Frame::Expand(root)];
+
+        while let Some(frame) = stack.pop() {
+            let node = match frame {
+                Frame::Expand(node) => node,
+                Frame::Exhausted(key, marker) => {
+                    if search.branch_marker() == marker {
+                        search.record_failure(key);
                     }
+                    continue;
                 }
-                PlanItem::Unigoal(var_name, arg, value) => {
-                    if frame.state.satisfies_unigoal(var_name, arg, value) {
-                        stack.push_back(PlanningFrame {
-                            state: frame.state,
-                            todo_list: remaining_todo,
-                            plan: frame.plan,
-                            depth: frame.depth,
-                        });
-                    } else if let Some(methods) = self.domain.get_unigoal_methods(var_name) {
-                        for method in methods.iter().rev() {
-                            if let Some(subtasks) = method(&frame.state, arg, value) {
-                                let mut new_todo = subtasks;
-                                new_todo.extend(remaining_todo.clone());
-                                
-                                stack.push_back(PlanningFrame {
-                                    state: frame.state.copy(None),
-                                    todo_list: new_todo,
-                                    plan: frame.plan.clone(),
-                                    depth: frame.depth + 1,
-                                });
-                            }
-                        }
+            };
+
+            let key = search.failure_key(&node);
+            if key.as_deref().is_some_and(|key| search.is_known_failure(key)) {
+                continue;
+            }
+
+            match self.expand_node(node, search)? {
+                Expansion::Solved(solution) => {
+                    if search.record_plan(solution) {
+                        return Ok(true);
                     }
                 }
-                PlanItem::Multigoal(multigoal) => {
-                    if multigoal.is_satisfied_by(&frame.state) {
-                        stack.push_back(PlanningFrame {
-                            state: frame.state,
-                            todo_list: remaining_todo,
-                            plan: frame.plan,
-                            depth: frame.depth,
-                        });
-                    } else {
-                        // Convert multigoal to individual unigoals
-                        let mut new_todo = Vec::new();
-                        for (var_name, arg, value) in multigoal.to_unigoals() {
-                            new_todo.push(PlanItem::unigoal(var_name, arg, value));
-                        }
-                        new_todo.extend(remaining_todo);
-
-                        stack.push_back(PlanningFrame {
-                            state: frame.state,
-                            todo_list: new_todo,
-                            plan: frame.plan,
-                            depth: frame.depth,
-                        });
+                Expansion::Successors(successors) => {
+                    // Popped only once every successor below it has been explored
+                    if let Some(key) = key {
+                        stack.push(Frame::Exhausted(key, search.branch_marker()));
                     }
+                    // Push in reverse so the first successor is explored first
+                    stack.extend(successors.into_iter().rev().map(Frame::Expand));
                 }
             }
         }
-        
-        if self.verbose_level >= 1 {
-            println!("FP> result = None");
-        }
-        Ok(None)
+
+        Ok(false)
     }
-    
+
     /// Recursive planning implementation
-    fn find_plan_recursive(&self, state: State, todo_list: Vec<PlanItem>, depth: usize) -> Result<Option<Plan>> {
-        if self.verbose_level >= 2 {
-            println!("FP> depth {}, todo_list = {:?}", depth, todo_list);
+    ///
+    /// Returns `true` once enough plans have been found to stop the search.
+    fn find_plan_recursive(&self, node: SearchNode, search: &mut SearchContext) -> Result<bool> {
+        let limit = match self.strategy {
+            PlanningStrategy::Auto => Some(self.recursion_limit.unwrap_or(DEFAULT_RECURSION_LIMIT)),
+            _ => self.recursion_limit,
+        };
+        if let Some(limit) = limit.filter(|limit| node.depth > *limit) {
+            return match self.strategy {
+                PlanningStrategy::Auto => self.search_iterative(node, search),
+                _ => Err(GTRustHopError::recursion_limit(limit)),
+            };
         }
-        
-        if todo_list.is_empty() {
-            return Ok(Some(Vec::new()));
+
+        let key = search.failure_key(&node);
+        if key.as_deref().is_some_and(|key| search.is_known_failure(key)) {
+            return Ok(false);
         }
-        
-        let current_item = &todo_list[0];
-        let remaining_todo = todo_list[1..].to_vec();
-        
-        match current_item {
-            PlanItem::Action(action_name, args) => {
-                if let Some(action_fn) = self.domain.get_action(action_name) {
-                    let mut state_copy = state.copy(None);
-                    if let Some(new_state) = action_fn(&mut state_copy, args) {
-                        if let Some(mut plan) = self.find_plan_recursive(new_state, remaining_todo, depth + 1)? {
-                            plan.insert(0, current_item.clone());
-                            return Ok(Some(plan));
-                        }
+
+        match self.expand_node(node, search)? {
+            Expansion::Solved(solution) => Ok(search.record_plan(solution)),
+            Expansion::Successors(successors) => {
+                let marker = search.branch_marker();
+                for successor in successors {
+                    if self.find_plan_recursive(successor, search)? {
+                        return Ok(true);
                     }
                 }
-            }
+                if let Some(key) = key {
+                    if search.branch_marker() == marker {
+                        search.record_failure(key);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    /// Expand a single search node into its successors
+    ///
+    /// Both planning strategies share this step, so they explore the same
+    /// nodes in the same order and report identical statistics.
+    fn expand_node(&self, node: SearchNode, search: &mut SearchContext) -> Result<Expansion> {
+        if self.cancel_token.as_ref().is_some_and(|token| token.load(Ordering::Relaxed)) {
+            return Err(GTRustHopError::cancelled());
+        }
+        search.record_expansion(node.depth);
+
+        if self.verbose_level >= 2 {
+            println!("FP> depth {}, todo_list = {:?}", node.depth, node.todo_list);
+        }
+
+        let SearchNode { state, mut todo_list, plan, depth, history, maintained, choices, refinements, untried } = node;
+        search.record_event(SearchEventKind::Pop, depth, || describe_todo(&todo_list));
+        if plan.len > search.longest_prefix.len {
+            search.longest_prefix = plan.clone();
+        }
+
+        // In conformant mode, a plan after which the predicate doesn't hold for every
+        // action outcome goes on with the todo list
+        if let Some(predicate) = self.goal_predicate.as_ref().filter(|predicate| (predicate.0)(&state)) {
+            if !self.rejects_nonconformant(&search.conformant_root, &plan, |_, state| (predicate.0)(state))? {
+                if self.verbose_level >= 2 {
+                    println!("FP> goal predicate holds, ignoring the rest of the todo list");
+                }
+                search.record_event(SearchEventKind::Success, depth, || "goal predicate holds".to_string());
+                return Ok(Expansion::Solved(Solution {
+                    plan: plan.to_vec(),
+                    choices: choices.to_vec(),
+                    refinements: refinements.to_vec(),
+                    untried: untried.to_vec(),
+                }));
+            }
+        }
+
+        if todo_list.is_empty() {
+            if self.rejects_nonconformant(&search.conformant_root, &plan, |root_todo, state| unmet_goals(root_todo, state).is_empty())? {
+                search.record_event(SearchEventKind::Backtrack, depth, || "plan fails for some action outcome".to_string());
+                return Ok(Expansion::Successors(Vec::new()));
+            }
+            let plan = plan.to_vec();
+            search.record_event(SearchEventKind::Success, depth, || format!("plan of {} actions", plan.len()));
+            return Ok(Expansion::Solved(Solution {
+                plan,
+                choices: choices.to_vec(),
+                refinements: refinements.to_vec(),
+                untried: untried.to_vec(),
+            }));
+        }
+
+        let current_item = todo_list.remove(0);
+        let remaining_todo = todo_list;
+        let (remaining_len, plan_len, choices_len) = (remaining_todo.len(), plan.len, choices.len);
+        let node_state = Arc::clone(&state);
+        let mut successors = Vec::new();
+
+        match &current_item {
+            PlanItem::Action(action_name, args) => {
+                if let Some(new_state) = self.apply_action(&state, action_name, args, &maintained)? {
+                    search.record_event(SearchEventKind::ApplyAction, depth, || item_to_string(&current_item));
+                    successors.push(SearchNode {
+                        state: Arc::new(new_state),
+                        todo_list: remaining_todo,
+                        plan: plan.push(current_item.clone()),
+                        depth: depth + 1,
+                        history,
+                        maintained,
+                        choices,
+                        refinements,
+                        untried,
+                    });
+                }
+            }
+            PlanItem::Task(task_name, args) if Domain::is_builtin_task(task_name) => {
+                // Verification is checked directly: a failure is reported as an error
+                // rather than treated as an inapplicable method to backtrack from
+                self.check_verification(&state, task_name, args)?;
+
+                successors.push(SearchNode {
+                    state,
+                    todo_list: remaining_todo,
+                    plan,
+                    depth,
+                    history: history.clone(),
+                    maintained: maintained.clone(),
+                    choices: choices.clone(),
+                    refinements: refinements.clone(),
+                    untried: untried.clone(),
+                });
+            }
+            PlanItem::Task(task_name, args) if task_name == ACHIEVE_MULTIGOAL_TASK => {
+                let id = args.first().and_then(|v| v.as_str()).unwrap_or_default();
+                let Some(multigoal) = self.multigoals.get(id) else {
+                    return Err(GTRustHopError::unknown_multigoal(id));
+                };
+                let mut new_todo = vec![PlanItem::multigoal(multigoal.clone())];
+                new_todo.extend(remaining_todo);
+
+                successors.push(SearchNode {
+                    state,
+                    todo_list: new_todo,
+                    plan,
+                    depth,
+                    history,
+                    maintained,
+                    choices,
+                    refinements,
+                    untried,
+                });
+            }
             PlanItem::Task(task_name, args) => {
+                if let Some(ids) = self.domain.multigoal_task_ids(task_name) {
+                    let id = args.first().and_then(|v| v.as_str()).unwrap_or_default();
+                    if !ids.contains(id) {
+                        return Err(GTRustHopError::unknown_multigoal(id));
+                    }
+                }
+
+                if let Some(expected) = self.domain.task_arity(task_name).filter(|expected| *expected != args.len()) {
+                    return Err(GTRustHopError::task_arity_mismatch(task_name, expected, args.len()));
+                }
+
                 if let Some(methods) = self.domain.get_task_methods(task_name) {
-                    for method in methods {
-                        if let Some(subtasks) = method(&state, args) {
+                    // A heuristic that panics under the Skip policy turns detection off for this node
+                    let thrashing = match self.thrashing_limit.zip(self.heuristic.as_ref()) {
+                        Some((limit, heuristic)) => self.call_guarded("heuristic", || Some(heuristic.distance(&state)))?
+                            .map(|distance| (limit, distance)),
+                        None => None,
+                    };
+                    // Results of leading actions, all applied to this node's state
+                    let mut action_cache: HashMap<(String, String), Option<Arc<State>>> = HashMap::new();
+
+                    let forced = search.forced_choice(choices.len, task_name);
+                    let before = successors.len();
+                    // Indices of the methods starting with a preferred action
+                    let mut preferred = Vec::new();
+                    for (index, method) in methods.iter().enumerate() {
+                        if !forced.allows(index) {
+                            continue;
+                        }
+                        if let Some(subtasks) = self.try_method(search, depth, &format!("{task_name}[{index}]"), || method(&state, args))? {
+                            if self.starts_with_preferred_action(&subtasks) {
+                                preferred.push(index);
+                            }
+                            let child_history = match thrashing {
+                                Some((limit, distance)) => {
+                                    let (child_history, repeats) = history.record(task_name, index, distance);
+                                    if repeats > limit {
+                                        search.stats.thrashing_cuts += 1;
+                                        if self.verbose_level >= 2 {
+                                            println!("FP> possible thrashing: method {index} of task '{task_name}' applied {repeats} times without progress");
+                                        }
+                                        continue;
+                                    }
+                                    child_history
+                                }
+                                None => history.clone(),
+                            };
+
                             let mut new_todo = subtasks;
-                            new_todo.extend(remaining_todo.clone());
-                            
-                            if let Some(plan) = self.find_plan_recursive(state.copy(None), new_todo, depth + 1)? {
-                                return Ok(Some(plan));
+                            new_todo.extend(remaining_todo.iter().cloned());
+
+                            if let (true, Some(PlanItem::Action(action_name, action_args))) = (self.action_cache, new_todo.first()) {
+                                let key = (action_name.clone(), serde_json::to_string(action_args).unwrap_or_default());
+                                let new_state = match action_cache.get(&key) {
+                                    Some(cached) => {
+                                        search.stats.action_cache_hits += 1;
+                                        cached.clone()
+                                    }
+                                    None => {
+                                        let result = self.apply_action(&state, action_name, action_args, &maintained)?.map(Arc::new);
+                                        action_cache.insert(key, result.clone());
+                                        result
+                                    }
+                                };
+                                // An inapplicable leading action makes the method fail right away
+                                if let Some(new_state) = new_state {
+                                    let action = new_todo.remove(0);
+                                    search.record_event(SearchEventKind::ApplyAction, depth + 1, || item_to_string(&action));
+                                    successors.push(SearchNode {
+                                        state: new_state,
+                                        todo_list: new_todo,
+                                        plan: plan.push(action),
+                                        depth: depth + 2,
+                                        history: child_history,
+                                        maintained: maintained.clone(),
+                                        choices: choices.push((task_name.clone(), index)),
+                                        refinements: refinements.clone(),
+                                        untried: untried.clone(),
+                                    });
+                                }
+                                continue;
                             }
+
+                            successors.push(SearchNode {
+                                state: Arc::clone(&state),
+                                todo_list: new_todo,
+                                plan: plan.clone(),
+                                depth: depth + 1,
+                                history: child_history,
+                                maintained: maintained.clone(),
+                                choices: choices.push((task_name.clone(), index)),
+                                refinements: refinements.clone(),
+                                untried: untried.clone(),
+                            });
                         }
                     }
+                    search.check_forced_choice(forced, task_name, successors.len() > before);
+                    if !preferred.is_empty() {
+                        successors[before..].sort_by_key(|successor| {
+                            successor.choices.last.as_ref().is_none_or(|link| !preferred.contains(&link.item.1))
+                        });
+                    }
                 }
             }
             PlanItem::Unigoal(var_name, arg, value) => {
                 if state.satisfies_unigoal(var_name, arg, value) {
-                    return self.find_plan_recursive(state, remaining_todo, depth);
+                    successors.push(SearchNode {
+                        state,
+                        todo_list: remaining_todo,
+                        plan,
+                        depth,
+                        history,
+                        maintained,
+                        choices,
+                        refinements,
+                        untried,
+                    });
                 } else if let Some(methods) = self.domain.get_unigoal_methods(var_name) {
-                    for method in methods {
-                        if let Some(subtasks) = method(&state, arg, value) {
+                    let forced = search.forced_choice(choices.len, var_name);
+                    let before = successors.len();
+                    for (index, method) in methods.iter().enumerate() {
+                        if !forced.allows(index) {
+                            continue;
+                        }
+                        if let Some(subtasks) = self.try_method(search, depth, &format!("{var_name}[{index}]"), || method(&state, arg, value))? {
                             let mut new_todo = subtasks;
-                            new_todo.extend(remaining_todo.clone());
-                            
-                            if let Some(plan) = self.find_plan_recursive(state.copy(None), new_todo, depth + 1)? {
-                                return Ok(Some(plan));
+                            if self.verify_goals {
+                                new_todo.push(PlanItem::task("_verify_g", vec![
+                                    string_value(format!("{var_name}[{index}]")),
+                                    string_value(var_name),
+                                    string_value(arg),
+                                    value.clone(),
+                                    depth.into(),
+                                ]));
                             }
+                            new_todo.extend(remaining_todo.iter().cloned());
+
+                            successors.push(SearchNode {
+                                state: Arc::clone(&state),
+                                todo_list: new_todo,
+                                plan: plan.clone(),
+                                depth: depth + 1,
+                                history: history.clone(),
+                                maintained: maintained.clone(),
+                                choices: choices.push((var_name.clone(), index)),
+                                refinements: refinements.clone(),
+                                untried: untried.clone(),
+                            });
+                        }
+                    }
+                    search.check_forced_choice(forced, var_name, successors.len() > before);
+                }
+            }
+            PlanItem::OneOf(alternatives) => {
+                // Each alternative is a separate branch; the first that leads to a plan wins
+                for alternative in alternatives {
+                    let mut new_todo = vec![alternative.clone()];
+                    new_todo.extend(remaining_todo.iter().cloned());
+
+                    successors.push(SearchNode {
+                        state: Arc::clone(&state),
+                        todo_list: new_todo,
+                        plan: plan.clone(),
+                        depth,
+                        history: history.clone(),
+                        maintained: maintained.clone(),
+                        choices: choices.clone(),
+                        refinements: refinements.clone(),
+                        untried: untried.clone(),
+                    });
+                }
+            }
+            // An empty block has nothing to order and is skipped
+            PlanItem::Unordered(items) if items.is_empty() => {
+                successors.push(SearchNode {
+                    state,
+                    todo_list: remaining_todo,
+                    plan,
+                    depth,
+                    history,
+                    maintained,
+                    choices,
+                    refinements,
+                    untried,
+                });
+            }
+            PlanItem::Unordered(items) => {
+                if items.len() > MAX_UNORDERED_ITEMS {
+                    return Err(GTRustHopError::planning_failed(format!(
+                        "unordered block has {} items, more than the limit of {MAX_UNORDERED_ITEMS}",
+                        items.len()
+                    )));
+                }
+
+                // Branch on which item goes first; the rest stays unordered, so
+                // every permutation is reachable without enumerating them upfront.
+                // Items another item is preferred before are tried last.
+                let mut order: Vec<usize> = (0..items.len()).collect();
+                if !self.order_preferences.is_empty() {
+                    order.sort_by_key(|index| {
+                        items.iter().any(|other| self.order_preferences.iter()
+                            .any(|(before, after)| before == other.name() && after == items[*index].name()))
+                    });
+                }
+                let mut tried: Vec<&PlanItem> = Vec::with_capacity(items.len());
+                for index in order {
+                    let item = &items[index];
+                    if tried.contains(&item) {
+                        continue; // Same item already tried first
+                    }
+                    tried.push(item);
+
+                    let mut rest = items.clone();
+                    rest.remove(index);
+
+                    let mut new_todo = vec![item.clone()];
+                    if !rest.is_empty() {
+                        new_todo.push(PlanItem::Unordered(rest));
+                    }
+                    new_todo.extend(remaining_todo.iter().cloned());
+
+                    successors.push(SearchNode {
+                        state: Arc::clone(&state),
+                        todo_list: new_todo,
+                        plan: plan.clone(),
+                        depth,
+                        history: history.clone(),
+                        maintained: maintained.clone(),
+                        choices: choices.clone(),
+                        refinements: refinements.clone(),
+                        untried: untried.clone(),
+                    });
+                }
+            }
+            PlanItem::TaskPattern(task_name, args) => {
+                // Branch on the entities of the first wildcard's type; the other
+                // wildcards are filled when the resulting pattern is expanded
+                let wildcard = args.iter().enumerate().find_map(|(index, arg)| Some((index, wildcard_type(arg)?)));
+                let candidates = match wildcard {
+                    Some((index, type_name)) => {
+                        let entities = state.get_var(TYPES_VAR, type_name)
+                            .and_then(|entities| entities.as_array())
+                            .map(Vec::as_slice)
+                            .unwrap_or_default();
+                        entities.iter()
+                            .map(|entity| {
+                                let mut args = args.clone();
+                                args[index] = entity.clone();
+                                PlanItem::TaskPattern(task_name.clone(), args)
+                            })
+                            .collect()
+                    }
+                    None => vec![PlanItem::Task(task_name.clone(), args.clone())],
+                };
+
+                for candidate in candidates {
+                    let mut new_todo = vec![candidate];
+                    new_todo.extend(remaining_todo.iter().cloned());
+
+                    successors.push(SearchNode {
+                        state: Arc::clone(&state),
+                        todo_list: new_todo,
+                        plan: plan.clone(),
+                        depth,
+                        history: history.clone(),
+                        maintained: maintained.clone(),
+                        choices: choices.clone(),
+                        refinements: refinements.clone(),
+                        untried: untried.clone(),
+                    });
+                }
+            }
+            PlanItem::RelGoal { rel, args, value } => {
+                if state.satisfies_relgoal(rel, args, value) {
+                    successors.push(SearchNode {
+                        state,
+                        todo_list: remaining_todo,
+                        plan,
+                        depth,
+                        history,
+                        maintained,
+                        choices,
+                        refinements,
+                        untried,
+                    });
+                } else if let Some(methods) = self.domain.get_relgoal_methods(rel) {
+                    let forced = search.forced_choice(choices.len, rel);
+                    let before = successors.len();
+                    for (index, method) in methods.iter().enumerate() {
+                        if !forced.allows(index) {
+                            continue;
+                        }
+                        if let Some(subtasks) = self.try_method(search, depth, &format!("{rel}[{index}]"), || method(&state, args, value))? {
+                            let mut new_todo = subtasks;
+                            new_todo.extend(remaining_todo.iter().cloned());
+
+                            successors.push(SearchNode {
+                                state: Arc::clone(&state),
+                                todo_list: new_todo,
+                                plan: plan.clone(),
+                                depth: depth + 1,
+                                history: history.clone(),
+                                maintained: maintained.clone(),
+                                choices: choices.push((rel.clone(), index)),
+                                refinements: refinements.clone(),
+                                untried: untried.clone(),
+                            });
                         }
                     }
+                    search.check_forced_choice(forced, rel, successors.len() > before);
+                }
+            }
+            PlanItem::Maintain(goal) => {
+                let Some(holds) = goal.is_satisfied_by(&state) else {
+                    return Err(GTRustHopError::invalid_item_type(goal.to_string(), depth));
+                };
+                // A goal can only be maintained from a state that already satisfies it
+                if holds {
+                    let mut goals = (*maintained).clone();
+                    goals.push((**goal).clone());
+                    successors.push(SearchNode {
+                        state,
+                        todo_list: remaining_todo,
+                        plan,
+                        depth,
+                        history,
+                        maintained: Arc::new(goals),
+                        choices,
+                        refinements,
+                        untried,
+                    });
                 }
             }
             PlanItem::Multigoal(multigoal) => {
+                // Domain multigoal methods replace the default decomposition, but not a custom decomposer
+                let methods = match multigoal.has_decomposer() {
+                    true => Vec::new(),
+                    false => self.domain.relevant_multigoal_methods(multigoal),
+                };
                 if multigoal.is_satisfied_by(&state) {
-                    return self.find_plan_recursive(state, remaining_todo, depth);
-                } else {
-                    // Convert multigoal to individual unigoals
-                    let mut new_todo = Vec::new();
-                    for (var_name, arg, value) in multigoal.to_unigoals() {
-                        new_todo.push(PlanItem::unigoal(var_name, arg, value));
+                    successors.push(SearchNode {
+                        state,
+                        todo_list: remaining_todo,
+                        plan,
+                        depth,
+                        history,
+                        maintained,
+                        choices,
+                        refinements,
+                        untried,
+                    });
+                } else if !methods.is_empty() {
+                    let forced = search.forced_choice(choices.len, &multigoal.name);
+                    let before = successors.len();
+                    for (index, method) in methods.iter().enumerate() {
+                        if !forced.allows(index) {
+                            continue;
+                        }
+                        if let Some(subgoals) = self.try_method(search, depth, &format!("{}[{index}]", multigoal.name), || method(&state, multigoal))? {
+                            let mut new_todo = subgoals;
+                            if self.verify_goals {
+                                new_todo.push(create_multigoal_verification_task(&format!("{}[{index}]", multigoal.name), multigoal, depth)?);
+                            }
+                            new_todo.extend(remaining_todo.iter().cloned());
+
+                            successors.push(SearchNode {
+                                state: Arc::clone(&state),
+                                todo_list: new_todo,
+                                plan: plan.clone(),
+                                depth: depth + 1,
+                                history: history.clone(),
+                                maintained: maintained.clone(),
+                                choices: choices.push((multigoal.name.clone(), index)),
+                                refinements: refinements.clone(),
+                                untried: untried.clone(),
+                            });
+                        }
+                    }
+                    search.check_forced_choice(forced, &multigoal.name, successors.len() > before);
+                } else if let Some(mut new_todo) = self.call_guarded(&multigoal.name, || Some(multigoal.decompose(&state)))? {
+                    // Decompose the multigoal (into individual unigoals by default); a skipped panic leaves no successor
+                    // Custom decomposers are user code, so check that they achieve the multigoal
+                    if self.verify_goals && multigoal.has_decomposer() {
+                        new_todo.push(create_multigoal_verification_task(&format!("{} decomposer", multigoal.name), multigoal, depth)?);
                     }
                     new_todo.extend(remaining_todo);
 
-                    return self.find_plan_recursive(state, new_todo, depth);
+                    successors.push(SearchNode {
+                        state,
+                        todo_list: new_todo,
+                        plan,
+                        depth,
+                        history,
+                        maintained,
+                        choices,
+                        refinements,
+                        untried,
+                    });
                 }
             }
         }
-        
-        Ok(None)
+
+        if successors.is_empty() {
+            search.record_event(SearchEventKind::Backtrack, depth, || format!("no way to refine {}", item_to_string(&current_item)));
+            search.record_dead_end(depth, &current_item, node_state);
+        }
+        for successor in &successors {
+            search.record_event(SearchEventKind::Push, successor.depth, || describe_todo(&successor.todo_list));
+        }
+
+        // Successors made by a method choice are in the order they will be tried
+        let made_choice = |successor: &&mut SearchNode| successor.choices.len > choices_len;
+        let alternatives = successors.iter_mut().filter(made_choice).count();
+        for (position, successor) in successors.iter_mut().filter(made_choice).enumerate() {
+            let name = successor.choices.last.as_ref().map(|link| link.item.0.clone()).unwrap_or_default();
+            successor.untried = successor.untried.push((name, alternatives - position - 1));
+        }
+
+        if search.record_refinements {
+            for successor in &mut successors {
+                successor.refinements = match &current_item {
+                    PlanItem::Action(..) => successor.refinements.push(Refinement::Action(current_item.clone())),
+                    _ => {
+                        // The action cache may have applied the first new item already
+                        let applied = successor.plan.len - plan_len;
+                        let added = successor.todo_list.len() + applied - remaining_len;
+                        let refinements = successor.refinements.push(Refinement::Task(current_item.clone(), added));
+                        match (applied, &successor.plan.last) {
+                            (1, Some(action)) => refinements.push(Refinement::Action(action.item.clone())),
+                            _ => refinements,
+                        }
+                    }
+                };
+            }
+        }
+
+        Ok(Expansion::Successors(successors))
     }
     
+    /// Check whether a plan applies for every action outcome and every final state is `achieved`
+    ///
+    /// Outcomes go through `apply_action_outcomes()`, so an outcome the search would
+    /// reject, such as one breaking a goal maintained from the root todo list, fails the plan.
+    fn is_conformant(&self, initial: &State, todo_list: &[PlanItem], plan: &[PlanItem], achieved: impl Fn(&State) -> bool) -> Result<bool> {
+        let maintained: Vec<PlanItem> = todo_list.iter()
+            .filter_map(|item| match item {
+                PlanItem::Maintain(goal) => Some((**goal).clone()),
+                _ => None,
+            })
+            .collect();
+        let mut beliefs = vec![initial.clone()];
+        for item in plan {
+            let PlanItem::Action(name, args) = item else {
+                return Ok(false);
+            };
+            let mut next = Vec::with_capacity(beliefs.len());
+            for state in &beliefs {
+                let Some(outcomes) = self.apply_action_outcomes(state, name, args, &maintained)? else {
+                    return Ok(false);
+                };
+                next.extend(outcomes);
+            }
+            beliefs = next;
+        }
+        Ok(beliefs.iter().all(achieved))
+    }
+
+    /// Check whether conformant mode rejects a plan found by the search
+    ///
+    /// `root` is the search's `conformant_root`, and `achieved` tells, given the root
+    /// todo list, whether a final state reaches the goal the plan was found for.
+    fn rejects_nonconformant(&self, root: &Option<(State, Vec<PlanItem>)>, plan: &PlanPrefix, achieved: impl Fn(&[PlanItem], &State) -> bool) -> Result<bool> {
+        let Some((initial, root_todo)) = root else {
+            return Ok(false);
+        };
+        let plan = plan.to_vec();
+        if self.is_conformant(initial, root_todo, &plan, |state| achieved(root_todo, state))? {
+            return Ok(false);
+        }
+        if self.verbose_level >= 2 {
+            println!("FP> rejected plan that fails for some action outcome: {plan:?}");
+        }
+        Ok(true)
+    }
+
+    /// Check whether a method's subtasks start with an action that has a preferred tag
+    fn starts_with_preferred_action(&self, subtasks: &[PlanItem]) -> bool {
+        match subtasks.first() {
+            Some(PlanItem::Action(name, _)) => {
+                self.domain.action_tags(name).iter().any(|tag| self.preferred_tags.contains(tag))
+            }
+            _ => false,
+        }
+    }
+
+    /// Call a method, recording the attempt and its outcome if events are recorded
+    fn try_method<T>(&self, search: &mut SearchContext, depth: usize, name: &str, method: impl FnOnce() -> Option<T>) -> Result<Option<T>> {
+        search.record_event(SearchEventKind::TryMethod, depth, || name.to_string());
+        let result = self.call_guarded(name, method)?;
+        let kind = if result.is_some() { SearchEventKind::MethodApplicable } else { SearchEventKind::MethodRejected };
+        search.record_event(kind, depth, || name.to_string());
+        Ok(result)
+    }
+
+    /// Apply an action to a copy of `state`
+    ///
+    /// Returns `None` if the action is unknown, not applicable, exhausts a resource,
+    /// or leads to a state forbidden by a state constraint or breaking a maintained
+    /// goal. The domain's action observer sees every attempt.
+    fn apply_action(&self, state: &State, action_name: &str, args: &[StateValue], maintained: &[PlanItem]) -> Result<Option<State>> {
+        let Some(action_fn) = self.domain.get_action(action_name) else {
+            return Ok(None);
+        };
+        // A failing precondition saves copying the state
+        let new_state = if self.precondition_holds(state, action_name, args)? {
+            let mut state_copy = state.copy(None);
+            self.call_guarded(action_name, || match (self.domain.rigid_action(action_name), self.domain.action_effect(action_name)) {
+                (Some(rigid_fn), _) => rigid_fn(&mut state_copy, &self.rigid_state, args),
+                // The precondition holds, so only the effect is left to apply
+                (None, Some(effect)) => {
+                    effect(&mut state_copy, args);
+                    Some(state_copy)
+                }
+                (None, None) => action_fn(&mut state_copy, args),
+            })?
+        } else {
+            None
+        };
+        self.check_outcome(state, new_state, action_name, args, maintained)
+    }
+
+    /// Apply every outcome of an action to `state`, each checked like the result of `apply_action()`
+    ///
+    /// A deterministic action's only outcome is its `apply_action()` result. Returns
+    /// `None` if the action doesn't apply or any of its outcomes is rejected.
+    fn apply_action_outcomes(&self, state: &State, action_name: &str, args: &[StateValue], maintained: &[PlanItem]) -> Result<Option<Vec<State>>> {
+        if !self.domain.is_nondet_action(action_name) {
+            return Ok(self.apply_action(state, action_name, args, maintained)?.map(|new_state| vec![new_state]));
+        }
+        if !self.precondition_holds(state, action_name, args)? {
+            return Ok(self.check_outcome(state, None, action_name, args, maintained)?.map(|new_state| vec![new_state]));
+        }
+        let outcomes = self.call_guarded(action_name, || Some(self.domain.action_outcomes(action_name, state, args)))?.unwrap_or_default();
+        if outcomes.is_empty() {
+            return Ok(None);
+        }
+        let mut checked = Vec::with_capacity(outcomes.len());
+        for outcome in outcomes {
+            let Some(outcome) = self.check_outcome(state, Some(outcome), action_name, args, maintained)? else {
+                return Ok(None);
+            };
+            checked.push(outcome);
+        }
+        Ok(Some(checked))
+    }
+
+    /// Check an action's precondition against `state`, if it has one
+    fn precondition_holds(&self, state: &State, action_name: &str, args: &[StateValue]) -> Result<bool> {
+        match self.domain.action_precondition(action_name) {
+            Some(precond) => Ok(self.call_guarded(action_name, || Some(precond(state, args)))?.unwrap_or(false)),
+            None => Ok(true),
+        }
+    }
+
+    /// Finish applying an action to `state`, given the state the action returned
+    ///
+    /// Applies the action's resource deltas, shows the attempt to the domain's action
+    /// observer, and rejects a state that exhausts a resource, is forbidden by a state
+    /// constraint or breaks a maintained goal.
+    fn check_outcome(&self, state: &State, mut new_state: Option<State>, action_name: &str, args: &[StateValue], maintained: &[PlanItem]) -> Result<Option<State>> {
+        if let Some(resulting) = &mut new_state {
+            if !self.update_resources(state, resulting, action_name, args) {
+                new_state = None;
+            }
+        }
+        if let Some(observer) = self.domain.action_observer() {
+            observer(action_name, args, state, new_state.as_ref());
+        }
+        let Some(new_state) = new_state else {
+            return Ok(None);
+        };
+        for constraint in &self.constraints {
+            if !self.call_guarded("state constraint", || Some(constraint.allows(&new_state)))?.unwrap_or(false) {
+                return Ok(None);
+            }
+        }
+        Ok(Some(new_state).filter(|new_state| maintained.iter().all(|goal| goal.is_satisfied_by(new_state) == Some(true))))
+    }
+
+    /// Give the tracked resources missing from a state their initial level
+    fn seed_resources(&self, state: &mut State) {
+        for (resource, initial) in &self.resources {
+            if state.get_var(RESOURCES_VAR, resource).is_none() {
+                state.set_var(RESOURCES_VAR, resource, (*initial).into());
+            }
+        }
+    }
+
+    /// Apply the resource deltas of an action, computed on the state before it
+    ///
+    /// Returns `false` if a tracked resource would become negative.
+    fn update_resources(&self, before: &State, after: &mut State, action_name: &str, args: &[StateValue]) -> bool {
+        for (resource, delta_fn) in self.domain.resource_deltas(action_name) {
+            if !self.resources.iter().any(|(name, _)| name == resource) {
+                continue;
+            }
+            let level = before.get_var(RESOURCES_VAR, resource).and_then(StateValue::as_f64).unwrap_or_default();
+            let level = level + delta_fn(before, args);
+            if level < 0.0 {
+                return false;
+            }
+            after.set_var(RESOURCES_VAR, resource, level.into());
+        }
+        true
+    }
+
+    /// Apply the actions of a plan to a state, in order, as the search does
+    ///
+    /// Unlike `Domain::apply_plan()`, which it otherwise mirrors, rigid actions
+    /// get the planner's rigid state, resource deltas are applied (from the
+    /// initial levels if the state has none yet) and state constraints are checked.
+    fn apply_plan(&self, state: State, plan: &[PlanItem]) -> Result<State> {
+        self.replay_plan(state, plan, |_| {})
+    }
+
+    /// Apply the actions of a plan like `apply_plan()` and capture every intermediate state
+    fn replay_with_states(&self, initial: State, plan: &[PlanItem]) -> Result<Vec<State>> {
+        let mut states = vec![initial.clone()];
+        self.replay_plan(initial, plan, |state| states.push(state.clone()))?;
+        Ok(states)
+    }
+
+    fn replay_plan(&self, state: State, plan: &[PlanItem], mut visit: impl FnMut(&State)) -> Result<State> {
+        let mut current = state;
+        self.seed_resources(&mut current);
+        for (index, item) in plan.iter().enumerate() {
+            let PlanItem::Action(action_name, args) = item else {
+                return Err(GTRustHopError::invalid_item_type(item.to_string(), index));
+            };
+            current = self.apply_action(&current, action_name, args, &[])?
+                .ok_or_else(|| GTRustHopError::plan_execution_failed(index))?;
+            visit(&current);
+        }
+        Ok(current)
+    }
+
+    /// Check whether two actions give the same state from `state` in either order
+    fn actions_commute(&self, state: &State, first: &PlanItem, second: &PlanItem) -> bool {
+        let forward = self.apply_plan(state.copy(None), &[first.clone(), second.clone()]);
+        let backward = self.apply_plan(state.copy(None), &[second.clone(), first.clone()]);
+        match (forward, backward) {
+            (Ok(forward), Ok(backward)) => forward.fingerprint() == backward.fingerprint(),
+            _ => false,
+        }
+    }
+
+    /// Call a user closure, catching panics if panic isolation is enabled
+    ///
+    /// `name` identifies the closure in the `MethodPanicked` error.
+    #[cfg(feature = "std")]
+    fn call_guarded<T>(&self, name: &str, closure: impl FnOnce() -> Option<T>) -> Result<Option<T>> {
+        let Some(policy) = self.panic_policy else {
+            return Ok(closure());
+        };
+        let payload = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(closure)) {
+            Ok(result) => return Ok(result),
+            Err(payload) => payload,
+        };
+
+        let message = payload.downcast_ref::<&str>().map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        match policy {
+            PanicPolicy::Fail => Err(GTRustHopError::method_panicked(name, message)),
+            PanicPolicy::Skip => {
+                if self.verbose_level >= 1 {
+                    println!("FP> '{name}' panicked ({message}); treating it as not applicable");
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Call a user closure; without `std` panics can't be caught
+    #[cfg(not(feature = "std"))]
+    fn call_guarded<T>(&self, _name: &str, closure: impl FnOnce() -> Option<T>) -> Result<Option<T>> {
+        Ok(closure())
+    }
+
+    /// Check a `_verify_g` or `_verify_mg` task against the current state
+    ///
+    /// Returns `MethodVerificationFailed` or `MultigoalVerificationFailed` when the
+    /// method that injected the task did not achieve its goal.
+    fn check_verification(&self, state: &State, task_name: &str, args: &[StateValue]) -> Result<()> {
+        let method = args.first().and_then(|v| v.as_str()).unwrap_or("unknown");
+
+        if task_name == "_verify_g" {
+            let (Some(var_name), Some(arg), Some(value), Some(depth)) = (
+                args.get(1).and_then(|v| v.as_str()),
+                args.get(2).and_then(|v| v.as_str()),
+                args.get(3),
+                args.get(4).and_then(|v| v.as_u64()),
+            ) else {
+                return Err(GTRustHopError::invalid_item_type(format!("{task_name} {args:?}"), 0));
+            };
+
+            if self.verbose_level >= 3 {
+                println!("FP> verifying that method '{method}' achieved ({var_name} {arg} {value})");
+            }
+            if !state.satisfies_unigoal(var_name, arg, value) {
+                let goal = format!("({var_name} {arg} {value})");
+                return Err(GTRustHopError::method_verification_failed(method, goal, depth as usize));
+            }
+        } else {
+            let (Some(multigoal), Some(depth)) = (
+                args.get(1).and_then(|v| serde_json::from_value::<Multigoal>(v.clone()).ok()),
+                args.get(2).and_then(|v| v.as_u64()),
+            ) else {
+                return Err(GTRustHopError::invalid_item_type(format!("{task_name} {args:?}"), 0));
+            };
+
+            if self.verbose_level >= 3 {
+                println!("FP> verifying that method '{method}' achieved {multigoal}");
+            }
+            if !multigoal.is_satisfied_by(state) {
+                return Err(GTRustHopError::multigoal_verification_failed(method, multigoal.to_string(), depth as usize));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Check if verbose output should be printed at the given level
     pub fn is_verbose(&self, level: i32) -> bool {
         self.verbose_level >= level
@@ -290,11 +1847,79 @@ impl Planner {
     ///
     /// Note: whenever run_lazy_lookahead encounters an action for which there is
     /// no corresponding command definition, it uses the action definition instead.
+    ///
+    /// Multigoal goals with a deadline (see `Multigoal::set_deadline()`) are planned
+    /// for first; if one is unsatisfied after its deadline, or can no longer be
+    /// planned for, `GTRustHopError::DeadlineMissed` is returned. If `find_plan`
+    /// returns an empty plan while goals of the todo list are unmet,
+    /// `GTRustHopError::ActingIncomplete` is returned. When the todo list is made
+    /// only of goals, acting stops as soon as a command leaves them all satisfied,
+    /// even if the plan has actions left.
     pub fn run_lazy_lookahead(
         &self,
-        mut state: State,
+        state: State,
+        todo_list: Vec<PlanItem>,
+        max_tries: usize,
+    ) -> Result<State> {
+        self.run_lazy_lookahead_with_max_plan_len(state, todo_list, max_tries, None)
+    }
+
+    /// Run lazy lookahead, refusing to execute overly long plans
+    ///
+    /// Works like `run_lazy_lookahead()`, but if `max_plan_len` is set and
+    /// `find_plan` returns a plan with more actions than that, no command is
+    /// executed and `GTRustHopError::PlanTooLong` is returned. This is a safety
+    /// valve for acting loops driven by a buggy domain.
+    pub fn run_lazy_lookahead_with_max_plan_len(
+        &self,
+        state: State,
+        todo_list: Vec<PlanItem>,
+        max_tries: usize,
+        max_plan_len: Option<usize>,
+    ) -> Result<State> {
+        self.lazy_lookahead(state, todo_list, max_tries, max_plan_len, &HashSet::new(), CommandPolicy::Fallback)
+    }
+
+    /// Run lazy lookahead, tolerating command failures for some actions
+    ///
+    /// Works like `run_lazy_lookahead()`, except that when the command for an
+    /// action named in `lenient` fails, the failure is logged and the action is
+    /// skipped instead of triggering replanning. Useful for fire-and-forget
+    /// actions such as notifications.
+    pub fn run_lazy_lookahead_lenient(
+        &self,
+        state: State,
+        todo_list: Vec<PlanItem>,
+        max_tries: usize,
+        lenient: &HashSet<String>,
+    ) -> Result<State> {
+        self.lazy_lookahead(state, todo_list, max_tries, None, lenient, CommandPolicy::Fallback)
+    }
+
+    /// Run lazy lookahead, choosing what to do for actions without a command
+    ///
+    /// `run_lazy_lookahead()` uses `CommandPolicy::Fallback`. With
+    /// `CommandPolicy::Strict`, executing an action without a `c_<action>`
+    /// command fails with `GTRustHopError::MissingCommand`.
+    pub fn run_lazy_lookahead_with_command_policy(
+        &self,
+        state: State,
         todo_list: Vec<PlanItem>,
         max_tries: usize,
+        policy: CommandPolicy,
+    ) -> Result<State> {
+        self.lazy_lookahead(state, todo_list, max_tries, None, &HashSet::new(), policy)
+    }
+
+    /// Shared implementation of the lazy lookahead variants
+    fn lazy_lookahead(
+        &self,
+        mut state: State,
+        mut todo_list: Vec<PlanItem>,
+        max_tries: usize,
+        max_plan_len: Option<usize>,
+        lenient: &HashSet<String>,
+        policy: CommandPolicy,
     ) -> Result<State> {
         if self.is_verbose(1) {
             println!("RLL> run_lazy_lookahead, verbose = {}, max_tries = {}", self.verbose_level, max_tries);
@@ -302,7 +1927,21 @@ impl Planner {
             println!("RLL> To do: {:?}", todo_list);
         }
 
+        // Goals with deadlines are planned for first, earliest deadline first
+        let deadlines = deadlined_goals(&todo_list);
+        if !deadlines.is_empty() {
+            let mut prioritized: Vec<PlanItem> = deadlines.iter()
+                .map(|(var_name, arg, value, _)| PlanItem::unigoal(var_name, arg, value.clone()))
+                .collect();
+            prioritized.append(&mut todo_list);
+            todo_list = prioritized;
+        }
+
         for tries in 1..=max_tries {
+            if let Some(goal) = missed_deadline(&state, &deadlines, tries) {
+                return Err(GTRustHopError::deadline_missed(goal));
+            }
+
             if self.is_verbose(1) {
                 let ordinal = match tries {
                     1 => "st",
@@ -317,12 +1956,21 @@ impl Planner {
 
             match plan {
                 None => {
+                    // Planning can't help any more, so unsatisfied deadlined goals are missed
+                    if let Some(goal) = missed_deadline(&state, &deadlines, usize::MAX) {
+                        return Err(GTRustHopError::deadline_missed(goal));
+                    }
                     if self.is_verbose(1) {
                         return Err(crate::error::GTRustHopError::planning_failed("run_lazy_lookahead: find_plan has failed"));
                     }
                     return Ok(state);
                 }
                 Some(plan) if plan.is_empty() => {
+                    // Goal methods may wrongly claim there is nothing left to do
+                    let unmet = unmet_goals(&todo_list, &state);
+                    if !unmet.is_empty() {
+                        return Err(GTRustHopError::acting_incomplete(unmet));
+                    }
                     if self.is_verbose(1) {
                         println!("RLL> Empty plan => success after {} calls to find_plan.", tries);
                     }
@@ -331,19 +1979,24 @@ impl Planner {
                     }
                     return Ok(state);
                 }
+                Some(plan) if max_plan_len.is_some_and(|max| plan.len() > max) => {
+                    return Err(GTRustHopError::plan_too_long(plan.len()));
+                }
                 Some(plan) => {
                     // Execute the plan
                     let mut plan_failed = false;
                     for action in &plan {
                         if let PlanItem::Action(action_name, args) = action {
                             let command_name = format!("c_{}", action_name);
+                            if policy == CommandPolicy::Strict && self.domain.get_command(&command_name).is_none() {
+                                return Err(GTRustHopError::missing_command(action_name));
+                            }
 
                             // Try to find a command, fall back to action
-                            let command_fn = self.domain.get_command(&command_name)
-                                .or_else(|| self.domain.get_action(action_name));
+                            let command_fn = self.domain.get_command(&command_name);
 
-                            if let Some(cmd_fn) = command_fn {
-                                if self.domain.get_command(&command_name).is_none() && self.is_verbose(1) {
+                            if command_fn.is_some() || self.domain.get_action(action_name).is_some() {
+                                if command_fn.is_none() && self.is_verbose(1) {
                                     println!("RLL> {} not defined, using {} instead\n", command_name, action_name);
                                 }
 
@@ -351,12 +2004,27 @@ impl Planner {
                                     println!("RLL> Command: {} {:?}", command_name, args);
                                 }
 
-                                let mut state_copy = state.copy(None);
-                                if let Some(new_state) = cmd_fn(&mut state_copy, args) {
+                                // The fallback action is applied as during planning, with
+                                // the rigid state and resource deltas
+                                let executed = match command_fn {
+                                    Some(cmd_fn) => cmd_fn(&mut state.copy(None), args),
+                                    None => self.apply_action(&state, action_name, args, &[])?,
+                                };
+                                if let Some(new_state) = executed {
                                     if self.is_verbose(2) {
                                         new_state.display(None);
                                     }
                                     state = new_state;
+                                    if todo_goals_met(&todo_list, &state) {
+                                        if self.is_verbose(1) {
+                                            println!("RLL> Goals achieved after {}; stopping early.", command_name);
+                                        }
+                                        return Ok(state);
+                                    }
+                                } else if lenient.contains(action_name) {
+                                    if self.is_verbose(1) {
+                                        println!("RLL> WARNING: command {} failed; skipping lenient action.", command_name);
+                                    }
                                 } else {
                                     if self.is_verbose(1) {
                                         println!("RLL> WARNING: command {} failed; will call find_plan.", command_name);
@@ -387,10 +2055,100 @@ impl Planner {
         if self.is_verbose(2) {
             state.display(Some("RLL> final state"));
         }
+        if let Some(goal) = missed_deadline(&state, &deadlines, usize::MAX) {
+            return Err(GTRustHopError::deadline_missed(goal));
+        }
         Ok(state)
     }
 }
 
+/// Count the item insertions, deletions and substitutions turning one plan into another
+fn plan_edit_distance(from: &[PlanItem], to: &[PlanItem]) -> usize {
+    let mut previous: Vec<usize> = (0..=to.len()).collect();
+    for (i, from_item) in from.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, to_item) in to.iter().enumerate() {
+            let substitution = previous[j] + usize::from(from_item != to_item);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[to.len()]
+}
+
+/// Check whether a todo list is made only of goals that a state satisfies
+fn todo_goals_met(todo_list: &[PlanItem], state: &State) -> bool {
+    !todo_list.is_empty() && todo_list.iter().all(|item| item.is_satisfied_by(state) == Some(true))
+}
+
+/// Describe the goals of a todo list that a state does not satisfy
+fn unmet_goals(todo_list: &[PlanItem], state: &State) -> Vec<String> {
+    todo_list
+        .iter()
+        .flat_map(|item| match item {
+            PlanItem::Multigoal(multigoal) => multigoal
+                .goals()
+                .into_iter()
+                .map(|(var_name, arg, value)| PlanItem::unigoal(var_name, arg, value.clone()))
+                .collect(),
+            item => vec![item.clone()],
+        })
+        .filter(|goal| goal.is_satisfied_by(state) == Some(false))
+        .map(|goal| item_to_string(&goal))
+        .collect()
+}
+
+
+/// Describe a node's todo list by its first item, for search events
+fn describe_todo(todo_list: &[PlanItem]) -> String {
+    match todo_list.first() {
+        Some(item) if todo_list.len() > 1 => format!("{} and {} more", item_to_string(item), todo_list.len() - 1),
+        Some(item) => item_to_string(item),
+        None => "empty todo list".to_string(),
+    }
+}
+
+/// Rebuild `count` hierarchical plan nodes from refinements in the order they were made
+///
+/// Each refinement of a task is followed by the refinements of the items it was
+/// replaced by, so the nodes are read back depth first. Verification tasks are dropped.
+fn build_nodes(refinements: &mut impl Iterator<Item = Refinement>, count: usize) -> Vec<HPlanNode> {
+    let mut nodes = Vec::with_capacity(count);
+    for _ in 0..count {
+        match refinements.next() {
+            Some(Refinement::Action(item)) => nodes.push(HPlanNode::Action { name: item.name().to_string(), args: item.args() }),
+            Some(Refinement::Task(item, added)) => {
+                let children = build_nodes(refinements, added);
+                if !matches!(&item, PlanItem::Task(name, _) if Domain::is_builtin_task(name)) {
+                    nodes.push(HPlanNode::Task { name: item.name().to_string(), args: item.args(), children });
+                }
+            }
+            None => break,
+        }
+    }
+    nodes
+}
+
+/// Collect the deadlined goals of all multigoals in a todo list, earliest deadline first
+fn deadlined_goals(todo_list: &[PlanItem]) -> Vec<(String, String, StateValue, usize)> {
+    let mut goals: Vec<_> = todo_list.iter()
+        .filter_map(|item| match item {
+            PlanItem::Multigoal(multigoal) => Some(multigoal.deadlined_goals()),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    goals.sort_by_key(|(_, _, _, deadline)| *deadline);
+    goals
+}
+
+/// Describe the first goal with a deadline before `iteration` that `state` doesn't satisfy
+fn missed_deadline(state: &State, goals: &[(String, String, StateValue, usize)], iteration: usize) -> Option<String> {
+    goals.iter()
+        .find(|(var_name, arg, value, deadline)| *deadline < iteration && !state.satisfies_unigoal(var_name, arg, value))
+        .map(|(var_name, arg, _, _)| format!("{var_name}[{arg}]"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -577,4 +2335,1907 @@ mod tests {
         assert_eq!(final_state.get_var("loc", "obj1").unwrap().as_str(), Some("loc2"));
         Ok(())
     }
+
+    /// Domain whose single task method expands into a chain of four actions
+    fn create_chain_domain() -> Result<Domain> {
+        let mut domain = Domain::new("chain_domain");
+        domain.declare_action("step", |state: &mut State, _args: &[crate::core::StateValue]| {
+            Some(state.clone())
+        })?;
+        domain.declare_task_method("chain", |_state: &State, _args: &[crate::core::StateValue]| {
+            Some((0..4).map(|_| PlanItem::action("step", vec![])).collect())
+        })?;
+        Ok(domain)
+    }
+
+    #[test]
+    fn test_progress_callback_invoked_for_both_strategies() -> Result<()> {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        for strategy in [PlanningStrategy::Iterative, PlanningStrategy::Recursive] {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let counter = Arc::clone(&calls);
+
+            let planner = PlannerBuilder::new()
+                .with_domain(create_chain_domain()?)
+                .with_strategy(strategy)
+                .with_progress_callback(2, Arc::new(move |stats| {
+                    assert!(stats.nodes_expanded.is_multiple_of(2));
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }))
+                .build()?;
+
+            let plan = planner.find_plan(State::new("s"), vec![PlanItem::task("chain", vec![])])?;
+            assert_eq!(plan.map(|p| p.len()), Some(4));
+
+            // Root task, four actions and the final empty todo list: 6 expansions
+            assert_eq!(calls.load(Ordering::SeqCst), 3);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_plan_with_stats() -> Result<()> {
+        let planner = PlannerBuilder::new()
+            .with_domain(create_chain_domain()?)
+            .build()?;
+
+        let (plan, stats) = planner.find_plan_with_stats(State::new("s"), vec![PlanItem::task("chain", vec![])])?;
+        assert!(plan.is_some());
+        assert_eq!(stats.nodes_expanded, 6);
+        assert_eq!(stats.max_depth, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats_count_untried_methods() -> Result<()> {
+        let mut domain = create_chain_domain()?;
+        domain.declare_task_method("chain", |_state: &State, _args: &[crate::core::StateValue]| {
+            Some(vec![PlanItem::action("step", vec![])])
+        })?;
+        let planner = PlannerBuilder::new().with_domain(domain).build()?;
+
+        let (plan, stats) = planner.find_plan_with_stats(State::new("s"), vec![PlanItem::task("chain", vec![])])?;
+        assert_eq!(plan.map(|p| p.len()), Some(4));
+        assert_eq!(stats.untried_methods, vec![("chain".to_string(), 1)]);
+
+        let (_, stats) = planner.find_plan_with_stats(State::new("s"), vec![PlanItem::action("step", vec![])])?;
+        assert!(stats.untried_methods.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_plan_stable_prefers_reference() -> Result<()> {
+        let mut domain = Domain::new("commute");
+        for mode in ["walk", "drive"] {
+            domain.declare_action(mode, |state: &mut State, _args: &[crate::core::StateValue]| Some(state.clone()))?;
+        }
+        domain.declare_task_methods("commute", vec![
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![PlanItem::action("walk", vec![]), PlanItem::action("walk", vec![])]),
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![PlanItem::action("drive", vec![]), PlanItem::action("walk", vec![])]),
+        ])?;
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+        let todo = vec![PlanItem::task("commute", vec![])];
+
+        // The planner keeps a single plan, but the stable search compares several
+        let reference = vec![PlanItem::action("drive", vec![])];
+        let stable = planner.find_plan_stable(State::new("s"), todo.clone(), &reference)?;
+        assert_eq!(stable, Some(vec![PlanItem::action("drive", vec![]), PlanItem::action("walk", vec![])]));
+
+        // Without a closer plan, the first plan found wins
+        let first = planner.find_plan(State::new("s"), todo.clone())?;
+        assert_eq!(planner.find_plan_stable(State::new("s"), todo, &[])?, first);
+        Ok(())
+    }
+
+    #[test]
+    fn test_weighted_method_tried_first() -> Result<()> {
+        let mut domain = Domain::new("commute");
+        for mode in ["walk", "drive"] {
+            domain.declare_action(mode, |state: &mut State, _args: &[crate::core::StateValue]| Some(state.clone()))?;
+        }
+        domain.declare_task_method("commute", |_state: &State, _args: &[crate::core::StateValue]| {
+            Some(vec![PlanItem::action("walk", vec![])])
+        })?;
+        domain.declare_task_method_weighted("commute", 2.0, |state: &State, _args: &[crate::core::StateValue]| {
+            state.get_var("has", "car")?.as_bool()?.then(|| vec![PlanItem::action("drive", vec![])])
+        })?;
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+        let todo = vec![PlanItem::task("commute", vec![])];
+
+        let mut state = State::new("s");
+        state.set_var("has", "car", true.into());
+        assert_eq!(planner.find_plan(state.clone(), todo.clone())?, Some(vec![PlanItem::action("drive", vec![])]));
+
+        state.set_var("has", "car", false.into());
+        assert_eq!(planner.find_plan(state, todo)?, Some(vec![PlanItem::action("walk", vec![])]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reachable_states_in_two_block_world() -> Result<()> {
+        let mut domain = Domain::new("two_blocks");
+        crate::domains::blocks::install_blocks_actions(&mut domain)?;
+        let planner = PlannerBuilder::new().with_domain(domain).with_verbose_level(0)?.build()?;
+
+        let mut state = State::new("s");
+        for block in ["a", "b"] {
+            state.set_var("pos", block, string_value("table"));
+            state.set_var("clear", block, true.into());
+        }
+        state.set_var("holding", "hand", false.into());
+        let ground = |_state: &State| {
+            let mut instances = Vec::new();
+            for x in ["a", "b"] {
+                instances.push(("pickup".to_string(), vec![string_value(x)]));
+                instances.push(("putdown".to_string(), vec![string_value(x)]));
+                for y in ["a", "b"].into_iter().filter(|y| *y != x) {
+                    instances.push(("unstack".to_string(), vec![string_value(x), string_value(y)]));
+                    instances.push(("stack".to_string(), vec![string_value(x), string_value(y)]));
+                }
+            }
+            instances
+        };
+
+        assert_eq!(planner.reachable_states(state.clone(), 0, ground)?.len(), 1);
+        // Holding a or b, then a on b or b on a; putting down is back to the start
+        assert_eq!(planner.reachable_states(state.clone(), 1, ground)?.len(), 3);
+        assert_eq!(planner.reachable_states(state, 2, ground)?.len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_cache_skips_repeated_search() -> Result<()> {
+        let planner = PlannerBuilder::new()
+            .with_domain(create_chain_domain()?)
+            .with_plan_cache(4)
+            .build()?;
+        let todo = vec![PlanItem::task("chain", vec![])];
+
+        let (plan, stats) = planner.find_plan_with_stats(State::new("s"), todo.clone())?;
+        assert_eq!(stats.nodes_expanded, 6);
+
+        // A differently named but equal state hits the cache
+        let (cached, stats) = planner.find_plan_with_stats(State::new("other"), todo.clone())?;
+        assert_eq!(cached, plan);
+        assert_eq!(stats.nodes_expanded, 0);
+
+        let mut changed = State::new("s");
+        changed.set_var("pos", "a", string_value("table"));
+        let (_, stats) = planner.find_plan_with_stats(changed, todo)?;
+        assert_eq!(stats.nodes_expanded, 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_strategy_continues_past_recursion_limit() -> Result<()> {
+        let mut domain = create_chain_domain()?;
+        domain.declare_task_method("countdown", |_state: &State, args: &[crate::core::StateValue]| {
+            match args.first()?.as_u64()? {
+                0 => Some(vec![]),
+                n => Some(vec![PlanItem::action("step", vec![]), PlanItem::task("countdown", vec![(n - 1).into()])]),
+            }
+        })?;
+        let todo = vec![PlanItem::task("countdown", vec![100.into()])];
+        let planner = |strategy| PlannerBuilder::new()
+            .with_domain(domain.clone())
+            .with_strategy(strategy)
+            .with_recursion_limit(50)
+            .with_verbose_level(0)?
+            .build();
+
+        let recursive = planner(PlanningStrategy::Recursive)?.find_plan(State::new("s"), todo.clone());
+        assert_eq!(recursive, Err(GTRustHopError::recursion_limit(50)));
+
+        let auto = planner(PlanningStrategy::Auto)?.find_plan(State::new("s"), todo.clone())?;
+        assert_eq!(auto.as_ref().map(Vec::len), Some(100));
+        assert_eq!(auto, planner(PlanningStrategy::Iterative)?.find_plan(State::new("s"), todo)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_recursive_strategy_is_unlimited_by_default() -> Result<()> {
+        let mut domain = create_chain_domain()?;
+        domain.declare_task_method("countdown", |_state: &State, args: &[crate::core::StateValue]| {
+            match args.first()?.as_u64()? {
+                0 => Some(vec![]),
+                n => Some(vec![PlanItem::action("step", vec![]), PlanItem::task("countdown", vec![(n - 1).into()])]),
+            }
+        })?;
+        let steps = DEFAULT_RECURSION_LIMIT + 1;
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_strategy(PlanningStrategy::Recursive)
+            .with_verbose_level(0)?
+            .build()?;
+
+        // The recursive search keeps one call frame per node, so give it room
+        let plan = std::thread::Builder::new()
+            .stack_size(256 * 1024 * 1024)
+            .spawn(move || planner.find_plan(State::new("s"), vec![PlanItem::task("countdown", vec![steps.into()])]))
+            .expect("spawn planning thread")
+            .join()
+            .expect("planning thread panicked")?;
+        assert_eq!(plan.map(|plan| plan.len()), Some(steps));
+        Ok(())
+    }
+
+    /// Blocks-like domain where `pos` goals are achieved by a single `stack` action
+    /// that requires both blocks to be clear, so goal order matters
+    fn create_stacking_domain() -> Result<Domain> {
+        let mut domain = Domain::new("stacking_domain");
+        domain.declare_action("stack", |state: &mut State, args: &[crate::core::StateValue]| {
+            let (x, y) = (args[0].as_str()?, args[1].as_str()?);
+            let is_clear = |block: &str| {
+                state.get_var_map("pos").is_none_or(|pos| pos.values().all(|v| v.as_str() != Some(block)))
+            };
+            if is_clear(x) && is_clear(y) {
+                state.set_var("pos", x, string_value(y));
+                Some(state.clone())
+            } else {
+                None
+            }
+        })?;
+        domain.declare_unigoal_method("pos", |_state: &State, arg: &str, value: &crate::core::StateValue| {
+            Some(vec![PlanItem::action("stack", vec![string_value(arg), value.clone()])])
+        })?;
+        Ok(domain)
+    }
+
+    #[test]
+    fn test_multigoal_custom_decomposer() -> Result<()> {
+        use crate::core::Multigoal;
+        use std::sync::Arc;
+
+        let mut state = State::new("tower");
+        for block in ["a", "b", "c"] {
+            state.set_var("pos", block, string_value("table"));
+        }
+
+        let mut goal = Multigoal::new("tower_goal");
+        goal.set_goal("pos", "a", string_value("b"));
+        goal.set_goal("pos", "b", string_value("c"));
+
+        // Build the tower bottom-up: blocks that others are stacked onto are placed first
+        let bottom_up = goal.clone().with_decomposer(Arc::new(|mg: &Multigoal, _state: &State| {
+            let mut goals = mg.to_unigoals();
+            goals.sort_by_key(|(_, arg, _)| !mg.variables["pos"].values().any(|v| v.as_str() == Some(arg.as_str())));
+            goals.into_iter().map(|(var, arg, value)| PlanItem::unigoal(var, arg, value)).collect()
+        }));
+
+        let planner = PlannerBuilder::new()
+            .with_domain(create_stacking_domain()?)
+            .build()?;
+
+        let plan = planner.find_plan(state.clone(), vec![PlanItem::multigoal(bottom_up)])?;
+        assert_eq!(plan, Some(vec![
+            PlanItem::action("stack", vec![string_value("b"), string_value("c")]),
+            PlanItem::action("stack", vec![string_value("a"), string_value("b")]),
+        ]));
+
+        // The reverse order cannot succeed since `b` is no longer clear
+        let top_down = goal.with_decomposer(Arc::new(|_mg: &Multigoal, _state: &State| {
+            vec![
+                PlanItem::unigoal("pos", "a", string_value("b")),
+                PlanItem::unigoal("pos", "b", string_value("c")),
+            ]
+        }));
+        assert_eq!(planner.find_plan(state, vec![PlanItem::multigoal(top_down)])?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_plan_staged_carries_state_forward() -> Result<()> {
+        let mut state = State::new("tower");
+        for block in ["a", "b", "c"] {
+            state.set_var("pos", block, string_value("table"));
+        }
+        let stage = |arg: &str, target: &str| {
+            let mut goal = Multigoal::new(format!("{arg}_on_{target}"));
+            goal.set_goal("pos", arg, string_value(target));
+            goal
+        };
+
+        let planner = PlannerBuilder::new()
+            .with_domain(create_stacking_domain()?)
+            .with_verbose_level(0)?
+            .build()?;
+
+        let plan = planner.find_plan_staged(state.clone(), vec![stage("b", "c"), stage("a", "b")])?;
+        assert_eq!(plan, Some(vec![
+            PlanItem::action("stack", vec![string_value("b"), string_value("c")]),
+            PlanItem::action("stack", vec![string_value("a"), string_value("b")]),
+        ]));
+
+        // `c` is covered once the first stage is done
+        assert!(planner.find_plan_staged(state.clone(), vec![stage("c", "a")])?.is_some());
+        assert_eq!(planner.find_plan_staged(state, vec![stage("b", "c"), stage("c", "a")])?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_plan_with_builds_goals_from_state() -> Result<()> {
+        let mut state = State::new("tower");
+        for (block, home) in [("a", "b"), ("b", "table"), ("c", "table")] {
+            state.set_var("pos", block, string_value("table"));
+            state.set_var("home", block, string_value(home));
+        }
+
+        let planner = PlannerBuilder::new()
+            .with_domain(create_stacking_domain()?)
+            .with_verbose_level(0)?
+            .build()?;
+        let plan = planner.find_plan_with(state, |state| {
+            let mut misplaced: Vec<(&str, &StateValue)> = state.relation("home").iter()
+                .filter(|(block, home)| state.get_var("pos", block) != Some(home))
+                .collect();
+            misplaced.sort_by_key(|(block, _)| *block);
+            assert_eq!(misplaced.len(), 1);
+            misplaced.into_iter().map(|(block, home)| PlanItem::unigoal("pos", block, home.clone())).collect()
+        })?;
+        assert_eq!(plan, Some(vec![PlanItem::action("stack", vec![string_value("a"), string_value("b")])]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_strategies() -> Result<()> {
+        use crate::domains::create_simple_htn_domain;
+        use crate::examples::create_travel_task;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut state = State::new("travel");
+        state.set_var("loc", "alice", string_value("home_a"));
+        state.set_var("cash", "alice", 20.0.into());
+        let planner = PlannerBuilder::new()
+            .with_domain(create_simple_htn_domain()?)
+            .with_verbose_level(0)?
+            .build()?;
+        let comparison = planner.compare_strategies(state.clone(), vec![create_travel_task("alice", "home_a", "park")])?;
+        assert!(comparison.iterative.is_some());
+        assert!(comparison.agree && comparison.same_actions());
+
+        // A method that only works on every other call makes the strategies disagree
+        let flaky = || -> Result<Domain> {
+            let calls = AtomicUsize::new(0);
+            let mut flaky = Domain::new("flaky");
+            flaky.declare_action("noop", |state: &mut State, _args: &[crate::core::StateValue]| Some(state.clone()))?;
+            flaky.declare_task_method("work", move |_state: &State, _args: &[crate::core::StateValue]| {
+                (calls.fetch_add(1, Ordering::SeqCst) % 2 == 1).then(|| vec![PlanItem::action("noop", vec![])])
+            })?;
+            Ok(flaky)
+        };
+        let planner = PlannerBuilder::new()
+            .with_domain(flaky()?)
+            .with_verbose_level(0)?
+            .build()?;
+        let comparison = planner.compare_strategies(state.clone(), vec![PlanItem::task("work", vec![])])?;
+        assert_eq!(comparison.iterative, None);
+        assert!(comparison.recursive.is_some());
+        assert!(!comparison.agree && !comparison.same_actions());
+
+        // The plan cache doesn't hide the disagreement
+        let planner = PlannerBuilder::new()
+            .with_domain(flaky()?)
+            .with_plan_cache(4)
+            .with_verbose_level(0)?
+            .build()?;
+        let comparison = planner.compare_strategies(state, vec![PlanItem::task("work", vec![])])?;
+        assert!(comparison.recursive.is_some());
+        assert!(!comparison.agree);
+        Ok(())
+    }
+
+    #[test]
+    fn test_task_pattern_tries_each_entity_of_type() -> Result<()> {
+        use crate::core::wildcard;
+
+        let mut domain = Domain::new("taxi_pool");
+        domain.declare_action("ride", |state: &mut State, args: &[crate::core::StateValue]| {
+            let (taxi, person) = (args[0].as_str()?, args[1].as_str()?);
+            let fuel = state.get_var("fuel", taxi)?.as_i64()?;
+            (fuel > 0).then(|| {
+                state.set_var("fuel", taxi, (fuel - 1).into());
+                state.set_var("loc", person, string_value("park"));
+                state.clone()
+            })
+        })?;
+        domain.declare_task_method("get_taxi", |_state: &State, args: &[crate::core::StateValue]| {
+            Some(vec![PlanItem::action("ride", args.to_vec())])
+        })?;
+
+        let mut state = State::new("s");
+        state.set_var("types", "taxi", serde_json::json!(["taxi1", "taxi2", "taxi3"]));
+        state.set_var("fuel", "taxi1", 0.into());
+        state.set_var("fuel", "taxi2", 3.into());
+        state.set_var("fuel", "taxi3", 3.into());
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+        let todo = vec![PlanItem::task_pattern("get_taxi", vec![wildcard("taxi"), string_value("alice")])];
+        let plan = planner.find_plan(state.clone(), todo)?;
+        assert_eq!(plan, Some(vec![PlanItem::action("ride", vec![string_value("taxi2"), string_value("alice")])]));
+
+        // No entities of the type means no candidates
+        let todo = vec![PlanItem::task_pattern("get_taxi", vec![wildcard("limo"), string_value("alice")])];
+        assert_eq!(planner.find_plan(state, todo)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_precondition_is_checked_once() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let checks = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&checks);
+        let mut domain = Domain::new("lamp");
+        domain.declare_action_with_precond(
+            "switch_on",
+            move |state: &State, _args: &[crate::core::StateValue]| {
+                counted.fetch_add(1, Ordering::SeqCst);
+                state.get_var("lamp", "on") == Some(&false.into())
+            },
+            |state: &mut State, _args: &[crate::core::StateValue]| state.set_var("lamp", "on", true.into()),
+        )?;
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+
+        let mut state = State::new("off");
+        state.set_var("lamp", "on", false.into());
+        let plan = planner.find_plan(state, vec![PlanItem::action("switch_on", vec![])])?;
+        assert_eq!(plan, Some(vec![PlanItem::action("switch_on", vec![])]));
+        assert_eq!(checks.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_panic_isolation_reports_panicking_method() -> Result<()> {
+        let mut domain = Domain::new("fragile");
+        domain.declare_action("noop", |state: &mut State, _args: &[crate::core::StateValue]| Some(state.clone()))?;
+        domain.declare_task_methods("work", vec![
+            |_state: &State, _args: &[crate::core::StateValue]| -> Option<Vec<PlanItem>> { panic!("boom") },
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![PlanItem::action("noop", vec![])]),
+        ])?;
+        let todo = vec![PlanItem::task("work", vec![])];
+
+        let builder = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .with_panic_isolation(true);
+        let result = builder.clone().build()?.find_plan(State::new("s"), todo.clone());
+        assert_eq!(result, Err(GTRustHopError::method_panicked("work[0]", "boom")));
+
+        let planner = builder.with_panic_policy(crate::planning::PanicPolicy::Skip).build()?;
+        assert_eq!(planner.find_plan(State::new("s"), todo)?, Some(vec![PlanItem::action("noop", vec![])]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_panic_isolation_guards_constraints_heuristics_and_decomposers() -> Result<()> {
+        let mut domain = Domain::new("fragile");
+        domain.declare_action("noop", |state: &mut State, _args: &[crate::core::StateValue]| Some(state.clone()))?;
+        domain.declare_task_methods("work", vec![
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![PlanItem::action("noop", vec![])]),
+        ])?;
+        let todo = vec![PlanItem::task("work", vec![])];
+        let builder = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .with_panic_isolation(true);
+
+        let constrained = builder.clone().with_state_constraint(Arc::new(|_state: &State| -> bool { panic!("bad constraint") }));
+        let result = constrained.clone().build()?.find_plan(State::new("s"), todo.clone());
+        assert_eq!(result, Err(GTRustHopError::method_panicked("state constraint", "bad constraint")));
+        // A skipped constraint panic forbids the state
+        let planner = constrained.with_panic_policy(crate::planning::PanicPolicy::Skip).build()?;
+        assert_eq!(planner.find_plan(State::new("s"), todo.clone())?, None);
+
+        let heuristic = builder.clone()
+            .with_heuristic(Arc::new(|_state: &State| -> f64 { panic!("bad heuristic") }))
+            .with_thrashing_detection(3);
+        let result = heuristic.clone().build()?.find_plan(State::new("s"), todo.clone());
+        assert_eq!(result, Err(GTRustHopError::method_panicked("heuristic", "bad heuristic")));
+        let planner = heuristic.with_panic_policy(crate::planning::PanicPolicy::Skip).build()?;
+        assert_eq!(planner.find_plan(State::new("s"), todo)?, Some(vec![PlanItem::action("noop", vec![])]));
+
+        let mut goal = Multigoal::new("mg").with_decomposer(Arc::new(|_multigoal: &Multigoal, _state: &State| -> Vec<PlanItem> {
+            panic!("bad decomposer")
+        }));
+        goal.set_goal("loc", "robot", string_value("park"));
+        let todo = vec![PlanItem::multigoal(goal)];
+        let result = builder.clone().build()?.find_plan(State::new("s"), todo.clone());
+        assert_eq!(result, Err(GTRustHopError::method_panicked("mg", "bad decomposer")));
+        let planner = builder.with_panic_policy(crate::planning::PanicPolicy::Skip).build()?;
+        assert_eq!(planner.find_plan(State::new("s"), todo)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_optimal_plan_skips_expensive_soft_goal() -> Result<()> {
+        let mut domain = Domain::new("errands");
+        domain.declare_action("move", |state: &mut State, args: &[crate::core::StateValue]| {
+            state.set_var("loc", "robot", args.first()?.clone());
+            Some(state.clone())
+        })?;
+        domain.declare_action("collect", |state: &mut State, args: &[crate::core::StateValue]| {
+            state.set_var("has", args.first()?.as_str()?, true.into());
+            Some(state.clone())
+        })?;
+        domain.set_action_cost("collect", 10.0);
+        domain.declare_unigoal_method("loc", |_state: &State, _arg: &str, value: &crate::core::StateValue| {
+            Some(vec![PlanItem::action("move", vec![value.clone()])])
+        })?;
+        domain.declare_unigoal_method("has", |_state: &State, arg: &str, _value: &crate::core::StateValue| {
+            Some(vec![PlanItem::action("collect", vec![string_value(arg)])])
+        })?;
+
+        let mut state = State::new("s");
+        state.set_var("loc", "robot", string_value("base"));
+        state.set_var("has", "flag", false.into());
+        let mut goal = Multigoal::new("errands");
+        goal.set_goal("loc", "robot", string_value("dock"));
+        goal.set_soft_goal("has", "flag", true.into(), 3.0);
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+
+        // Collecting the flag costs more than the penalty for leaving it
+        let (plan, cost) = planner.find_optimal_plan(state.clone(), &goal)?.unwrap();
+        assert_eq!(plan, vec![PlanItem::action("move", vec![string_value("dock")])]);
+        assert_eq!(cost, 4.0);
+
+        goal.set_soft_goal("has", "flag", true.into(), 20.0);
+        let (plan, cost) = planner.find_optimal_plan(state, &goal)?.unwrap();
+        assert!(plan.contains(&PlanItem::action("collect", vec![string_value("flag")])));
+        assert_eq!(cost, 11.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_multigoal_methods_filtered_by_relevant_vars() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let loc_calls = Arc::new(AtomicUsize::new(0));
+        let pos_calls = Arc::new(AtomicUsize::new(0));
+        let mut domain = create_stacking_domain()?;
+        domain.declare_action("move", |state: &mut State, args: &[crate::core::StateValue]| {
+            state.set_var("loc", "robot", args.first()?.clone());
+            Some(state.clone())
+        })?;
+        let calls = Arc::clone(&loc_calls);
+        domain.declare_multigoal_method_for(&["loc"], move |_state: &State, mg: &Multigoal| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Some(vec![PlanItem::action("move", vec![mg.get_goal("loc", "robot")?.clone()])])
+        })?;
+        let calls = Arc::clone(&pos_calls);
+        domain.declare_multigoal_method_for(&["pos"], move |_state: &State, _mg: &Multigoal| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            None
+        })?;
+
+        let mut state = State::new("s");
+        state.set_var("loc", "robot", string_value("base"));
+        let mut goal = Multigoal::new("goal");
+        goal.set_goal("loc", "robot", string_value("dock"));
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+        let plan = planner.find_plan(state, vec![PlanItem::multigoal(goal)])?;
+        assert_eq!(plan, Some(vec![PlanItem::action("move", vec![string_value("dock")])]));
+        assert_eq!(loc_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(pos_calls.load(Ordering::SeqCst), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_maintain_prunes_actions_breaking_goal() -> Result<()> {
+        let mut domain = Domain::new("travel");
+        // Driving takes bob along, walking leaves him where he is
+        domain.declare_action("drive", |state: &mut State, args: &[StateValue]| {
+            let target = args.first()?.clone();
+            state.set_var("loc", "alice", target.clone());
+            state.set_var("loc", "bob", target);
+            Some(state.clone())
+        })?;
+        domain.declare_action("walk", |state: &mut State, args: &[StateValue]| {
+            state.set_var("loc", "alice", args.first()?.clone());
+            Some(state.clone())
+        })?;
+        domain.declare_task_method("go", |_state: &State, args: &[StateValue]| {
+            Some(vec![PlanItem::action("drive", args.to_vec())])
+        })?;
+        domain.declare_task_method("go", |_state: &State, args: &[StateValue]| {
+            Some(vec![PlanItem::action("walk", args.to_vec())])
+        })?;
+        let mut state = State::new("s");
+        state.set_var("loc", "alice", string_value("home"));
+        state.set_var("loc", "bob", string_value("home"));
+
+        let planner = PlannerBuilder::new().with_domain(domain).with_verbose_level(0)?.build()?;
+        let go = PlanItem::task("go", vec![string_value("park")]);
+        assert_eq!(planner.find_plan(state.clone(), vec![go.clone()])?, Some(vec![PlanItem::action("drive", vec![string_value("park")])]));
+
+        let keep_bob_home = PlanItem::maintain(PlanItem::unigoal("loc", "bob", string_value("home")));
+        let plan = planner.find_plan(state.clone(), vec![keep_bob_home, go.clone()])?;
+        assert_eq!(plan, Some(vec![PlanItem::action("walk", vec![string_value("park")])]));
+
+        // A goal that does not hold to begin with cannot be maintained
+        let keep_bob_out = PlanItem::maintain(PlanItem::unigoal("loc", "bob", string_value("park")));
+        assert_eq!(planner.find_plan(state, vec![keep_bob_out, go])?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resource_prunes_routes_running_out_of_fuel() -> Result<()> {
+        let mut domain = Domain::new("road_trip");
+        domain.declare_action("drive", |state: &mut State, args: &[StateValue]| {
+            state.set_var("loc", "car", args.last()?.clone());
+            Some(state.clone())
+        })?;
+        domain.declare_action_resource_delta("drive", "fuel", |_state: &State, args: &[StateValue]| {
+            -args.first().and_then(StateValue::as_f64).unwrap_or_default()
+        });
+        // The scenic route is tried first but is longer than the highway
+        domain.declare_task_method("go", |_state: &State, args: &[StateValue]| {
+            Some(vec![
+                PlanItem::action("drive", vec![6.into(), string_value("lake")]),
+                PlanItem::action("drive", vec![6.into(), args.first()?.clone()]),
+            ])
+        })?;
+        domain.declare_task_method("go", |_state: &State, args: &[StateValue]| {
+            Some(vec![PlanItem::action("drive", vec![9.into(), args.first()?.clone()])])
+        })?;
+        let mut state = State::new("s");
+        state.set_var("loc", "car", string_value("home"));
+        let todo = vec![PlanItem::task("go", vec![string_value("city")])];
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .with_resource("fuel", 10.0)
+            .build()?;
+        let plan = planner.find_plan(state.clone(), todo.clone())?;
+        assert_eq!(plan, Some(vec![PlanItem::action("drive", vec![9.into(), string_value("city")])]));
+
+        // Committed actions spend fuel too, leaving too little for the rest
+        let detour = PlanItem::action("drive", vec![9.into(), string_value("lake")]);
+        assert_eq!(planner.complete_plan(state.clone(), std::slice::from_ref(&detour), todo.clone())?, None);
+        let overspend = planner.complete_plan(state.clone(), &[detour.clone(), detour.clone()], todo.clone());
+        assert_eq!(overspend, Err(GTRustHopError::action_not_applicable(detour.to_string())));
+
+        // A level in the initial state takes precedence over the initial amount
+        state.set_var(RESOURCES_VAR, "fuel", 5.0.into());
+        assert_eq!(planner.find_plan(state, todo)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_postprocessor_drops_redundant_pair() -> Result<()> {
+        use crate::domains::blocks::{install_blocks_actions, install_blocks_methods};
+
+        let mut domain = Domain::new("blocks");
+        install_blocks_actions(&mut domain)?;
+        install_blocks_methods(&mut domain)?;
+        let mut state = State::new("blocks");
+        for block in ["a", "b"] {
+            state.set_var("pos", block, string_value("table"));
+            state.set_var("clear", block, true.into());
+        }
+        state.set_var("holding", "hand", false.into());
+        let todo = vec![
+            PlanItem::task("take", vec![string_value("a")]),
+            PlanItem::task("put", vec![string_value("a"), string_value("table")]),
+            PlanItem::task("take", vec![string_value("a")]),
+            PlanItem::task("put", vec![string_value("a"), string_value("b")]),
+        ];
+
+        // Putting a block down only to pick it up again achieves nothing
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .with_plan_postprocessor(Arc::new(|plan: Plan| {
+                let mut compressed: Plan = Vec::new();
+                for item in plan {
+                    let redundant = matches!((compressed.last(), &item), (Some(PlanItem::Action(last, last_args)), PlanItem::Action(name, args))
+                        if last == "putdown" && name == "pickup" && last_args == args);
+                    if redundant {
+                        compressed.pop();
+                    } else {
+                        compressed.push(item);
+                    }
+                }
+                compressed
+            }))
+            .build()?;
+        let plan = planner.find_plan(state.clone(), todo)?.unwrap();
+        assert_eq!(plan, vec![
+            PlanItem::action("pickup", vec![string_value("a")]),
+            PlanItem::action("stack", vec![string_value("a"), string_value("b")]),
+        ]);
+        let final_state = planner.domain().apply_plan(state, &plan)?;
+        assert_eq!(final_state.get_var("pos", "a"), Some(&string_value("b")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_plan_empty_domain() -> Result<()> {
+        let planner = PlannerBuilder::new()
+            .with_domain(Domain::new("empty"))
+            .build()?;
+
+        // An empty todo list is trivially solved
+        assert_eq!(planner.find_plan(State::new("s"), vec![])?, Some(vec![]));
+
+        let result = planner.find_plan(State::new("s"), vec![PlanItem::task("anything", vec![])]);
+        assert!(matches!(result, Err(GTRustHopError::EmptyDomain { ref name }) if name == "empty"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_relgoal_two_arguments() -> Result<()> {
+        let mut domain = Domain::new("network_domain");
+        domain.declare_action("link", |state: &mut State, args: &[crate::core::StateValue]| {
+            state.set_rel("connected", args, crate::core::bool_value(true));
+            Some(state.clone())
+        })?;
+        // connected(a, c) can be achieved through an intermediate hub
+        domain.declare_relgoal_method("connected", |_state: &State, args: &[crate::core::StateValue], _value: &crate::core::StateValue| {
+            if args.contains(&string_value("hub")) {
+                return None;
+            }
+            Some(vec![
+                PlanItem::relgoal("connected", vec![args[0].clone(), string_value("hub")], crate::core::bool_value(true)),
+                PlanItem::relgoal("connected", vec![string_value("hub"), args[1].clone()], crate::core::bool_value(true)),
+                PlanItem::action("link", args.to_vec()),
+            ])
+        })?;
+        domain.declare_relgoal_method("connected", |_state: &State, args: &[crate::core::StateValue], _value: &crate::core::StateValue| {
+            Some(vec![PlanItem::action("link", args.to_vec())])
+        })?;
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .build()?;
+
+        let mut state = State::new("network");
+        state.set_rel("connected", &[string_value("a"), string_value("hub")], crate::core::bool_value(true));
+
+        let goal = PlanItem::relgoal("connected", vec![string_value("a"), string_value("c")], crate::core::bool_value(true));
+        let plan = planner.find_plan(state, vec![goal])?;
+        assert_eq!(plan, Some(vec![
+            PlanItem::action("link", vec![string_value("hub"), string_value("c")]),
+            PlanItem::action("link", vec![string_value("a"), string_value("c")]),
+        ]));
+        Ok(())
+    }
+
+    /// Domain whose first `loc` method claims success but leaves the goal unmet
+    fn create_lying_method_domain() -> Result<Domain> {
+        let mut domain = Domain::new("lying_domain");
+        domain.declare_action("move", |state: &mut State, args: &[crate::core::StateValue]| {
+            state.set_var("loc", args[0].as_str()?, args[1].clone());
+            Some(state.clone())
+        })?;
+        domain.declare_unigoal_method("loc", |_state: &State, _arg: &str, _value: &crate::core::StateValue| {
+            Some(vec![])
+        })?;
+        domain.declare_unigoal_method("loc", |_state: &State, arg: &str, value: &crate::core::StateValue| {
+            Some(vec![PlanItem::action("move", vec![string_value(arg), value.clone()])])
+        })?;
+        Ok(domain)
+    }
+
+    #[test]
+    fn test_goal_verification_failure_is_an_error() -> Result<()> {
+        let mut state = State::new("s");
+        state.set_var("loc", "alice", string_value("home"));
+        let goal = vec![PlanItem::unigoal("loc", "alice", string_value("park"))];
+
+        // With verification, the lying method is reported instead of silently
+        // backtracking into the sibling method that would have succeeded
+        for strategy in [PlanningStrategy::Iterative, PlanningStrategy::Recursive] {
+            let planner = PlannerBuilder::new()
+                .with_domain(create_lying_method_domain()?)
+                .with_strategy(strategy)
+                .with_goal_verification(true)
+                .build()?;
+
+            let result = planner.find_plan(state.clone(), goal.clone());
+            assert!(matches!(
+                result,
+                Err(GTRustHopError::MethodVerificationFailed { ref method, depth: 0, .. }) if method == "loc[0]"
+            ));
+        }
+
+        // Without verification, the lying method's empty plan is accepted
+        let planner = PlannerBuilder::new()
+            .with_domain(create_lying_method_domain()?)
+            .with_goal_verification(false)
+            .build()?;
+        assert_eq!(planner.find_plan(state, goal)?, Some(vec![]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_plan_verified_vs_unverified() -> Result<()> {
+        let mut state = State::new("s");
+        state.set_var("loc", "alice", string_value("home"));
+        let goal = vec![PlanItem::unigoal("loc", "alice", string_value("park"))];
+
+        let planner = PlannerBuilder::new()
+            .with_domain(create_lying_method_domain()?)
+            .build()?;
+        let (verified, unverified, differ) = planner.find_plan_verified_vs_unverified(state, goal);
+        assert_eq!(verified, None);
+        assert_eq!(unverified, Some(vec![]));
+        assert!(differ);
+
+        let planner = PlannerBuilder::new()
+            .with_domain(create_chain_domain()?)
+            .build()?;
+        let (verified, unverified, differ) = planner.find_plan_verified_vs_unverified(State::new("s"), vec![PlanItem::task("chain", vec![])]);
+        assert!(verified.is_some());
+        assert_eq!(verified, unverified);
+        assert!(!differ);
+        Ok(())
+    }
+
+    #[test]
+    fn test_preferred_tag_method_tried_first() -> Result<()> {
+        let mut domain = Domain::new("tagged");
+        for action in ["jump", "climb"] {
+            domain.declare_action(action, |state: &mut State, _args: &[crate::core::StateValue]| Some(state.clone()))?;
+        }
+        domain.declare_task_method("cross", |_state: &State, _args: &[crate::core::StateValue]| {
+            Some(vec![PlanItem::action("jump", vec![])])
+        })?;
+        domain.declare_task_method("cross", |_state: &State, _args: &[crate::core::StateValue]| {
+            Some(vec![PlanItem::action("climb", vec![])])
+        })?;
+        domain.tag_action("climb", &["safe", "slow"]);
+        assert_eq!(domain.action_tags("climb"), ["safe", "slow"]);
+
+        let todo = vec![PlanItem::task("cross", vec![])];
+        for strategy in [PlanningStrategy::Iterative, PlanningStrategy::Recursive] {
+            let planner = PlannerBuilder::new()
+                .with_domain(domain.clone())
+                .with_strategy(strategy)
+                .with_verbose_level(0)?;
+            assert_eq!(planner.clone().build()?.find_plan(State::new("s"), todo.clone())?, Some(vec![PlanItem::action("jump", vec![])]));
+            let planner = planner.prefer_tags(&["safe"]).build()?;
+            assert_eq!(planner.find_plan(State::new("s"), todo.clone())?, Some(vec![PlanItem::action("climb", vec![])]));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_token_stops_search() -> Result<()> {
+        use std::sync::atomic::AtomicBool;
+        use std::time::{Duration, Instant};
+
+        // A task that keeps refining into itself never finishes on its own
+        let mut domain = Domain::new("endless");
+        domain.declare_action("step", |state: &mut State, _args: &[crate::core::StateValue]| Some(state.clone()))?;
+        domain.declare_task_method("forever", |_state: &State, _args: &[crate::core::StateValue]| {
+            Some(vec![PlanItem::action("step", vec![]), PlanItem::task("forever", vec![])])
+        })?;
+
+        let token = Arc::new(AtomicBool::new(false));
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .with_cancel_token(Arc::clone(&token))
+            .build()?;
+
+        let started = Instant::now();
+        let handle = std::thread::spawn(move || planner.find_plan(State::new("s"), vec![PlanItem::task("forever", vec![])]));
+        std::thread::sleep(Duration::from_millis(50));
+        token.store(true, Ordering::Relaxed);
+
+        let result = handle.join().expect("planner thread panicked");
+        assert!(matches!(result, Err(GTRustHopError::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        Ok(())
+    }
+
+    #[test]
+    fn test_conformant_plan_covers_every_outcome() -> Result<()> {
+        let mut domain = Domain::new("coin");
+        domain.declare_nondet_action("toss", |state: &State, _args: &[crate::core::StateValue]| {
+            ["heads", "tails"].into_iter()
+                .map(|side| {
+                    let mut outcome = state.copy(None);
+                    outcome.set_var("coin", "c", string_value(side));
+                    outcome
+                })
+                .collect()
+        })?;
+        domain.declare_action("turn_to_heads", |state: &mut State, _args: &[crate::core::StateValue]| {
+            state.set_var("coin", "c", string_value("heads"));
+            Some(state.clone())
+        })?;
+        domain.declare_unigoal_method("coin", |_state: &State, _arg: &str, _value: &crate::core::StateValue| {
+            Some(vec![PlanItem::action("toss", vec![])])
+        })?;
+        domain.declare_unigoal_method("coin", |_state: &State, _arg: &str, _value: &crate::core::StateValue| {
+            Some(vec![PlanItem::action("toss", vec![]), PlanItem::action("turn_to_heads", vec![])])
+        })?;
+
+        let mut state = State::new("s");
+        state.set_var("coin", "c", string_value("edge"));
+        let todo = vec![PlanItem::unigoal("coin", "c", string_value("heads"))];
+        let toss = PlanItem::action("toss", vec![]);
+
+        // Without conformant mode the toss is assumed to land heads
+        let planner = PlannerBuilder::new().with_domain(domain.clone()).with_verbose_level(0)?.build()?;
+        assert_eq!(planner.find_plan(state.clone(), todo.clone())?, Some(vec![toss.clone()]));
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .with_conformant(true)
+            .build()?;
+        assert_eq!(planner.find_plan(state, todo)?, Some(vec![toss, PlanItem::action("turn_to_heads", vec![])]));
+        Ok(())
+    }
+
+    /// Coin domain where `toss` may land heads on the table or on the floor
+    fn create_landing_coin_domain() -> Result<Domain> {
+        let mut domain = Domain::new("landing_coin");
+        domain.declare_nondet_action("toss", |state: &State, _args: &[crate::core::StateValue]| {
+            [("heads", "table"), ("heads", "floor")].into_iter()
+                .map(|(side, place)| {
+                    let mut outcome = state.copy(None);
+                    outcome.set_var("coin", "c", string_value(side));
+                    outcome.set_var("landed", "c", string_value(place));
+                    outcome
+                })
+                .collect()
+        })?;
+        domain.declare_action("place_heads", |state: &mut State, _args: &[crate::core::StateValue]| {
+            state.set_var("coin", "c", string_value("heads"));
+            state.set_var("landed", "c", string_value("table"));
+            Some(state.clone())
+        })?;
+        domain.declare_unigoal_method("coin", |_state: &State, _arg: &str, _value: &crate::core::StateValue| {
+            Some(vec![PlanItem::action("toss", vec![])])
+        })?;
+        domain.declare_unigoal_method("coin", |_state: &State, _arg: &str, _value: &crate::core::StateValue| {
+            Some(vec![PlanItem::action("place_heads", vec![])])
+        })?;
+        Ok(domain)
+    }
+
+    #[test]
+    fn test_conformant_outcomes_go_through_state_constraints() -> Result<()> {
+        let mut state = State::new("s");
+        state.set_var("coin", "c", string_value("tails"));
+        let todo = vec![PlanItem::unigoal("coin", "c", string_value("heads"))];
+        let planner = PlannerBuilder::new()
+            .with_domain(create_landing_coin_domain()?)
+            .with_verbose_level(0)?
+            .with_conformant(true)
+            .with_state_constraint(Arc::new(|state: &State| {
+                state.get_var("landed", "c").and_then(|place| place.as_str()) != Some("floor")
+            }))
+            .build()?;
+
+        // Every toss lands heads, but one of its outcomes is forbidden
+        assert_eq!(planner.find_plan(state, todo)?, Some(vec![PlanItem::action("place_heads", vec![])]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_conformant_mode_checks_goal_predicate_plans() -> Result<()> {
+        let mut domain = create_landing_coin_domain()?;
+        domain.declare_task_method("settle", |_state: &State, _args: &[crate::core::StateValue]| {
+            Some(vec![PlanItem::action("toss", vec![]), PlanItem::action("place_heads", vec![])])
+        })?;
+        let mut state = State::new("s");
+        state.set_var("coin", "c", string_value("tails"));
+        let on_table = |state: &State| state.get_var("landed", "c").and_then(|place| place.as_str()) == Some("table");
+        let planner = |conformant| PlannerBuilder::new()
+            .with_domain(domain.clone())
+            .with_verbose_level(0)?
+            .with_conformant(conformant)
+            .with_goal_predicate(Arc::new(on_table))
+            .build();
+        let toss = PlanItem::action("toss", vec![]);
+        let todo = vec![PlanItem::task("settle", vec![])];
+
+        // The predicate holds after the toss for its first outcome only
+        assert_eq!(planner(false)?.find_plan(state.clone(), todo.clone())?, Some(vec![toss.clone()]));
+        assert_eq!(planner(true)?.find_plan(state, todo)?, Some(vec![toss, PlanItem::action("place_heads", vec![])]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_best_partial_reports_prefix_before_blocked_item() -> Result<()> {
+        let planner = PlannerBuilder::new()
+            .with_domain(create_chain_domain()?)
+            .with_verbose_level(0)?
+            .build()?;
+        let step = PlanItem::action("step", vec![]);
+
+        let todo = vec![PlanItem::task("chain", vec![]), step.clone(), PlanItem::action("missing", vec![])];
+        let (plan, partial) = planner.find_best_partial(State::new("s"), todo)?;
+        assert_eq!(plan, None);
+        assert_eq!(partial, vec![step.clone(); 5]);
+
+        let (plan, partial) = planner.find_best_partial(State::new("s"), vec![PlanItem::task("chain", vec![])])?;
+        assert_eq!(plan, Some(vec![step.clone(); 4]));
+        assert_eq!(partial, vec![step; 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_invariant_checked_before_search() -> Result<()> {
+        use crate::domains::blocks::{install_blocks_actions, install_blocks_methods};
+
+        let mut domain = Domain::new("blocks");
+        install_blocks_actions(&mut domain)?;
+        install_blocks_methods(&mut domain)?;
+        domain.add_state_invariant(Arc::new(|state: &State| {
+            match state.get_var_map("holding").map_or(0, |holding| holding.len()) {
+                1 => Ok(()),
+                entries => Err(format!("expected one holding entry, found {entries}")),
+            }
+        }));
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+
+        let mut state = State::new("s");
+        state.set_var("pos", "a", string_value("table"));
+        state.set_var("clear", "a", true.into());
+        let todo = vec![PlanItem::task("take", vec![string_value("a")])];
+        let result = planner.find_plan(state.clone(), todo.clone());
+        assert!(matches!(
+            result,
+            Err(GTRustHopError::InvariantViolated { ref message }) if message == "expected one holding entry, found 0"
+        ));
+
+        state.set_var("holding", "hand", false.into());
+        assert_eq!(planner.find_plan(state, todo)?, Some(vec![PlanItem::action("pickup", vec![string_value("a")])]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_goal_predicate_stops_before_todo_list_is_done() -> Result<()> {
+        let count = |state: &State| state.get_var("count", "c").and_then(|value| value.as_i64()).unwrap_or(0);
+        let mut domain = Domain::new("counter");
+        domain.declare_action("inc", move |state: &mut State, _args: &[crate::core::StateValue]| {
+            let next = count(state) + 1;
+            state.set_var("count", "c", next.into());
+            Some(state.clone())
+        })?;
+        domain.declare_task_method("count_to_five", |_state: &State, _args: &[crate::core::StateValue]| {
+            Some(vec![PlanItem::action("inc", vec![]); 5])
+        })?;
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain.clone())
+            .with_verbose_level(0)?
+            .with_goal_predicate(Arc::new(move |state: &State| count(state) >= 3))
+            .build()?;
+        let plan = planner.find_plan(State::new("s"), vec![PlanItem::task("count_to_five", vec![])])?
+            .expect("the predicate is reached");
+        assert_eq!(plan, vec![PlanItem::action("inc", vec![]); 3]);
+        assert_eq!(count(&domain.apply_plan(State::new("s"), &plan)?), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_plan_parallel_layers_independent_towers() -> Result<()> {
+        use crate::core::StateValue;
+
+        // Blocks world without a hand, so blocks of different towers move independently
+        let mut domain = Domain::new("handless_blocks");
+        domain.declare_action("move", |state: &mut State, args: &[StateValue]| {
+            let (block, dest) = (args[0].as_str()?, args[1].as_str()?);
+            let is_clear = |state: &State, block: &str| state.get_var("clear", block) == Some(&true.into());
+            if !is_clear(state, block) || !is_clear(state, dest) {
+                return None;
+            }
+            let from = state.get_var("pos", block)?.as_str()?.to_string();
+            state.set_var("clear", &from, true.into());
+            state.set_var("pos", block, string_value(dest));
+            state.set_var("clear", dest, false.into());
+            Some(state.clone())
+        })?;
+        let moves = [("a", "b"), ("e", "a"), ("c", "d")];
+        domain.declare_task_method("build", move |_state: &State, _args: &[StateValue]| {
+            Some(moves.iter().map(|(block, dest)| PlanItem::action("move", vec![string_value(*block), string_value(*dest)])).collect())
+        })?;
+
+        let mut state = State::new("towers");
+        for block in ["a", "b", "c", "d", "e"] {
+            state.set_var("pos", block, string_value("table"));
+            state.set_var("clear", block, true.into());
+        }
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+
+        let layers = planner.find_plan_parallel(state, vec![PlanItem::task("build", vec![])])?.expect("the towers can be built");
+        let step = |block: &str, dest: &str| PlanItem::action("move", vec![string_value(block), string_value(dest)]);
+        assert_eq!(layers, vec![vec![step("a", "b"), step("c", "d")], vec![step("e", "a")]]);
+        assert!(layers.len() < moves.len());
+        Ok(())
+    }
+
+    /// Domain whose only action pays a taxi fare computed from rigid distances
+    fn create_fares_domain() -> Result<Domain> {
+        use crate::core::StateValue;
+
+        let mut domain = Domain::new("fares");
+        domain.declare_action_with_rigid("pay_driver", |state: &mut State, rigid: &State, args: &[StateValue]| {
+            let (person, from, to) = (args[0].as_str()?, args[1].as_str()?, args[2].as_str()?);
+            let dist = rigid.get_var("dist", &format!("{from}_{to}"))?.as_f64()?;
+            let fare = 1.5 + 0.5 * dist;
+            let cash = state.get_var("cash", person)?.as_f64()?;
+            if cash < fare {
+                return None;
+            }
+            state.set_var("cash", person, (cash - fare).into());
+            Some(state.clone())
+        })?;
+        Ok(domain)
+    }
+
+    #[test]
+    fn test_rigid_action_reads_planner_rigid_state() -> Result<()> {
+        use crate::domains::create_rigid_relations;
+
+        let domain = create_fares_domain()?;
+        let mut state = State::new("s");
+        state.set_var("cash", "alice", 20.0.into());
+        let pay = PlanItem::action("pay_driver", vec![string_value("alice"), string_value("home_a"), string_value("park")]);
+        // The 8 unit ride costs 1.5 + 0.5 * 8, leaving alice 14.5
+        let todo = vec![pay.clone(), PlanItem::unigoal("cash", "alice", 14.5.into())];
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain.clone())
+            .with_verbose_level(0)?
+            .with_rigid_state(create_rigid_relations())
+            .build()?;
+        assert_eq!(planner.find_plan(state.clone(), todo.clone())?, Some(vec![pay]));
+
+        // Without rigid facts there is no distance, so no fare
+        let planner = PlannerBuilder::new().with_domain(domain).with_verbose_level(0)?.build()?;
+        assert_eq!(planner.find_plan(state, todo)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rigid_action_replays_with_planner_rigid_state() -> Result<()> {
+        use crate::domains::create_rigid_relations;
+
+        let mut state = State::new("s");
+        state.set_var("cash", "alice", 20.0.into());
+        let pay = PlanItem::action("pay_driver", vec![string_value("alice"), string_value("home_a"), string_value("park")]);
+        let planner = PlannerBuilder::new()
+            .with_domain(create_fares_domain()?)
+            .with_verbose_level(0)?
+            .with_rigid_state(create_rigid_relations())
+            .build()?;
+
+        assert_eq!(planner.find_plan_parallel(state.clone(), vec![pay.clone()])?, Some(vec![vec![pay.clone()]]));
+        assert_eq!(planner.compact_plan(state.clone(), std::slice::from_ref(&pay))?, vec![pay.clone()]);
+
+        // Without a c_pay_driver command, acting applies the action with the rigid state
+        let final_state = planner.run_lazy_lookahead(state, vec![pay], 1)?;
+        assert_eq!(final_state.get_var("cash", "alice"), Some(&14.5.into()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_preference_avoids_backtracking() -> Result<()> {
+        use crate::core::StateValue;
+
+        let mut domain = Domain::new("door");
+        domain.declare_action("open_door", |state: &mut State, _args: &[StateValue]| {
+            state.set_var("door", "front", true.into());
+            Some(state.clone())
+        })?;
+        domain.declare_action("walk_through", |state: &mut State, _args: &[StateValue]| {
+            (state.get_var("door", "front") == Some(&true.into())).then(|| state.clone())
+        })?;
+        domain.declare_task_method("open", |_state: &State, _args: &[StateValue]| {
+            Some(vec![PlanItem::action("open_door", vec![])])
+        })?;
+        domain.declare_task_method("leave", |_state: &State, _args: &[StateValue]| {
+            Some(vec![PlanItem::action("walk_through", vec![])])
+        })?;
+
+        let mut state = State::new("s");
+        state.set_var("door", "front", false.into());
+        let todo = vec![PlanItem::unordered(vec![PlanItem::task("leave", vec![]), PlanItem::task("open", vec![])])];
+        let expected = Some(vec![PlanItem::action("open_door", vec![]), PlanItem::action("walk_through", vec![])]);
+
+        let builder = PlannerBuilder::new().with_domain(domain).with_verbose_level(0)?;
+        let (plan, unpreferred) = builder.clone().build()?.find_plan_with_stats(state.clone(), todo.clone())?;
+        assert_eq!(plan, expected);
+        let (plan, preferred) = builder.with_order_preference("open", "leave").build()?.find_plan_with_stats(state, todo)?;
+        assert_eq!(plan, expected);
+        assert!(preferred.nodes_expanded < unpreferred.nodes_expanded);
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_prefix_sharing() {
+        let parent = PlanPrefix::default().push(PlanItem::action("a", vec![]));
+        let left = parent.push(PlanItem::action("left", vec![]));
+        let right = parent.push(PlanItem::action("right", vec![]));
+
+        assert_eq!(parent.to_vec(), vec![PlanItem::action("a", vec![])]);
+        assert_eq!(left.to_vec(), vec![PlanItem::action("a", vec![]), PlanItem::action("left", vec![])]);
+        assert_eq!(right.to_vec(), vec![PlanItem::action("a", vec![]), PlanItem::action("right", vec![])]);
+    }
+
+    #[test]
+    fn test_sibling_branches_do_not_share_actions() -> Result<()> {
+        let mut domain = Domain::new("sibling_domain");
+        for name in ["first", "second"] {
+            domain.declare_action(name, |state: &mut State, _args: &[crate::core::StateValue]| {
+                Some(state.clone())
+            })?;
+        }
+        domain.declare_action("fail", |_state: &mut State, _args: &[crate::core::StateValue]| None)?;
+        // The first method records an action before failing, the second succeeds
+        domain.declare_task_method("work", |_state: &State, _args: &[crate::core::StateValue]| {
+            Some(vec![PlanItem::action("first", vec![]), PlanItem::action("fail", vec![])])
+        })?;
+        domain.declare_task_method("work", |_state: &State, _args: &[crate::core::StateValue]| {
+            Some(vec![PlanItem::action("second", vec![])])
+        })?;
+
+        for strategy in [PlanningStrategy::Iterative, PlanningStrategy::Recursive] {
+            let planner = PlannerBuilder::new()
+                .with_domain(domain.clone())
+                .with_strategy(strategy)
+                .build()?;
+            let plan = planner.find_plan(State::new("s"), vec![PlanItem::task("work", vec![])])?;
+            assert_eq!(plan, Some(vec![PlanItem::action("second", vec![])]));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_failure_cache_prunes_rederived_failures() -> Result<()> {
+        let mut domain = Domain::new("rederivation_domain");
+        domain.declare_action("noop", |state: &mut State, _args: &[crate::core::StateValue]| {
+            Some(state.clone())
+        })?;
+        // Two ways to do nothing, both leading back to the same state
+        for _ in 0..2 {
+            domain.declare_task_method("choose", |_state: &State, _args: &[crate::core::StateValue]| {
+                Some(vec![PlanItem::action("noop", vec![])])
+            })?;
+        }
+        domain.declare_task_method("impossible", |_state: &State, _args: &[crate::core::StateValue]| None)?;
+
+        let mut todo_list = vec![PlanItem::task("choose", vec![]); 6];
+        todo_list.push(PlanItem::task("impossible", vec![]));
+
+        for strategy in [PlanningStrategy::Iterative, PlanningStrategy::Recursive] {
+            let search = |failure_cache: bool| {
+                PlannerBuilder::new()
+                    .with_domain(domain.clone())
+                    .with_strategy(strategy)
+                    .with_failure_cache(failure_cache)
+                    .build()?
+                    .find_plan_with_stats(State::new("s"), todo_list.clone())
+            };
+
+            let (plan, uncached) = search(false)?;
+            assert!(plan.is_none());
+            let (plan, cached) = search(true)?;
+            assert!(plan.is_none());
+
+            // Without the cache every one of the 2^6 decompositions is explored
+            assert!(uncached.nodes_expanded > 200);
+            assert!(cached.nodes_expanded < 30);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_plan_after_committed_pickup() -> Result<()> {
+        use crate::examples::blocks_htn_example::create_blocks_htn_domain;
+
+        let planner = PlannerBuilder::new()
+            .with_domain(create_blocks_htn_domain()?)
+            .with_verbose_level(0)?
+            .build()?;
+
+        let mut state = State::new("table_top");
+        for block in ["a", "b"] {
+            state.set_var("pos", block, string_value("table"));
+            state.set_var("clear", block, true.into());
+        }
+        state.set_var("holding", "hand", false.into());
+
+        let pickup = PlanItem::action("pickup", vec![string_value("a")]);
+        let plan = planner.complete_plan(
+            state.clone(),
+            std::slice::from_ref(&pickup),
+            vec![PlanItem::task("put", vec![string_value("a"), string_value("b")])],
+        )?;
+        assert_eq!(plan, Some(vec![
+            pickup.clone(),
+            PlanItem::action("stack", vec![string_value("a"), string_value("b")]),
+        ]));
+
+        // Committing an inapplicable action is an error
+        let twice = [pickup.clone(), pickup];
+        let result = planner.complete_plan(state, &twice, vec![]);
+        assert!(matches!(result, Err(GTRustHopError::ActionNotApplicable { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_plan_horizon() -> Result<()> {
+        let planner = PlannerBuilder::new()
+            .with_domain(create_chain_domain()?)
+            .build()?;
+        let state = State::new("s");
+        let todo_list = vec![PlanItem::task("chain", vec![])];
+
+        let full = planner.find_plan(state.clone(), todo_list.clone())?.expect("chain is solvable");
+        let prefix = planner.find_plan_horizon(state.clone(), todo_list.clone(), 2)?.expect("chain is solvable");
+        assert_eq!(prefix.len(), 2);
+        assert_eq!(prefix[..], full[..2]);
+
+        // A horizon beyond the plan length returns the full plan
+        assert_eq!(planner.find_plan_horizon(state, todo_list, 10)?, Some(full));
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_lazy_lookahead_max_plan_len() -> Result<()> {
+        let mut domain = Domain::new("long_plan_domain");
+        domain.declare_action("tick", |state: &mut State, _args: &[crate::core::StateValue]| {
+            Some(state.clone())
+        })?;
+        domain.declare_task_method("busy", |_state: &State, _args: &[crate::core::StateValue]| {
+            Some(vec![PlanItem::action("tick", vec![]); 1000])
+        })?;
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+
+        let todo_list = vec![PlanItem::task("busy", vec![])];
+        let result = planner.run_lazy_lookahead_with_max_plan_len(State::new("s"), todo_list.clone(), 5, Some(10));
+        assert!(matches!(result, Err(GTRustHopError::PlanTooLong { len: 1000 })));
+
+        // A cap at the plan length lets the plan run
+        assert!(planner.run_lazy_lookahead_with_max_plan_len(State::new("s"), todo_list, 5, Some(1000)).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_one_of_alternative_goals() -> Result<()> {
+        let mut domain = Domain::new("travel_domain");
+        domain.declare_action("walk", |state: &mut State, args: &[crate::core::StateValue]| {
+            state.set_var("loc", args[0].as_str()?, args[1].clone());
+            Some(state.clone())
+        })?;
+        // Only the park is within walking distance
+        domain.declare_unigoal_method("loc", |_state: &State, arg: &str, value: &crate::core::StateValue| {
+            (value.as_str() == Some("park")).then(|| vec![PlanItem::action("walk", vec![string_value(arg), value.clone()])])
+        })?;
+
+        for strategy in [PlanningStrategy::Iterative, PlanningStrategy::Recursive] {
+            let planner = PlannerBuilder::new()
+                .with_domain(domain.clone())
+                .with_strategy(strategy)
+                .build()?;
+            let mut state = State::new("s");
+            state.set_var("loc", "alice", string_value("home"));
+
+            let reachable = PlanItem::one_of(vec![
+                PlanItem::unigoal("loc", "alice", string_value("station")),
+                PlanItem::unigoal("loc", "alice", string_value("park")),
+            ]);
+            let plan = planner.find_plan(state.clone(), vec![reachable])?;
+            assert_eq!(plan, Some(vec![PlanItem::action("walk", vec![string_value("alice"), string_value("park")])]));
+
+            let unreachable = PlanItem::one_of(vec![
+                PlanItem::unigoal("loc", "alice", string_value("station")),
+                PlanItem::unigoal("loc", "alice", string_value("airport")),
+            ]);
+            assert_eq!(planner.find_plan(state, vec![unreachable])?, None);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unordered_block_finds_working_order() -> Result<()> {
+        let mut domain = Domain::new("cooking_domain");
+        domain.declare_action("boil_water", |state: &mut State, _args: &[crate::core::StateValue]| {
+            state.set_var("water", "pot", string_value("boiling"));
+            Some(state.clone())
+        })?;
+        domain.declare_action("add_pasta", |state: &mut State, _args: &[crate::core::StateValue]| {
+            (state.get_var("water", "pot")?.as_str() == Some("boiling")).then(|| state.clone())
+        })?;
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .build()?;
+
+        // The listed order dead-ends, but the reverse order succeeds
+        let block = PlanItem::unordered(vec![
+            PlanItem::action("add_pasta", vec![]),
+            PlanItem::action("boil_water", vec![]),
+        ]);
+        let plan = planner.find_plan(State::new("kitchen"), vec![block])?;
+        assert_eq!(plan, Some(vec![
+            PlanItem::action("boil_water", vec![]),
+            PlanItem::action("add_pasta", vec![]),
+        ]));
+
+        // An empty block is a no-op
+        let empty = vec![PlanItem::unordered(vec![]), PlanItem::action("boil_water", vec![])];
+        assert_eq!(planner.find_plan(State::new("kitchen"), empty)?, Some(vec![PlanItem::action("boil_water", vec![])]));
+
+        // Blocks beyond the limit are rejected
+        let too_many = PlanItem::unordered(vec![PlanItem::action("boil_water", vec![]); MAX_UNORDERED_ITEMS + 1]);
+        let result = planner.find_plan(State::new("kitchen"), vec![too_many]);
+        assert!(matches!(result, Err(GTRustHopError::PlanningFailed { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_failure_cache_keeps_nodes_reaching_duplicate_plans() -> Result<()> {
+        let mut domain = Domain::new("duplicates_domain");
+        for name in ["a", "b", "c"] {
+            domain.declare_action(name, |state: &mut State, _args: &[crate::core::StateValue]| Some(state.clone()))?;
+        }
+        domain.declare_task_methods("top", vec![
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![PlanItem::action("a", vec![]), PlanItem::action("c", vec![])]),
+            // Reaches the plan above again, after which the node with todo [c] must not count as failed
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![PlanItem::action("a", vec![]), PlanItem::task("rest", vec![])]),
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![PlanItem::action("b", vec![]), PlanItem::task("rest", vec![])]),
+        ])?;
+        domain.declare_task_method("rest", |_state: &State, _args: &[crate::core::StateValue]| {
+            Some(vec![PlanItem::action("c", vec![])])
+        })?;
+
+        for strategy in [PlanningStrategy::Iterative, PlanningStrategy::Recursive] {
+            let planner = PlannerBuilder::new()
+                .with_domain(domain.clone())
+                .with_strategy(strategy)
+                .with_failure_cache(true)
+                .with_max_plans(2)
+                .with_verbose_level(0)?
+                .build()?;
+            let (plan, meta) = planner.find_plan_meta(State::new("s"), vec![PlanItem::task("top", vec![])])?;
+            assert_eq!(plan, Some(vec![PlanItem::action("a", vec![]), PlanItem::action("c", vec![])]));
+            assert!(!meta.unique, "{strategy:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_plan_meta_detects_second_plan() -> Result<()> {
+        let mut domain = Domain::new("travel_domain");
+        domain.declare_action("walk", |state: &mut State, _args: &[crate::core::StateValue]| Some(state.clone()))?;
+        domain.declare_action("ride", |state: &mut State, _args: &[crate::core::StateValue]| Some(state.clone()))?;
+        domain.declare_task_methods("travel", vec![
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![PlanItem::action("walk", vec![])]),
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![PlanItem::action("ride", vec![])]),
+        ])?;
+
+        for strategy in [PlanningStrategy::Iterative, PlanningStrategy::Recursive] {
+            let builder = PlannerBuilder::new()
+                .with_domain(domain.clone())
+                .with_strategy(strategy)
+                .with_verbose_level(0)?;
+            let todo = vec![PlanItem::task("travel", vec![])];
+
+            // By default the search stops at the first plan
+            let (plan, meta) = builder.clone().build()?.find_plan_meta(State::new("s"), todo.clone())?;
+            assert_eq!(plan, Some(vec![PlanItem::action("walk", vec![])]));
+            assert!(meta.unique);
+
+            let planner = builder.with_max_plans(2).build()?;
+            let (plan, meta) = planner.find_plan_meta(State::new("s"), todo.clone())?;
+            assert_eq!(plan, Some(vec![PlanItem::action("walk", vec![])]));
+            assert!(!meta.unique);
+            assert_eq!(planner.find_plan(State::new("s"), todo)?, plan);
+        }
+        Ok(())
+    }
+
+    /// Two `fix` methods that undo each other, and a third that actually finishes
+    fn create_oscillating_domain() -> Result<Domain> {
+        let mut domain = Domain::new("oscillating_domain");
+        for (name, on) in [("switch_on", true), ("switch_off", false)] {
+            domain.declare_action(name, move |state: &mut State, _args: &[crate::core::StateValue]| {
+                state.set_var("light", "lamp", on.into());
+                Some(state.clone())
+            })?;
+        }
+        domain.declare_action("finish", |state: &mut State, _args: &[crate::core::StateValue]| {
+            state.set_var("done", "lamp", true.into());
+            Some(state.clone())
+        })?;
+        domain.declare_task_methods("fix", vec![
+            |state: &State, _args: &[crate::core::StateValue]| {
+                (state.get_var("light", "lamp")?.as_bool() == Some(false))
+                    .then(|| vec![PlanItem::action("switch_on", vec![]), PlanItem::task("fix", vec![])])
+            },
+            |state: &State, _args: &[crate::core::StateValue]| {
+                (state.get_var("light", "lamp")?.as_bool() == Some(true))
+                    .then(|| vec![PlanItem::action("switch_off", vec![]), PlanItem::task("fix", vec![])])
+            },
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![PlanItem::action("finish", vec![])]),
+        ])?;
+        Ok(domain)
+    }
+
+    #[test]
+    fn test_thrashing_detection_cuts_oscillating_branch() -> Result<()> {
+        let mut state = State::new("s");
+        state.set_var("light", "lamp", false.into());
+
+        // Toggling the light never gets closer to being done
+        let heuristic: crate::planning::HeuristicFn = Arc::new(|state: &State| {
+            if state.get_var("done", "lamp").is_some() { 0.0 } else { 1.0 }
+        });
+
+        for strategy in [PlanningStrategy::Iterative, PlanningStrategy::Recursive] {
+            let planner = PlannerBuilder::new()
+                .with_domain(create_oscillating_domain()?)
+                .with_strategy(strategy)
+                .with_heuristic(Arc::clone(&heuristic))
+                .with_thrashing_detection(3)
+                .with_verbose_level(0)?
+                .build()?;
+
+            let (plan, stats) = planner.find_plan_with_stats(state.clone(), vec![PlanItem::task("fix", vec![])])?;
+            let plan = plan.expect("the finishing method is reached after the cut");
+            assert_eq!(plan.len(), 7);
+            assert_eq!(plan.last(), Some(&PlanItem::action("finish", vec![])));
+            assert_eq!(stats.thrashing_cuts, 1);
+        }
+
+        // Detection needs a heuristic to measure progress
+        let result = PlannerBuilder::new()
+            .with_domain(create_oscillating_domain()?)
+            .with_thrashing_detection(3)
+            .build();
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_constraint_routes_around_forbidden_state() -> Result<()> {
+        let mut domain = Domain::new("route_domain");
+        domain.declare_action("move", |state: &mut State, args: &[crate::core::StateValue]| {
+            state.set_var("loc", "robot", args.first()?.clone());
+            Some(state.clone())
+        })?;
+        domain.declare_task_methods("deliver", vec![
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![
+                PlanItem::action("move", vec![string_value("hazard_zone")]),
+                PlanItem::action("move", vec![string_value("dock")]),
+            ]),
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![
+                PlanItem::action("move", vec![string_value("corridor")]),
+                PlanItem::action("move", vec![string_value("dock")]),
+            ]),
+        ])?;
+
+        let mut state = State::new("s");
+        state.set_var("loc", "robot", string_value("start"));
+        let todo = vec![PlanItem::task("deliver", vec![])];
+
+        let builder = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?;
+        let shortcut = builder.clone().build()?.find_plan(state.clone(), todo.clone())?;
+        assert_eq!(shortcut.and_then(|plan| plan.first().cloned()), Some(PlanItem::action("move", vec![string_value("hazard_zone")])));
+
+        let planner = builder
+            .with_state_constraint(Arc::new(|state: &State| {
+                state.get_var("loc", "robot").and_then(|loc| loc.as_str()) != Some("hazard_zone")
+            }))
+            .build()?;
+        let plan = planner.find_plan(state, todo)?;
+        assert_eq!(plan, Some(vec![
+            PlanItem::action("move", vec![string_value("corridor")]),
+            PlanItem::action("move", vec![string_value("dock")]),
+        ]));
+        Ok(())
+    }
+
+    /// Domain whose `check` methods all start with `inspect`, applicable only while the lamp is on
+    fn create_inspection_domain(inspections: Arc<std::sync::atomic::AtomicUsize>) -> Result<Domain> {
+        let mut domain = Domain::new("inspection_domain");
+        domain.declare_action("inspect", move |state: &mut State, _args: &[crate::core::StateValue]| {
+            inspections.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            (state.get_var("lamp", "room")?.as_bool() == Some(true)).then(|| state.clone())
+        })?;
+        domain.declare_action("jam", |_state: &mut State, _args: &[crate::core::StateValue]| None)?;
+        domain.declare_action("wait", |state: &mut State, _args: &[crate::core::StateValue]| Some(state.clone()))?;
+        domain.declare_action("toggle", |state: &mut State, _args: &[crate::core::StateValue]| {
+            let lamp = state.get_var("lamp", "room")?.as_bool()?;
+            state.set_var("lamp", "room", (!lamp).into());
+            Some(state.clone())
+        })?;
+        domain.declare_task_methods("check", vec![
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![
+                PlanItem::action("inspect", vec![]),
+                PlanItem::action("jam", vec![]),
+            ]),
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![
+                PlanItem::action("inspect", vec![]),
+                PlanItem::action("wait", vec![]),
+            ]),
+            |_state: &State, _args: &[crate::core::StateValue]| Some(vec![PlanItem::action("wait", vec![])]),
+        ])?;
+        Ok(domain)
+    }
+
+    #[test]
+    fn test_action_cache_reuses_leading_action_within_frame() -> Result<()> {
+        let mut state = State::new("s");
+        state.set_var("lamp", "room", true.into());
+        let todo = vec![PlanItem::task("check", vec![]), PlanItem::task("check", vec![])];
+
+        let inspections = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let planner = PlannerBuilder::new()
+            .with_domain(create_inspection_domain(Arc::clone(&inspections))?)
+            .with_verbose_level(0)?
+            .build()?;
+        let (uncached, _) = planner.find_plan_with_stats(state.clone(), todo.clone())?;
+        let uncached_inspections = inspections.swap(0, std::sync::atomic::Ordering::SeqCst);
+
+        let planner = PlannerBuilder::new()
+            .with_domain(create_inspection_domain(Arc::clone(&inspections))?)
+            .with_verbose_level(0)?
+            .with_action_cache(true)
+            .build()?;
+        let (cached, stats) = planner.find_plan_with_stats(state, todo)?;
+        assert_eq!(cached, uncached);
+        assert_eq!(stats.action_cache_hits, 2);
+        assert!(inspections.load(std::sync::atomic::Ordering::SeqCst) < uncached_inspections);
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_cache_does_not_leak_between_states() -> Result<()> {
+        let mut state = State::new("s");
+        state.set_var("lamp", "room", true.into());
+        let todo = vec![
+            PlanItem::task("check", vec![]),
+            PlanItem::action("toggle", vec![]),
+            PlanItem::task("check", vec![]),
+        ];
+
+        let inspections = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let planner = PlannerBuilder::new()
+            .with_domain(create_inspection_domain(Arc::clone(&inspections))?)
+            .with_verbose_level(0)?
+            .with_action_cache(true)
+            .build()?;
+        let plan = planner.find_plan(state, todo)?;
+        // With the lamp off, the second `check` must re-evaluate `inspect` and fall back to `wait`
+        assert_eq!(plan, Some(vec![
+            PlanItem::action("inspect", vec![]),
+            PlanItem::action("wait", vec![]),
+            PlanItem::action("toggle", vec![]),
+            PlanItem::action("wait", vec![]),
+        ]));
+        assert_eq!(inspections.load(std::sync::atomic::Ordering::SeqCst), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_lazy_lookahead_lenient_skips_failed_command() -> Result<()> {
+        let mut domain = Domain::new("notify_domain");
+        domain.declare_action("notify", |state: &mut State, _args: &[crate::core::StateValue]| Some(state.clone()))?;
+        domain.declare_command("c_notify", |_state: &mut State, _args: &[crate::core::StateValue]| None)?;
+        domain.declare_action("move", |state: &mut State, args: &[crate::core::StateValue]| {
+            state.set_var("loc", "robot", args.first()?.clone());
+            Some(state.clone())
+        })?;
+        domain.declare_unigoal_method("loc", |_state: &State, _arg: &str, value: &crate::core::StateValue| {
+            Some(vec![PlanItem::action("notify", vec![]), PlanItem::action("move", vec![value.clone()])])
+        })?;
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+
+        let mut state = State::new("s");
+        state.set_var("loc", "robot", string_value("start"));
+        let todo = vec![PlanItem::unigoal("loc", "robot", string_value("dock"))];
+
+        // The failing notification blocks every attempt
+        let strict = planner.run_lazy_lookahead(state.clone(), todo.clone(), 3)?;
+        assert_eq!(strict.get_var("loc", "robot"), Some(&string_value("start")));
+
+        let lenient = HashSet::from(["notify".to_string()]);
+        let result = planner.run_lazy_lookahead_lenient(state, todo, 3, &lenient)?;
+        assert_eq!(result.get_var("loc", "robot"), Some(&string_value("dock")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_lazy_lookahead_deadline_missed() -> Result<()> {
+        let mut domain = Domain::new("rover_domain");
+        domain.declare_action("move", |state: &mut State, args: &[crate::core::StateValue]| {
+            state.set_var("loc", args.first()?.as_str()?, args.get(1)?.clone());
+            Some(state.clone())
+        })?;
+        // Nothing can reach the moon
+        domain.declare_unigoal_method("loc", |_state: &State, arg: &str, value: &crate::core::StateValue| {
+            (value.as_str() != Some("moon")).then(|| vec![PlanItem::action("move", vec![string_value(arg), value.clone()])])
+        })?;
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+
+        let mut state = State::new("s");
+        state.set_var("loc", "rover1", string_value("base"));
+        state.set_var("loc", "rover2", string_value("base"));
+
+        let mut goals = Multigoal::new("goals");
+        goals.set_goal("loc", "rover1", string_value("dock"));
+        goals.set_goal("loc", "rover2", string_value("moon"));
+        goals.set_deadline("loc", "rover2", 2);
+
+        let result = planner.run_lazy_lookahead(state.clone(), vec![PlanItem::multigoal(goals.clone())], 5);
+        assert_eq!(result, Err(GTRustHopError::deadline_missed("loc[rover2]")));
+
+        // A reachable deadlined goal is met
+        goals.set_goal("loc", "rover2", string_value("dock"));
+        let final_state = planner.run_lazy_lookahead(state, vec![PlanItem::multigoal(goals)], 5)?;
+        assert_eq!(final_state.get_var("loc", "rover2"), Some(&string_value("dock")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_lazy_lookahead_command_policy() -> Result<()> {
+        let mut domain = Domain::new("rover_domain");
+        domain.declare_action("move", |state: &mut State, args: &[crate::core::StateValue]| {
+            state.set_var("loc", args.first()?.as_str()?, args.get(1)?.clone());
+            Some(state.clone())
+        })?;
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+
+        let mut state = State::new("s");
+        state.set_var("loc", "rover1", string_value("base"));
+        let todo = vec![PlanItem::action("move", vec![string_value("rover1"), string_value("dock")])];
+
+        let strict = planner.run_lazy_lookahead_with_command_policy(state.clone(), todo.clone(), 3, CommandPolicy::Strict);
+        assert_eq!(strict, Err(GTRustHopError::missing_command("move")));
+
+        let final_state = planner.run_lazy_lookahead_with_command_policy(state, todo, 3, CommandPolicy::Fallback)?;
+        assert_eq!(final_state.get_var("loc", "rover1"), Some(&string_value("dock")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_lazy_lookahead_rejects_wrongly_empty_multigoal_plan() -> Result<()> {
+        let mut domain = Domain::new("rover_domain");
+        domain.declare_action("move", |state: &mut State, args: &[crate::core::StateValue]| {
+            state.set_var("loc", args.first()?.as_str()?, args.get(1)?.clone());
+            Some(state.clone())
+        })?;
+        // Claims every multigoal is already achieved
+        domain.declare_multigoal_method(|_state: &State, _multigoal: &Multigoal| Some(vec![]))?;
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_goal_verification(false)
+            .with_verbose_level(0)?
+            .build()?;
+
+        let mut state = State::new("s");
+        state.set_var("loc", "rover1", string_value("base"));
+        let mut goals = Multigoal::new("goals");
+        goals.set_goal("loc", "rover1", string_value("dock"));
+
+        let result = planner.run_lazy_lookahead(state, vec![PlanItem::multigoal(goals)], 3);
+        assert_eq!(result, Err(GTRustHopError::acting_incomplete(vec!["(loc rover1 dock)".to_string()])));
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_lazy_lookahead_stops_once_goal_holds() -> Result<()> {
+        let mut domain = Domain::new("conveyor_domain");
+        domain.declare_action("move", |state: &mut State, args: &[crate::core::StateValue]| {
+            state.set_var("loc", "robot", args.first()?.clone());
+            Some(state.clone())
+        })?;
+        // A conveyor in the hall carries the robot on to the dock
+        domain.declare_command("c_move", |state: &mut State, args: &[crate::core::StateValue]| {
+            let moves = state.get_var("moves", "robot").and_then(|moves| moves.as_i64()).unwrap_or(0);
+            state.set_var("moves", "robot", (moves + 1).into());
+            let destination = args.first()?.clone();
+            let reached = if destination == string_value("hall") { string_value("dock") } else { destination };
+            state.set_var("loc", "robot", reached);
+            Some(state.clone())
+        })?;
+        domain.declare_unigoal_method("loc", |_state: &State, _arg: &str, value: &crate::core::StateValue| {
+            Some(vec![PlanItem::action("move", vec![string_value("hall")]), PlanItem::action("move", vec![value.clone()])])
+        })?;
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+
+        let mut state = State::new("s");
+        state.set_var("loc", "robot", string_value("start"));
+        let todo = vec![PlanItem::unigoal("loc", "robot", string_value("dock"))];
+
+        let final_state = planner.run_lazy_lookahead(state, todo, 3)?;
+        assert_eq!(final_state.get_var("loc", "robot"), Some(&string_value("dock")));
+        assert_eq!(final_state.get_var("moves", "robot"), Some(&1.into()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_choices_backtracks_over_one_of_alternatives() -> Result<()> {
+        use crate::core::StateValue;
+
+        let mut domain = Domain::new("errands");
+        domain.declare_action("walk", |state: &mut State, args: &[StateValue]| {
+            state.set_var("loc", "robot", args.first()?.clone());
+            Some(state.clone())
+        })?;
+        // The bridge is out, so crossing it fails
+        domain.declare_task_method("cross_bridge", |_state: &State, _args: &[StateValue]| {
+            Some(vec![PlanItem::action("swim", vec![])])
+        })?;
+        domain.declare_task_method("take_tunnel", |_state: &State, _args: &[StateValue]| {
+            Some(vec![PlanItem::action("walk", vec![string_value("town")])])
+        })?;
+
+        let planner = PlannerBuilder::new()
+            .with_domain(domain)
+            .with_verbose_level(0)?
+            .build()?;
+        let todo = vec![PlanItem::one_of(vec![
+            PlanItem::task("cross_bridge", vec![]),
+            PlanItem::task("take_tunnel", vec![]),
+        ])];
+        let (plan, choices) = planner.find_plan_with_choices(State::new("s"), todo.clone())?;
+        assert_eq!(choices, vec![("take_tunnel".to_string(), 0)]);
+
+        // The failing first alternative decomposes another task than the trace names
+        assert_eq!(planner.replay_choices(State::new("s"), todo.clone(), &choices)?, plan);
+        assert!(planner.replay_choices(State::new("s"), todo, &[("take_tunnel".to_string(), 1)]).is_err());
+        Ok(())
+    }
 }
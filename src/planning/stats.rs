@@ -0,0 +1,112 @@
+//! Search statistics for GTRusthop planner instances
+
+use crate::compat::prelude::*;
+use crate::core::Plan;
+use alloc::sync::Arc;
+use core::time::Duration;
+
+/// Snapshot of the work performed by a single `find_plan` search
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlanningStats {
+    /// Number of search nodes (state/todo-list pairs) expanded so far
+    pub nodes_expanded: usize,
+    /// Depth of the most recently expanded node
+    pub depth: usize,
+    /// Deepest depth reached by any expanded node
+    pub max_depth: usize,
+    /// Wall-clock time elapsed since the search started, zero without the `std` feature
+    pub elapsed: Duration,
+    /// Number of branches abandoned by thrashing detection
+    pub thrashing_cuts: usize,
+    /// Number of leading actions whose result was reused within a task expansion
+    pub action_cache_hits: usize,
+    /// For each method choice leading to the first plan, the `(task or goal name, count)`
+    /// of applicable methods after the chosen one, untried when that plan was found
+    ///
+    /// A high count hints at alternative, maybe shorter, decompositions.
+    pub untried_methods: Vec<(String, usize)>,
+}
+
+impl PlanningStats {
+    /// Create an empty statistics record
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Extra information about the plan returned by `Planner::find_plan_meta()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlanMeta {
+    /// `false` if the search found a second, different plan
+    ///
+    /// Only searches allowed to look for more than one plan (see
+    /// `PlannerBuilder::with_max_plans()`) can detect this.
+    pub unique: bool,
+}
+
+/// Plans found by each search strategy, returned by `Planner::compare_strategies()`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrategyComparison {
+    /// Result of the iterative search
+    pub iterative: Option<Plan>,
+    /// Result of the recursive search
+    pub recursive: Option<Plan>,
+    /// `true` if both strategies found a plan or both failed
+    pub agree: bool,
+}
+
+impl StrategyComparison {
+    /// Check whether both plans contain the same actions, in any order
+    ///
+    /// Also `true` if both strategies failed.
+    pub fn same_actions(&self) -> bool {
+        let sorted = |plan: &Plan| {
+            let mut actions: Vec<String> = plan.iter().map(ToString::to_string).collect();
+            actions.sort();
+            actions
+        };
+        match (&self.iterative, &self.recursive) {
+            (Some(iterative), Some(recursive)) => sorted(iterative) == sorted(recursive),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Type alias for progress callbacks
+/// Progress callbacks receive a snapshot of the current search statistics
+pub type ProgressCallback = Arc<dyn Fn(&PlanningStats) + Send + Sync>;
+
+/// A progress callback together with how often it should fire
+#[derive(Clone)]
+pub struct ProgressReporter {
+    /// Number of node expansions between two callback invocations
+    pub every: usize,
+    /// Callback invoked with the current statistics
+    pub callback: ProgressCallback,
+}
+
+impl ProgressReporter {
+    /// Create a reporter firing every `every` node expansions (at least 1)
+    pub fn new(every: usize, callback: ProgressCallback) -> Self {
+        Self {
+            every: every.max(1),
+            callback,
+        }
+    }
+
+    /// Invoke the callback if the expansion count is a multiple of `every`
+    pub fn report(&self, stats: &PlanningStats) {
+        if stats.nodes_expanded.is_multiple_of(self.every) {
+            (self.callback)(stats);
+        }
+    }
+}
+
+impl core::fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ProgressReporter")
+            .field("every", &self.every)
+            .finish()
+    }
+}
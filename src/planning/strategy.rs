@@ -1,8 +1,10 @@
 //! Planning strategy implementations for GTRusthop
 
-use super::{PlanningContext, PlanningResult, PlanningStrategyTrait, is_verbose, verbose_print, item_to_string, todo_list_to_string};
+use crate::compat::prelude::*;
+use super::{PlanningContext, PlanningResult, PlanningStrategyTrait, item_to_string, todo_list_to_string};
 use crate::core::{State, Multigoal, PlanItem, TodoList, Plan, StateValue};
 use crate::error::{GTRustHopError, Result};
+#[cfg(feature = "std")]
 use std::sync::Mutex;
 
 /// Parameters for action-related planning operations
@@ -42,12 +44,17 @@ pub enum PlanningStrategy {
     Recursive,
     /// Iterative planning strategy (uses explicit stack)
     Iterative,
+    /// Recursive planning strategy that switches to the iterative one past the
+    /// planner's recursion limit, see `PlannerBuilder::with_recursion_limit()`
+    Auto,
 }
 
 /// Global planning strategy
+#[cfg(feature = "std")]
 static CURRENT_STRATEGY: Mutex<Option<PlanningStrategy>> = Mutex::new(None);
 
 /// Set the current planning strategy
+#[cfg(feature = "std")]
 pub fn set_planning_strategy(strategy: PlanningStrategy) {
     let mut current = CURRENT_STRATEGY.lock().unwrap();
     *current = Some(strategy);
@@ -55,16 +62,19 @@ pub fn set_planning_strategy(strategy: PlanningStrategy) {
     match strategy {
         PlanningStrategy::Recursive => println!("Using recursive seek_plan."),
         PlanningStrategy::Iterative => println!("Using iterative seek_plan."),
+        PlanningStrategy::Auto => println!("Using recursive seek_plan, falling back to iterative."),
     }
 }
 
 /// Get the current planning strategy
+#[cfg(feature = "std")]
 pub fn get_planning_strategy() -> Result<PlanningStrategy> {
     let current = CURRENT_STRATEGY.lock().unwrap();
     current.ok_or_else(|| GTRustHopError::NoPlanningStrategy)
 }
 
 /// Reset the planning strategy (force user to set it again)
+#[cfg(feature = "std")]
 pub fn reset_planning_strategy() {
     let mut current = CURRENT_STRATEGY.lock().unwrap();
     *current = None;
@@ -82,15 +92,15 @@ impl PlanningStrategyTrait for RecursiveStrategy {
         plan: Plan,
         depth: usize,
     ) -> Result<PlanningResult> {
-        if is_verbose(2) {
+        if context.is_verbose(2) {
             let todo_string = todo_list_to_string(&todo_list);
-            verbose_print(2, &format!("depth {depth} todo_list {todo_string}"));
+            context.verbose_print(2, &format!("depth {depth} todo_list {todo_string}"));
         }
 
         // Base case: empty todo list means we're done
         if todo_list.is_empty() {
-            if is_verbose(3) {
-                verbose_print(3, &format!("depth {depth} no more tasks or goals, return plan"));
+            if context.is_verbose(3) {
+                context.verbose_print(3, &format!("depth {depth} no more tasks or goals, return plan"));
             }
             return Ok(PlanningResult::Success(plan));
         }
@@ -129,6 +139,10 @@ impl PlanningStrategyTrait for RecursiveStrategy {
                     Err(GTRustHopError::invalid_item_type(item_to_string(item), depth))
                 }
             }
+            // Relational goals, disjunctions, unordered blocks, task patterns and maintained goals are only supported by the instance-based planner
+            PlanItem::RelGoal { .. } | PlanItem::OneOf(_) | PlanItem::Unordered(_) | PlanItem::TaskPattern(_, _) | PlanItem::Maintain(_) => {
+                Err(GTRustHopError::invalid_item_type(item_to_string(item), depth))
+            }
         }
     }
 }
@@ -143,15 +157,15 @@ impl RecursiveStrategy {
     ) -> Result<PlanningResult> {
         let PlanningState { todo_list, mut plan, depth } = planning_state;
 
-        if is_verbose(3) {
-            verbose_print(3, &format!("depth {depth} action {}: ", action_params.action_name));
+        if context.is_verbose(3) {
+            context.verbose_print(3, &format!("depth {depth} action {}: ", action_params.action_name));
         }
 
         if let Some(action_fn) = context.domain.get_action(action_params.action_name) {
             let mut new_state = state.copy(None);
             if let Some(result_state) = action_fn(&mut new_state, action_params.args) {
-                if is_verbose(3) {
-                    verbose_print(3, "applied");
+                if context.is_verbose(3) {
+                    context.verbose_print(3, "applied");
                     result_state.display(None);
                 }
                 plan.push(PlanItem::action(action_params.action_name, action_params.args.to_vec()));
@@ -159,8 +173,8 @@ impl RecursiveStrategy {
             }
         }
 
-        if is_verbose(3) {
-            verbose_print(3, "not applicable");
+        if context.is_verbose(3) {
+            context.verbose_print(3, "not applicable");
         }
         Ok(PlanningResult::Failure)
     }
@@ -175,19 +189,19 @@ impl RecursiveStrategy {
         let PlanningState { todo_list, plan, depth } = planning_state;
 
         if let Some(methods) = context.domain.get_task_methods(task_params.task_name) {
-            if is_verbose(3) {
-                verbose_print(3, &format!("depth {} task {} methods: {} methods", depth, task_params.task_name, methods.len()));
+            if context.is_verbose(3) {
+                context.verbose_print(3, &format!("depth {} task {} methods: {} methods", depth, task_params.task_name, methods.len()));
             }
 
             for method in methods {
-                if is_verbose(3) {
-                    verbose_print(3, &format!("depth {depth} trying method: "));
+                if context.is_verbose(3) {
+                    context.verbose_print(3, &format!("depth {depth} trying method: "));
                 }
 
                 if let Some(subtasks) = method(state, task_params.args) {
-                    if is_verbose(3) {
-                        verbose_print(3, "applicable");
-                        verbose_print(3, &format!("depth {} subtasks: {}", depth, todo_list_to_string(&subtasks)));
+                    if context.is_verbose(3) {
+                        context.verbose_print(3, "applicable");
+                        context.verbose_print(3, &format!("depth {} subtasks: {}", depth, todo_list_to_string(&subtasks)));
                     }
 
                     let mut new_todo = subtasks;
@@ -197,14 +211,14 @@ impl RecursiveStrategy {
                     if let PlanningResult::Success(_) = result {
                         return Ok(result);
                     }
-                } else if is_verbose(3) {
-                    verbose_print(3, "not applicable");
+                } else if context.is_verbose(3) {
+                    context.verbose_print(3, "not applicable");
                 }
             }
         }
 
-        if is_verbose(3) {
-            verbose_print(3, &format!("depth {depth} could not accomplish task {}", task_params.task_name));
+        if context.is_verbose(3) {
+            context.verbose_print(3, &format!("depth {depth} could not accomplish task {}", task_params.task_name));
         }
         Ok(PlanningResult::Failure)
     }
@@ -218,32 +232,32 @@ impl RecursiveStrategy {
     ) -> Result<PlanningResult> {
         let PlanningState { todo_list, plan, depth } = planning_state;
 
-        if is_verbose(3) {
-            verbose_print(3, &format!("depth {depth} goal ({} {} {}): ", unigoal_params.var_name, unigoal_params.arg, unigoal_params.value));
+        if context.is_verbose(3) {
+            context.verbose_print(3, &format!("depth {depth} goal ({} {} {}): ", unigoal_params.var_name, unigoal_params.arg, unigoal_params.value));
         }
 
         // Check if goal is already achieved
         if state.satisfies_unigoal(unigoal_params.var_name, unigoal_params.arg, unigoal_params.value) {
-            if is_verbose(3) {
-                verbose_print(3, "already achieved");
+            if context.is_verbose(3) {
+                context.verbose_print(3, "already achieved");
             }
             return self.seek_plan(context, state.clone(), todo_list, plan, depth + 1);
         }
 
         if let Some(methods) = context.domain.get_unigoal_methods(unigoal_params.var_name) {
-            if is_verbose(3) {
-                verbose_print(3, &format!("methods: {} methods", methods.len()));
+            if context.is_verbose(3) {
+                context.verbose_print(3, &format!("methods: {} methods", methods.len()));
             }
 
             for method in methods {
-                if is_verbose(3) {
-                    verbose_print(3, &format!("depth {depth} trying method: "));
+                if context.is_verbose(3) {
+                    context.verbose_print(3, &format!("depth {depth} trying method: "));
                 }
 
                 if let Some(subgoals) = method(state, unigoal_params.arg, unigoal_params.value) {
-                    if is_verbose(3) {
-                        verbose_print(3, "applicable");
-                        verbose_print(3, &format!("depth {} subgoals: {}", depth, todo_list_to_string(&subgoals)));
+                    if context.is_verbose(3) {
+                        context.verbose_print(3, "applicable");
+                        context.verbose_print(3, &format!("depth {} subgoals: {}", depth, todo_list_to_string(&subgoals)));
                     }
 
                     let mut new_todo = subgoals;
@@ -266,14 +280,14 @@ impl RecursiveStrategy {
                     if let PlanningResult::Success(_) = result {
                         return Ok(result);
                     }
-                } else if is_verbose(3) {
-                    verbose_print(3, "not applicable");
+                } else if context.is_verbose(3) {
+                    context.verbose_print(3, "not applicable");
                 }
             }
         }
 
-        if is_verbose(3) {
-            verbose_print(3, &format!("depth {depth} could not achieve goal ({} {} {})", unigoal_params.var_name, unigoal_params.arg, unigoal_params.value));
+        if context.is_verbose(3) {
+            context.verbose_print(3, &format!("depth {depth} could not achieve goal ({} {} {})", unigoal_params.var_name, unigoal_params.arg, unigoal_params.value));
         }
         Ok(PlanningResult::Failure)
     }
@@ -287,24 +301,24 @@ impl RecursiveStrategy {
         plan: Plan,
         depth: usize,
     ) -> Result<PlanningResult> {
-        if is_verbose(3) {
-            verbose_print(3, &format!("depth {depth} multigoal {multigoal}: "));
+        if context.is_verbose(3) {
+            context.verbose_print(3, &format!("depth {depth} multigoal {multigoal}: "));
         }
 
-        let methods = context.domain.get_multigoal_methods();
-        if is_verbose(3) {
-            verbose_print(3, &format!("methods: {} methods", methods.len()));
+        let methods = context.domain.relevant_multigoal_methods(multigoal);
+        if context.is_verbose(3) {
+            context.verbose_print(3, &format!("methods: {} methods", methods.len()));
         }
 
         for method in methods {
-            if is_verbose(3) {
-                verbose_print(3, &format!("depth {depth} trying method: "));
+            if context.is_verbose(3) {
+                context.verbose_print(3, &format!("depth {depth} trying method: "));
             }
 
             if let Some(subgoals) = method(state, multigoal) {
-                if is_verbose(3) {
-                    verbose_print(3, "applicable");
-                    verbose_print(3, &format!("depth {} subgoals: {}", depth, todo_list_to_string(&subgoals)));
+                if context.is_verbose(3) {
+                    context.verbose_print(3, "applicable");
+                    context.verbose_print(3, &format!("depth {} subgoals: {}", depth, todo_list_to_string(&subgoals)));
                 }
 
                 let mut new_todo = subgoals;
@@ -325,13 +339,13 @@ impl RecursiveStrategy {
                 if let PlanningResult::Success(_) = result {
                     return Ok(result);
                 }
-            } else if is_verbose(3) {
-                verbose_print(3, "not applicable");
+            } else if context.is_verbose(3) {
+                context.verbose_print(3, "not applicable");
             }
         }
 
-        if is_verbose(3) {
-            verbose_print(3, &format!("depth {depth} could not achieve multigoal {multigoal}"));
+        if context.is_verbose(3) {
+            context.verbose_print(3, &format!("depth {depth} could not achieve multigoal {multigoal}"));
         }
         Ok(PlanningResult::Failure)
     }
@@ -352,15 +366,15 @@ impl PlanningStrategyTrait for IterativeStrategy {
         let mut stack = vec![(initial_state, initial_todo_list, initial_plan, initial_depth)];
 
         while let Some((state, todo_list, plan, depth)) = stack.pop() {
-            if is_verbose(2) {
+            if context.is_verbose(2) {
                 let todo_string = todo_list_to_string(&todo_list);
-                verbose_print(2, &format!("depth {depth} todo_list {todo_string}"));
+                context.verbose_print(2, &format!("depth {depth} todo_list {todo_string}"));
             }
 
             // Base case: empty todo list means we're done
             if todo_list.is_empty() {
-                if is_verbose(3) {
-                    verbose_print(3, &format!("depth {depth} no more tasks or goals, return plan"));
+                if context.is_verbose(3) {
+                    context.verbose_print(3, &format!("depth {depth} no more tasks or goals, return plan"));
                 }
                 return Ok(PlanningResult::Success(plan));
             }
@@ -409,6 +423,10 @@ impl PlanningStrategyTrait for IterativeStrategy {
                         return Err(GTRustHopError::invalid_item_type(item_to_string(item), depth));
                     }
                 }
+                // Relational goals, disjunctions, unordered blocks, task patterns and maintained goals are only supported by the instance-based planner
+                PlanItem::RelGoal { .. } | PlanItem::OneOf(_) | PlanItem::Unordered(_) | PlanItem::TaskPattern(_, _) | PlanItem::Maintain(_) => {
+                    return Err(GTRustHopError::invalid_item_type(item_to_string(item), depth));
+                }
             }
         }
 
@@ -426,15 +444,15 @@ impl IterativeStrategy {
     ) -> Result<Option<(State, TodoList, Plan, usize)>> {
         let PlanningState { todo_list, mut plan, depth } = planning_state;
 
-        if is_verbose(3) {
-            verbose_print(3, &format!("depth {depth} action {}: ", action_params.action_name));
+        if context.is_verbose(3) {
+            context.verbose_print(3, &format!("depth {depth} action {}: ", action_params.action_name));
         }
 
         if let Some(action_fn) = context.domain.get_action(action_params.action_name) {
             let mut new_state = state.copy(None);
             if let Some(result_state) = action_fn(&mut new_state, action_params.args) {
-                if is_verbose(3) {
-                    verbose_print(3, "applied");
+                if context.is_verbose(3) {
+                    context.verbose_print(3, "applied");
                     result_state.display(None);
                 }
                 plan.push(PlanItem::action(action_params.action_name, action_params.args.to_vec()));
@@ -442,8 +460,8 @@ impl IterativeStrategy {
             }
         }
 
-        if is_verbose(3) {
-            verbose_print(3, "not applicable");
+        if context.is_verbose(3) {
+            context.verbose_print(3, "not applicable");
         }
         Ok(None)
     }
@@ -458,33 +476,33 @@ impl IterativeStrategy {
         let PlanningState { todo_list, plan, depth } = planning_state;
 
         if let Some(methods) = context.domain.get_task_methods(task_params.task_name) {
-            if is_verbose(3) {
-                verbose_print(3, &format!("depth {} task {} methods: {} methods", depth, task_params.task_name, methods.len()));
+            if context.is_verbose(3) {
+                context.verbose_print(3, &format!("depth {} task {} methods: {} methods", depth, task_params.task_name, methods.len()));
             }
 
             for method in methods {
-                if is_verbose(3) {
-                    verbose_print(3, &format!("depth {depth} trying method: "));
+                if context.is_verbose(3) {
+                    context.verbose_print(3, &format!("depth {depth} trying method: "));
                 }
 
                 if let Some(subtasks) = method(state, task_params.args) {
-                    if is_verbose(3) {
-                        verbose_print(3, "applicable");
-                        verbose_print(3, &format!("depth {} subtasks: {}", depth, todo_list_to_string(&subtasks)));
+                    if context.is_verbose(3) {
+                        context.verbose_print(3, "applicable");
+                        context.verbose_print(3, &format!("depth {} subtasks: {}", depth, todo_list_to_string(&subtasks)));
                     }
 
                     let mut new_todo = subtasks;
                     new_todo.extend(todo_list);
                     
                     return Ok(Some((state.clone(), new_todo, plan, depth + 1)));
-                } else if is_verbose(3) {
-                    verbose_print(3, "not applicable");
+                } else if context.is_verbose(3) {
+                    context.verbose_print(3, "not applicable");
                 }
             }
         }
 
-        if is_verbose(3) {
-            verbose_print(3, &format!("depth {depth} could not accomplish task {}", task_params.task_name));
+        if context.is_verbose(3) {
+            context.verbose_print(3, &format!("depth {depth} could not accomplish task {}", task_params.task_name));
         }
         Ok(None)
     }
@@ -498,32 +516,32 @@ impl IterativeStrategy {
     ) -> Result<Option<(State, TodoList, Plan, usize)>> {
         let PlanningState { todo_list, plan, depth } = planning_state;
 
-        if is_verbose(3) {
-            verbose_print(3, &format!("depth {depth} goal ({} {} {}): ", unigoal_params.var_name, unigoal_params.arg, unigoal_params.value));
+        if context.is_verbose(3) {
+            context.verbose_print(3, &format!("depth {depth} goal ({} {} {}): ", unigoal_params.var_name, unigoal_params.arg, unigoal_params.value));
         }
 
         // Check if goal is already achieved
         if state.satisfies_unigoal(unigoal_params.var_name, unigoal_params.arg, unigoal_params.value) {
-            if is_verbose(3) {
-                verbose_print(3, "already achieved");
+            if context.is_verbose(3) {
+                context.verbose_print(3, "already achieved");
             }
             return Ok(Some((state.clone(), todo_list, plan, depth + 1)));
         }
 
         if let Some(methods) = context.domain.get_unigoal_methods(unigoal_params.var_name) {
-            if is_verbose(3) {
-                verbose_print(3, &format!("methods: {} methods", methods.len()));
+            if context.is_verbose(3) {
+                context.verbose_print(3, &format!("methods: {} methods", methods.len()));
             }
 
             for method in methods {
-                if is_verbose(3) {
-                    verbose_print(3, &format!("depth {depth} trying method: "));
+                if context.is_verbose(3) {
+                    context.verbose_print(3, &format!("depth {depth} trying method: "));
                 }
 
                 if let Some(subgoals) = method(state, unigoal_params.arg, unigoal_params.value) {
-                    if is_verbose(3) {
-                        verbose_print(3, "applicable");
-                        verbose_print(3, &format!("depth {} subgoals: {}", depth, todo_list_to_string(&subgoals)));
+                    if context.is_verbose(3) {
+                        context.verbose_print(3, "applicable");
+                        context.verbose_print(3, &format!("depth {} subgoals: {}", depth, todo_list_to_string(&subgoals)));
                     }
 
                     let mut new_todo = subgoals;
@@ -543,14 +561,14 @@ impl IterativeStrategy {
                     new_todo.extend(todo_list);
                     
                     return Ok(Some((state.clone(), new_todo, plan, depth + 1)));
-                } else if is_verbose(3) {
-                    verbose_print(3, "not applicable");
+                } else if context.is_verbose(3) {
+                    context.verbose_print(3, "not applicable");
                 }
             }
         }
 
-        if is_verbose(3) {
-            verbose_print(3, &format!("depth {depth} could not achieve goal ({} {} {})", unigoal_params.var_name, unigoal_params.arg, unigoal_params.value));
+        if context.is_verbose(3) {
+            context.verbose_print(3, &format!("depth {depth} could not achieve goal ({} {} {})", unigoal_params.var_name, unigoal_params.arg, unigoal_params.value));
         }
         Ok(None)
     }
@@ -564,24 +582,24 @@ impl IterativeStrategy {
         plan: Plan,
         depth: usize,
     ) -> Result<Option<(State, TodoList, Plan, usize)>> {
-        if is_verbose(3) {
-            verbose_print(3, &format!("depth {depth} multigoal {multigoal}: "));
+        if context.is_verbose(3) {
+            context.verbose_print(3, &format!("depth {depth} multigoal {multigoal}: "));
         }
 
-        let methods = context.domain.get_multigoal_methods();
-        if is_verbose(3) {
-            verbose_print(3, &format!("methods: {} methods", methods.len()));
+        let methods = context.domain.relevant_multigoal_methods(multigoal);
+        if context.is_verbose(3) {
+            context.verbose_print(3, &format!("methods: {} methods", methods.len()));
         }
 
         for method in methods {
-            if is_verbose(3) {
-                verbose_print(3, &format!("depth {depth} trying method: "));
+            if context.is_verbose(3) {
+                context.verbose_print(3, &format!("depth {depth} trying method: "));
             }
 
             if let Some(subgoals) = method(state, multigoal) {
-                if is_verbose(3) {
-                    verbose_print(3, "applicable");
-                    verbose_print(3, &format!("depth {} subgoals: {}", depth, todo_list_to_string(&subgoals)));
+                if context.is_verbose(3) {
+                    context.verbose_print(3, "applicable");
+                    context.verbose_print(3, &format!("depth {} subgoals: {}", depth, todo_list_to_string(&subgoals)));
                 }
 
                 let mut new_todo = subgoals;
@@ -599,13 +617,13 @@ impl IterativeStrategy {
                 new_todo.extend(todo_list);
                 
                 return Ok(Some((state.clone(), new_todo, plan, depth + 1)));
-            } else if is_verbose(3) {
-                verbose_print(3, "not applicable");
+            } else if context.is_verbose(3) {
+                context.verbose_print(3, "not applicable");
             }
         }
 
-        if is_verbose(3) {
-            verbose_print(3, &format!("depth {depth} could not achieve multigoal {multigoal}"));
+        if context.is_verbose(3) {
+            context.verbose_print(3, &format!("depth {depth} could not achieve multigoal {multigoal}"));
         }
         Ok(None)
     }
@@ -0,0 +1,35 @@
+//! Search event logs for GTRusthop planner instances
+
+use crate::compat::prelude::*;
+
+/// What happened at a step of the search, see `SearchEvent`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SearchEventKind {
+    /// A node was added to the nodes left to explore
+    Push,
+    /// A node was taken up for expansion
+    Pop,
+    /// An action was applied
+    ApplyAction,
+    /// A method was called on a task or goal
+    TryMethod,
+    /// The method returned a decomposition
+    MethodApplicable,
+    /// The method did not apply
+    MethodRejected,
+    /// A node could not be refined, so the search goes back to an earlier choice
+    Backtrack,
+    /// A plan was found
+    Success,
+}
+
+/// A step of the search, recorded by `Planner::find_plan_trace()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchEvent {
+    /// What happened
+    pub kind: SearchEventKind,
+    /// Depth of the node it happened at
+    pub depth: usize,
+    /// The todo list item, method or action involved
+    pub description: String,
+}
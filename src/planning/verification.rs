@@ -1,7 +1,10 @@
 //! Goal verification functions for GTRusthop
 
+use crate::compat::prelude::*;
+use crate::compat::HashMap;
 use crate::core::{State, Multigoal, StateValue, TodoList};
 use crate::error::{GTRustHopError, Result};
+#[cfg(feature = "std")]
 use super::{is_verbose, verbose_print};
 
 /// Verify that a unigoal method achieved its goal
@@ -21,6 +24,7 @@ pub fn verify_unigoal(
         ));
     }
     
+    #[cfg(feature = "std")]
     if is_verbose(3) {
         verbose_print(3, &format!(
             "depth {depth}: method {method_name} achieved goal {var_name}[{arg}] = {desired_value}"
@@ -47,6 +51,7 @@ pub fn verify_multigoal(
         ));
     }
     
+    #[cfg(feature = "std")]
     if is_verbose(3) {
         verbose_print(3, &format!(
             "depth {depth}: method {method_name} achieved {multigoal}"
@@ -57,7 +62,7 @@ pub fn verify_multigoal(
 }
 
 /// Check which goals in a multigoal are not achieved by the current state
-pub fn goals_not_achieved(state: &State, multigoal: &Multigoal) -> std::collections::HashMap<String, std::collections::HashMap<String, StateValue>> {
+pub fn goals_not_achieved(state: &State, multigoal: &Multigoal) -> HashMap<String, HashMap<String, StateValue>> {
     multigoal.unsatisfied_goals(state)
 }
 